@@ -565,7 +565,7 @@ fn run_compile(opts: &Options, compile_path: &str, result: &mut ActoolResult) {
         };
 
         // Collect image references from the catalog
-        collect_asset_files(&asset, input, &mut all_images);
+        collect_asset_files(&asset, &mut all_images);
     }
 
     // For now, copy image files to the output directory
@@ -639,11 +639,7 @@ fn run_compile(opts: &Options, compile_path: &str, result: &mut ActoolResult) {
         .push(car_path.to_string_lossy().to_string());
 }
 
-fn collect_asset_files(
-    asset: &xcbuild_xcassets::Asset,
-    catalog_path: &str,
-    images: &mut HashMap<String, Vec<String>>,
-) {
+fn collect_asset_files(asset: &xcbuild_xcassets::Asset, images: &mut HashMap<String, Vec<String>>) {
     if let Some(contents) = &asset.contents {
         if let Some(imgs) = &contents.images {
             for img in imgs {
@@ -670,6 +666,6 @@ fn collect_asset_files(
     }
 
     for child in &asset.children {
-        collect_asset_files(child, catalog_path, images);
+        collect_asset_files(child, images);
     }
 }
@@ -0,0 +1,36 @@
+//! Checksums of files on disk, as recorded in the `PathInfo2` of each
+//! path.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Simple CRC32 (IEEE/ISO 3309), continuing from a previous `crc`.
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xEDB88320;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    !crc
+}
+
+/// CRC32 of the contents of the file at `path`.
+pub fn crc32_of_file(path: &Path) -> io::Result<u32> {
+    let mut file = fs::File::open(path)?;
+    let mut crc = 0;
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            return Ok(crc);
+        }
+        crc = crc32_update(crc, &buffer[..n]);
+    }
+}
@@ -1,6 +1,12 @@
 use byteorder::{BigEndian, ByteOrder};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
 use thiserror::Error;
 
+pub mod files;
+mod tree;
+pub use tree::{BomTree, BomTreeIter};
+
 #[derive(Error, Debug)]
 pub enum BomError {
     #[error("file too small")]
@@ -34,6 +40,9 @@ const TREE_HEADER_SIZE: usize = 21;
 const TREE_ENTRY_HEADER_SIZE: usize = 12;
 const TREE_ENTRY_INDEX_SIZE: usize = 8;
 
+/// Page size of trees written by [`BomWriter::build_tree`].
+pub const DEFAULT_BLOCK_SIZE: u32 = 4096;
+
 /// A read-only BOM archive context.
 #[derive(Debug, Clone)]
 pub struct Bom {
@@ -191,100 +200,35 @@ impl Bom {
             .map(|v| v.index)
     }
 
-    pub fn tree_entries(&self, variable_name: &str) -> Result<Vec<BomTreeEntry>, BomError> {
+    /// Open the tree named by a variable.
+    pub fn tree(&self, variable_name: &str) -> Result<BomTree<'_>, BomError> {
         let tree_index = self
             .variable_get(variable_name)
             .ok_or_else(|| BomError::TreeNotFound(variable_name.to_string()))?;
+        self.tree_at(tree_index)
+    }
 
-        let tree_data = self
-            .index_get(tree_index)
-            .ok_or(BomError::IndexOutOfRange(tree_index))?;
-
-        if tree_data.len() < TREE_HEADER_SIZE {
-            return Err(BomError::InvalidTreeMagic);
-        }
-
-        if &tree_data[0..4] != TREE_MAGIC {
-            return Err(BomError::InvalidTreeMagic);
-        }
-
-        let tree_version = BigEndian::read_u32(&tree_data[4..8]);
-        if tree_version != 1 {
-            return Err(BomError::InvalidTreeVersion);
-        }
-
-        let child_index = BigEndian::read_u32(&tree_data[8..12]);
+    /// Open the tree stored in a block.
+    pub fn tree_at(&self, index: u32) -> Result<BomTree<'_>, BomError> {
+        BomTree::load(self, index)
+    }
 
-        let mut entries = Vec::new();
-        self.collect_tree_entries(child_index, &mut entries)?;
-        Ok(entries)
+    /// Copy out every entry of a tree; see [`Bom::tree`] for lazy access.
+    pub fn tree_entries(&self, variable_name: &str) -> Result<Vec<BomTreeEntry>, BomError> {
+        self.tree(variable_name)?
+            .iter()
+            .map(|entry| {
+                entry.map(|(key, value)| BomTreeEntry {
+                    key: key.to_vec(),
+                    value: value.to_vec(),
+                })
+            })
+            .collect()
     }
 
     /// Check if a variable holds a valid tree.
     pub fn is_tree(&self, variable_index: u32) -> bool {
-        if let Some(data) = self.index_get(variable_index) {
-            if data.len() >= TREE_HEADER_SIZE && &data[0..4] == TREE_MAGIC {
-                let version = BigEndian::read_u32(&data[4..8]);
-                return version == 1;
-            }
-        }
-        false
-    }
-
-    fn collect_tree_entries(
-        &self,
-        entry_index: u32,
-        entries: &mut Vec<BomTreeEntry>,
-    ) -> Result<(), BomError> {
-        let entry_data = self
-            .index_get(entry_index)
-            .ok_or(BomError::IndexOutOfRange(entry_index))?;
-
-        if entry_data.len() < TREE_ENTRY_HEADER_SIZE {
-            return Err(BomError::DataOutOfBounds);
-        }
-
-        let is_leaf = BigEndian::read_u16(&entry_data[0..2]);
-        let count = BigEndian::read_u16(&entry_data[2..4]) as usize;
-        let forward = BigEndian::read_u32(&entry_data[4..8]);
-
-        if is_leaf == 0 {
-            // Non-leaf: follow the first child's value_index
-            if count > 0 && entry_data.len() >= TREE_ENTRY_HEADER_SIZE + TREE_ENTRY_INDEX_SIZE {
-                let value_index = BigEndian::read_u32(
-                    &entry_data[TREE_ENTRY_HEADER_SIZE..TREE_ENTRY_HEADER_SIZE + 4],
-                );
-                self.collect_tree_entries(value_index, entries)?;
-            }
-        } else {
-            // Leaf: collect all key/value entries
-            for i in 0..count {
-                let idx_offset = TREE_ENTRY_HEADER_SIZE + i * TREE_ENTRY_INDEX_SIZE;
-                if idx_offset + TREE_ENTRY_INDEX_SIZE > entry_data.len() {
-                    break;
-                }
-
-                let value_index =
-                    BigEndian::read_u32(&entry_data[idx_offset..idx_offset + 4]);
-                let key_index =
-                    BigEndian::read_u32(&entry_data[idx_offset + 4..idx_offset + 8]);
-
-                let key_data = self.index_get(key_index).unwrap_or(&[]);
-                let value_data = self.index_get(value_index).unwrap_or(&[]);
-
-                entries.push(BomTreeEntry {
-                    key: key_data.to_vec(),
-                    value: value_data.to_vec(),
-                });
-            }
-
-            // Follow forward pointer to next leaf
-            if forward != 0 {
-                self.collect_tree_entries(forward, entries)?;
-            }
-        }
-
-        Ok(())
+        self.tree_at(variable_index).is_ok()
     }
 }
 
@@ -318,6 +262,42 @@ pub mod paths {
         pub size: u32,
         pub checksum: u32,
         pub link_name: String,
+        /// One record per architecture of a Mach-O file, stored after the
+        /// link name.
+        pub binary_info: Vec<ArchInfo>,
+    }
+
+    /// Size and checksum of one architecture slice of a Mach-O file.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ArchInfo {
+        pub cpu_type: u32,
+        pub cpu_subtype: u32,
+        pub size: u32,
+        pub checksum: u32,
+    }
+
+    /// `PathInfo2::architecture` of a fat (universal) binary; thin binaries
+    /// store the low 16 bits of their CPU type, which drops the 64-bit ABI
+    /// flag (arm64 and arm both store 12).
+    ///
+    /// This encoding, like the `binary_info` layout, follows the published
+    /// reverse-engineered descriptions of the format and has not been
+    /// checked against a BOM written by Apple's tools, so it is only read:
+    /// mkbom and pkgbuild leave `architecture` 0 and `binary_info` empty.
+    pub const ARCH_FAT: u16 = 0xFFFF;
+
+    /// Size of one `ArchInfo` record.
+    const ARCH_INFO_SIZE: usize = 16;
+
+    /// One path written by [`BomWriter::add_paths`](crate::BomWriter::add_paths).
+    #[derive(Debug, Clone)]
+    pub struct PathEntry {
+        /// Path relative to the root, as `.` or `./name`. Parents come
+        /// before their children.
+        pub path: String,
+        pub info: PathInfo2,
+        /// Full size of the file; sizes over 4 GiB also go to `Size64`.
+        pub size: u64,
     }
 
     #[derive(Debug, Clone)]
@@ -355,9 +335,11 @@ pub mod paths {
             } else {
                 0
             };
+            let mut link_end = 31;
             let link_name = if data.len() >= 31 {
                 let link_len = BigEndian::read_u32(&data[27..31]) as usize;
                 if data.len() >= 31 + link_len {
+                    link_end += link_len;
                     String::from_utf8_lossy(&data[31..31 + link_len]).to_string()
                 } else {
                     String::new()
@@ -366,6 +348,20 @@ pub mod paths {
                 String::new()
             };
 
+            let mut binary_info = Vec::new();
+            if data.len() >= link_end + 4 {
+                let count = BigEndian::read_u32(&data[link_end..link_end + 4]) as usize;
+                let records = &data[link_end + 4..];
+                for record in records.chunks_exact(ARCH_INFO_SIZE).take(count) {
+                    binary_info.push(ArchInfo {
+                        cpu_type: BigEndian::read_u32(&record[0..4]),
+                        cpu_subtype: BigEndian::read_u32(&record[4..8]),
+                        size: BigEndian::read_u32(&record[8..12]),
+                        checksum: BigEndian::read_u32(&record[12..16]),
+                    });
+                }
+            }
+
             Some(PathInfo2 {
                 path_type,
                 architecture,
@@ -376,6 +372,7 @@ pub mod paths {
                 size,
                 checksum,
                 link_name,
+                binary_info,
             })
         }
 
@@ -403,6 +400,34 @@ pub mod paths {
         }
     }
 
+    /// A `Size64` tree entry: the 64-bit size of the path with the given
+    /// ID, for files whose `PathInfo2::size` overflowed.
+    pub fn size64_entry(id: u32, size: u64) -> (Vec<u8>, Vec<u8>) {
+        (id.to_be_bytes().to_vec(), size.to_be_bytes().to_vec())
+    }
+
+    pub fn parse_size64(key: &[u8], value: &[u8]) -> Option<(u32, u64)> {
+        if key.len() < 4 || value.len() < 8 {
+            return None;
+        }
+        Some((BigEndian::read_u32(&key[0..4]), BigEndian::read_u64(&value[0..8])))
+    }
+
+    /// An `HLIndex` tree entry for a group of hard links: keyed by the ID
+    /// of the first path, with the IDs of every path in the group. Like
+    /// [`ARCH_FAT`], this layout is unverified against Apple's tools, so
+    /// [`BomWriter::add_paths`](crate::BomWriter::add_paths) leaves
+    /// `HLIndex` empty.
+    pub fn hard_link_entry(ids: &[u32]) -> (Vec<u8>, Vec<u8>) {
+        let key = ids.first().copied().unwrap_or(0).to_be_bytes().to_vec();
+        let value = ids.iter().flat_map(|id| id.to_be_bytes()).collect();
+        (key, value)
+    }
+
+    pub fn parse_hard_link(value: &[u8]) -> Vec<u32> {
+        value.chunks_exact(4).map(BigEndian::read_u32).collect()
+    }
+
     /// Resolve the full path for a file by walking up the parent chain.
     pub fn resolve_path(
         file_key: &FileKey,
@@ -410,20 +435,28 @@ pub mod paths {
     ) -> String {
         let mut path = file_key.name.clone();
         let mut next = file_key.parent;
-        while let Some((parent, name)) = files.get(&next) {
-            path = format!("{name}/{path}");
-            next = *parent;
+        // Bounded so a parent cycle in a corrupt BOM can't loop forever.
+        for _ in 0..files.len() {
+            match files.get(&next) {
+                Some((parent, name)) => {
+                    path = format!("{name}/{path}");
+                    next = *parent;
+                }
+                None => break,
+            }
         }
         path
     }
 }
 
-/// A BOM writer for creating new BOM archives.
+/// A BOM writer for creating new BOM archives or editing existing ones.
 pub struct BomWriter {
     /// Stored data blocks (index 0 is unused/null).
     blocks: Vec<Vec<u8>>,
     /// Named variables mapping to block indices.
     variables: Vec<(String, u32)>,
+    /// Indices of freed blocks, reused lowest first by `add_block`.
+    free: BTreeSet<u32>,
 }
 
 impl BomWriter {
@@ -432,91 +465,339 @@ impl BomWriter {
         BomWriter {
             blocks: vec![vec![]],
             variables: Vec::new(),
+            free: BTreeSet::new(),
         }
     }
 
-    /// Add a data block and return its index.
+    /// Start from the blocks and variables of an existing BOM, keeping
+    /// every block at its index so references between blocks stay valid.
+    pub fn from_bom(bom: &Bom) -> Self {
+        let mut writer = BomWriter::new();
+        for (index, entry) in bom.indices() {
+            if index == 0 {
+                continue;
+            }
+            match bom.index_get(index) {
+                Some(data) if entry.address != 0 => writer.blocks.push(data.to_vec()),
+                _ => {
+                    writer.blocks.push(Vec::new());
+                    writer.free.insert(index);
+                }
+            }
+        }
+        writer.variables = bom.variables().into_iter().map(|v| (v.name, v.index)).collect();
+        writer
+    }
+
+    /// Add a data block and return its index. Freed indices are reused.
     pub fn add_block(&mut self, data: Vec<u8>) -> u32 {
+        if let Some(idx) = self.free.pop_first() {
+            self.blocks[idx as usize] = data;
+            return idx;
+        }
         let idx = self.blocks.len() as u32;
         self.blocks.push(data);
         idx
     }
 
+    /// The data of a block that is in use.
+    pub fn block(&self, index: u32) -> Option<&[u8]> {
+        if index == 0 || self.free.contains(&index) {
+            return None;
+        }
+        self.blocks.get(index as usize).map(|b| b.as_slice())
+    }
+
+    /// Replace the data of a block that is in use.
+    pub fn replace_block(&mut self, index: u32, data: Vec<u8>) -> Result<(), BomError> {
+        if self.block(index).is_none() {
+            return Err(BomError::IndexOutOfRange(index));
+        }
+        self.blocks[index as usize] = data;
+        Ok(())
+    }
+
+    /// Release a block; its index may be reused by a later `add_block`.
+    pub fn free_block(&mut self, index: u32) -> Result<(), BomError> {
+        if self.block(index).is_none() {
+            return Err(BomError::IndexOutOfRange(index));
+        }
+        self.blocks[index as usize] = Vec::new();
+        self.free.insert(index);
+        Ok(())
+    }
+
     /// Add a named variable pointing to a block index.
     pub fn add_variable(&mut self, name: &str, index: u32) {
         self.variables.push((name.to_string(), index));
     }
 
-    /// Build a BOM tree from key-value pairs and return the block index of the tree root.
-    pub fn build_tree(&mut self, entries: &[(Vec<u8>, Vec<u8>)]) -> u32 {
-        // Store each key and value as separate blocks
-        // Build leaf nodes containing entry indices
-
-        const ENTRIES_PER_LEAF: usize = 256;
+    /// The block index a variable points to.
+    pub fn variable(&self, name: &str) -> Option<u32> {
+        self.variables.iter().find(|(n, _)| n == name).map(|(_, i)| *i)
+    }
 
-        let mut leaf_indices: Vec<u32> = Vec::new();
+    /// Point a variable at a block, adding it if it doesn't exist.
+    pub fn set_variable(&mut self, name: &str, index: u32) {
+        match self.variables.iter_mut().find(|(n, _)| n == name) {
+            Some(variable) => variable.1 = index,
+            None => self.add_variable(name, index),
+        }
+    }
 
-        for chunk in entries.chunks(ENTRIES_PER_LEAF) {
-            let count = chunk.len() as u16;
-            let is_leaf: u16 = 1;
-            let forward: u32 = 0; // will be patched below
+    /// Remove a variable, returning the block index it pointed to.
+    pub fn remove_variable(&mut self, name: &str) -> Option<u32> {
+        let position = self.variables.iter().position(|(n, _)| n == name)?;
+        Some(self.variables.remove(position).1)
+    }
 
-            let mut leaf_data = Vec::new();
-            leaf_data.extend_from_slice(&is_leaf.to_be_bytes());
-            leaf_data.extend_from_slice(&count.to_be_bytes());
-            leaf_data.extend_from_slice(&forward.to_be_bytes());
-            // 4 bytes padding (backward pointer)
-            leaf_data.extend_from_slice(&0u32.to_be_bytes());
+    /// Free a tree: its header, its pages and the key and value blocks of
+    /// its entries. Blocks that values refer to are left to the caller.
+    /// Leaves are also followed through their forward pointers, which is
+    /// the only way to reach them in trees that chain every leaf from the
+    /// first one.
+    pub fn free_tree(&mut self, index: u32) -> Result<(), BomError> {
+        let header = self.block(index).ok_or(BomError::IndexOutOfRange(index))?;
+        if header.len() < TREE_HEADER_SIZE || &header[0..4] != TREE_MAGIC {
+            return Err(BomError::InvalidTreeMagic);
+        }
 
-            for (key, value) in chunk {
-                let value_idx = self.add_block(value.clone());
-                let key_idx = self.add_block(key.clone());
-                leaf_data.extend_from_slice(&value_idx.to_be_bytes());
-                leaf_data.extend_from_slice(&key_idx.to_be_bytes());
+        let mut blocks = BTreeSet::from([index]);
+        let mut pages = vec![BigEndian::read_u32(&header[8..12])];
+        while let Some(page) = pages.pop() {
+            if !blocks.insert(page) {
+                continue;
+            }
+            let data = self.block(page).ok_or(BomError::IndexOutOfRange(page))?;
+            if data.len() < TREE_ENTRY_HEADER_SIZE {
+                return Err(BomError::DataOutOfBounds);
+            }
+            let is_leaf = BigEndian::read_u16(&data[0..2]) != 0;
+            let count = BigEndian::read_u16(&data[2..4]) as usize;
+            if data.len() < TREE_ENTRY_HEADER_SIZE + count * TREE_ENTRY_INDEX_SIZE {
+                return Err(BomError::DataOutOfBounds);
+            }
+            let forward = BigEndian::read_u32(&data[4..8]);
+            if is_leaf && forward != 0 {
+                pages.push(forward);
+            }
+            for i in 0..count {
+                let offset = TREE_ENTRY_HEADER_SIZE + i * TREE_ENTRY_INDEX_SIZE;
+                let value = BigEndian::read_u32(&data[offset..offset + 4]);
+                let key = BigEndian::read_u32(&data[offset + 4..offset + 8]);
+                if is_leaf {
+                    blocks.insert(value);
+                } else {
+                    pages.push(value);
+                }
+                blocks.insert(key);
             }
+        }
 
-            let leaf_idx = self.add_block(leaf_data);
-            leaf_indices.push(leaf_idx);
+        for block in blocks {
+            if self.block(block).is_some() {
+                self.free_block(block)?;
+            }
         }
+        Ok(())
+    }
 
-        // Patch forward pointers in leaf nodes
-        for i in 0..leaf_indices.len() - 1 {
-            let next_idx = leaf_indices[i + 1];
-            let leaf = &mut self.blocks[leaf_indices[i] as usize];
-            // Forward pointer is at offset 4..8
-            leaf[4..8].copy_from_slice(&next_idx.to_be_bytes());
+    /// Rebuild the tree named by a variable from new entries, freeing the
+    /// old one, and re-point the variable. Returns the new tree's index.
+    pub fn replace_tree(
+        &mut self,
+        name: &str,
+        entries: &[(Vec<u8>, Vec<u8>)],
+        block_size: u32,
+        compare: impl Fn(&[u8], &[u8]) -> Ordering,
+    ) -> Result<u32, BomError> {
+        if let Some(old) = self.variable(name) {
+            self.free_tree(old)?;
         }
+        let tree = self.build_tree_with(entries, block_size, compare);
+        self.set_variable(name, tree);
+        Ok(tree)
+    }
 
-        let child_index = if leaf_indices.is_empty() {
-            // Empty tree: create an empty leaf
-            let mut leaf_data = Vec::new();
-            leaf_data.extend_from_slice(&1u16.to_be_bytes()); // is_leaf
-            leaf_data.extend_from_slice(&0u16.to_be_bytes()); // count
-            leaf_data.extend_from_slice(&0u32.to_be_bytes()); // forward
-            leaf_data.extend_from_slice(&0u32.to_be_bytes()); // backward
-            self.add_block(leaf_data)
-        } else {
-            leaf_indices[0]
-        };
+    /// Build a BOM tree from key-value pairs and return the block index of
+    /// the tree header. Keys are sorted bytewise and pages are
+    /// [`DEFAULT_BLOCK_SIZE`] bytes.
+    pub fn build_tree(&mut self, entries: &[(Vec<u8>, Vec<u8>)]) -> u32 {
+        self.build_tree_with(entries, DEFAULT_BLOCK_SIZE, |a, b| a.cmp(b))
+    }
 
-        let entry_count = entries.len() as u32;
+    /// Build a balanced B+tree with pages of `block_size` bytes, keys
+    /// ordered by `compare`. Leaves are linked both ways; each branch entry
+    /// points at a child page and holds the last key under it.
+    pub fn build_tree_with(
+        &mut self,
+        entries: &[(Vec<u8>, Vec<u8>)],
+        block_size: u32,
+        compare: impl Fn(&[u8], &[u8]) -> Ordering,
+    ) -> u32 {
+        let capacity = ((block_size as usize).saturating_sub(TREE_ENTRY_HEADER_SIZE) / TREE_ENTRY_INDEX_SIZE)
+            .clamp(2, u16::MAX as usize);
+
+        let mut sorted: Vec<&(Vec<u8>, Vec<u8>)> = entries.iter().collect();
+        sorted.sort_by(|a, b| compare(&a.0, &b.0));
+
+        // (value or child, key) block indices for the level being built.
+        let mut level: Vec<(u32, u32)> = sorted
+            .iter()
+            .map(|(key, value)| {
+                let value_idx = self.add_block(value.clone());
+                let key_idx = self.add_block(key.clone());
+                (value_idx, key_idx)
+            })
+            .collect();
+
+        let mut is_leaf = true;
+        let root = loop {
+            let pages = self.build_tree_level(&level, capacity, block_size, is_leaf);
+            if pages.len() == 1 {
+                break pages[0].0;
+            }
+            level = pages;
+            is_leaf = false;
+        };
 
-        // Build tree header
         let mut tree_data = Vec::new();
         tree_data.extend_from_slice(TREE_MAGIC);
         tree_data.extend_from_slice(&1u32.to_be_bytes()); // version
-        tree_data.extend_from_slice(&child_index.to_be_bytes());
-        // block size (unused, set to 4096)
-        tree_data.extend_from_slice(&4096u32.to_be_bytes());
-        // path count
-        tree_data.extend_from_slice(&entry_count.to_be_bytes());
-        // unknown byte
-        tree_data.push(0);
+        tree_data.extend_from_slice(&root.to_be_bytes());
+        tree_data.extend_from_slice(&block_size.to_be_bytes());
+        tree_data.extend_from_slice(&(entries.len() as u32).to_be_bytes()); // path count
+        tree_data.push(0); // unknown byte
 
         self.add_block(tree_data)
     }
 
-    /// Serialize the BOM to bytes.
+    /// Write the `Paths`, `HLIndex`, `Size64` and `VIndex` variables that
+    /// describe a file tree, with an empty `HLIndex`. IDs start at 1 in the
+    /// order of `entries`. Parents that are not listed, including the root
+    /// `.`, are added after them as directories owned by 0/0 with mode 0755.
+    pub fn add_paths(&mut self, entries: &[paths::PathEntry]) {
+        let mut path_ids: HashMap<String, u32> =
+            (1u32..).zip(entries).map(|(id, entry)| (entry.path.clone(), id)).collect();
+        let mut implied: Vec<String> = Vec::new();
+        let directory = paths::PathInfo2 {
+            path_type: 2,
+            architecture: 0,
+            mode: 0o755,
+            user: 0,
+            group: 0,
+            modtime: 0,
+            size: 0,
+            checksum: 0,
+            link_name: String::new(),
+            binary_info: Vec::new(),
+        };
+
+        let mut tree_entries: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+        let mut size64_entries: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+
+        // Implied directories are appended while this runs. 0 is the parent
+        // of the root.
+        let mut i = 0;
+        while i < entries.len() + implied.len() {
+            let id = i as u32 + 1;
+            let entry = entries.get(i);
+            let path = entry.map_or_else(|| implied[i - entries.len()].clone(), |e| e.path.clone());
+            i += 1;
+
+            let (parent, name) = match path.rsplit_once('/') {
+                _ if path == "." => (None, "."),
+                Some((parent, name)) if !parent.is_empty() => (Some(parent), name),
+                Some((_, name)) => (Some("."), name),
+                None => (Some("."), path.as_str()),
+            };
+            let parent = match parent {
+                None => 0,
+                Some(parent) => match path_ids.get(parent) {
+                    Some(&parent_id) => parent_id,
+                    None => {
+                        let parent_id = (entries.len() + implied.len()) as u32 + 1;
+                        path_ids.insert(parent.to_string(), parent_id);
+                        implied.push(parent.to_string());
+                        parent_id
+                    }
+                },
+            };
+
+            let info = entry.map_or(&directory, |e| &e.info);
+            if let Some(entry) = entry.filter(|e| e.size > u32::MAX as u64) {
+                size64_entries.push(paths::size64_entry(id, entry.size));
+            }
+
+            let index = self.add_block(info.to_bytes());
+            let file_key = paths::FileKey {
+                parent,
+                name: name.to_string(),
+            };
+            tree_entries.push((file_key.to_bytes(), paths::PathInfo1 { id, index }.to_bytes()));
+        }
+
+        let tree_idx = self.build_tree(&tree_entries);
+        self.add_variable("Paths", tree_idx);
+
+        let hlindex_idx = self.build_tree(&[]);
+        self.add_variable("HLIndex", hlindex_idx);
+
+        // Sizes of files over 4 GiB
+        let size64_idx = self.build_tree(&size64_entries);
+        self.add_variable("Size64", size64_idx);
+
+        // VIndex - a block with the entry count
+        let vindex_idx = self.add_block((tree_entries.len() as u32).to_be_bytes().to_vec());
+        self.add_variable("VIndex", vindex_idx);
+    }
+
+    /// Split one tree level into evenly filled pages and return a
+    /// (page, last key) pair for each, to become the level above.
+    fn build_tree_level(
+        &mut self,
+        entries: &[(u32, u32)],
+        capacity: usize,
+        block_size: u32,
+        is_leaf: bool,
+    ) -> Vec<(u32, u32)> {
+        let page_count = entries.len().div_ceil(capacity).max(1);
+        let mut pages: Vec<(u32, u32)> = Vec::with_capacity(page_count);
+        let mut start = 0;
+        for page in 0..page_count {
+            let end = start + (entries.len() - start) / (page_count - page);
+            let chunk = &entries[start..end];
+            start = end;
+
+            let mut data = Vec::with_capacity(block_size as usize);
+            data.extend_from_slice(&(is_leaf as u16).to_be_bytes());
+            data.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+            data.extend_from_slice(&0u32.to_be_bytes()); // forward, patched below
+            let backward = if is_leaf { pages.last().map_or(0, |p| p.0) } else { 0 };
+            data.extend_from_slice(&backward.to_be_bytes());
+            for (value, key) in chunk {
+                data.extend_from_slice(&value.to_be_bytes());
+                data.extend_from_slice(&key.to_be_bytes());
+            }
+            if data.len() < block_size as usize {
+                data.resize(block_size as usize, 0);
+            }
+
+            let last_key = chunk.last().map_or(0, |e| e.1);
+            pages.push((self.add_block(data), last_key));
+        }
+
+        if is_leaf {
+            for pair in pages.windows(2) {
+                let next = pair[1].0;
+                self.blocks[pair[0].0 as usize][4..8].copy_from_slice(&next.to_be_bytes());
+            }
+        }
+        pages
+    }
+
+    /// Serialize the BOM to bytes. Blocks are packed without gaps; freed
+    /// blocks get a null index entry and trailing ones are dropped.
     pub fn serialize(&self) -> Vec<u8> {
         // Calculate layout
         // Header: 32 bytes
@@ -524,13 +805,24 @@ impl BomWriter {
         // Then index table
         // Then variables table
 
-        let num_blocks = self.blocks.len() as u32;
+        let is_free = |index: usize| self.free.contains(&(index as u32));
+        let table_len = (1..self.blocks.len())
+            .rev()
+            .find(|&i| !is_free(i))
+            .map_or(1, |i| i + 1);
+        let blocks = &self.blocks[..table_len];
+        let num_blocks = table_len as u32;
+        let used_blocks = num_blocks - (1..table_len).filter(|&i| is_free(i)).count() as u32;
 
         // Calculate data block positions (starting after header)
         let mut block_offsets: Vec<(u32, u32)> = Vec::new(); // (offset, length)
         let mut data_offset: u32 = HEADER_SIZE as u32;
 
-        for block in &self.blocks {
+        for (i, block) in blocks.iter().enumerate() {
+            if is_free(i) {
+                block_offsets.push((0, 0));
+                continue;
+            }
             block_offsets.push((data_offset, block.len() as u32));
             data_offset += block.len() as u32;
         }
@@ -558,14 +850,14 @@ impl BomWriter {
         // Header (32 bytes)
         output.extend_from_slice(BOM_MAGIC); // magic
         output.extend_from_slice(&1u32.to_be_bytes()); // version
-        output.extend_from_slice(&num_blocks.to_be_bytes()); // block count
+        output.extend_from_slice(&used_blocks.to_be_bytes()); // block count
         output.extend_from_slice(&index_offset.to_be_bytes()); // index offset
         output.extend_from_slice(&index_size.to_be_bytes()); // index length
         output.extend_from_slice(&vars_offset.to_be_bytes()); // variables offset
         output.extend_from_slice(&(vars_data.len() as u32).to_be_bytes()); // variables length
 
-        // Data blocks
-        for block in &self.blocks {
+        // Data blocks (freed ones are empty)
+        for block in blocks {
             output.extend_from_slice(block);
         }
 
@@ -614,6 +906,15 @@ impl paths::PathInfo2 {
         let link_bytes = self.link_name.as_bytes();
         data.extend_from_slice(&(link_bytes.len() as u32).to_be_bytes());
         data.extend_from_slice(link_bytes);
+        if !self.binary_info.is_empty() {
+            data.extend_from_slice(&(self.binary_info.len() as u32).to_be_bytes());
+            for arch in &self.binary_info {
+                data.extend_from_slice(&arch.cpu_type.to_be_bytes());
+                data.extend_from_slice(&arch.cpu_subtype.to_be_bytes());
+                data.extend_from_slice(&arch.size.to_be_bytes());
+                data.extend_from_slice(&arch.checksum.to_be_bytes());
+            }
+        }
         data
     }
 }
@@ -664,7 +965,15 @@ mod tests {
             size: 100,
             checksum: 0,
             link_name: String::new(),
+            binary_info: vec![paths::ArchInfo {
+                cpu_type: 0x0100000C,
+                cpu_subtype: 0,
+                size: 100,
+                checksum: 42,
+            }],
         };
+        let parsed = paths::PathInfo2::from_bytes(&info2.to_bytes()).unwrap();
+        assert_eq!(parsed.binary_info, info2.binary_info);
 
         let info2_idx = writer.add_block(info2.to_bytes());
         let info1_with_index = paths::PathInfo1 {
@@ -682,4 +991,177 @@ mod tests {
         let read_entries = bom.tree_entries("Paths").expect("should read Paths tree");
         assert_eq!(read_entries.len(), 1);
     }
+
+    #[test]
+    fn test_add_paths_implies_missing_parents() {
+        // As in `lsbom -f` output: no root and no `./a` line.
+        let file = |path: &str, size: u64| paths::PathEntry {
+            path: path.to_string(),
+            info: paths::PathInfo2 {
+                path_type: 1,
+                architecture: 0,
+                mode: 0o644,
+                user: 0,
+                group: 0,
+                modtime: 0,
+                size: u32::try_from(size).unwrap_or(u32::MAX),
+                checksum: 0,
+                link_name: String::new(),
+                binary_info: Vec::new(),
+            },
+            size,
+        };
+        let mut writer = BomWriter::new();
+        writer.add_paths(&[file("./x", 1), file("./a/b", 5 << 30)]);
+        let bom = Bom::load(writer.serialize()).unwrap();
+
+        let mut names = std::collections::HashMap::new();
+        let mut keys = Vec::new();
+        for entry in bom.tree_entries("Paths").unwrap() {
+            let key = paths::FileKey::from_bytes(&entry.key).unwrap();
+            let value = paths::PathInfo1::from_bytes(&entry.value).unwrap();
+            let info = paths::PathInfo2::from_bytes(bom.index_get(value.index).unwrap()).unwrap();
+            names.insert(value.id, (key.parent, key.name.clone()));
+            keys.push((value.id, key, info.path_type()));
+        }
+        let mut resolved: Vec<(u32, String, paths::PathType)> = keys
+            .iter()
+            .map(|(id, key, path_type)| (*id, paths::resolve_path(key, &names), *path_type))
+            .collect();
+        resolved.sort_by_key(|(id, _, _)| *id);
+        assert_eq!(
+            resolved,
+            vec![
+                (1, "./x".to_string(), paths::PathType::File),
+                (2, "./a/b".to_string(), paths::PathType::File),
+                (3, ".".to_string(), paths::PathType::Directory),
+                (4, "./a".to_string(), paths::PathType::Directory),
+            ]
+        );
+
+        let size64 = bom.tree_entries("Size64").unwrap();
+        assert_eq!(paths::parse_size64(&size64[0].key, &size64[0].value), Some((2, 5 << 30)));
+        let vindex = bom.index_get(bom.variable_get("VIndex").unwrap()).unwrap();
+        assert_eq!(BigEndian::read_u32(vindex), 4);
+    }
+
+    #[test]
+    fn test_free_tree_follows_leaf_chain() {
+        let entries: Vec<(Vec<u8>, Vec<u8>)> =
+            (0..20u32).map(|i| (i.to_be_bytes().to_vec(), vec![i as u8])).collect();
+        let mut writer = BomWriter::new();
+        let tree = writer.build_tree_with(&entries, 64, |a, b| a.cmp(b));
+
+        // Point the header at the first leaf, as in trees whose leaves are
+        // only reachable through the chain.
+        let mut page = BigEndian::read_u32(&writer.block(tree).unwrap()[8..12]);
+        while BigEndian::read_u16(&writer.block(page).unwrap()[0..2]) == 0 {
+            page = BigEndian::read_u32(&writer.block(page).unwrap()[12..16]);
+        }
+        let mut header = writer.block(tree).unwrap().to_vec();
+        BigEndian::write_u32(&mut header[8..12], page);
+        writer.replace_block(tree, header).unwrap();
+
+        let mut leaves = Vec::new();
+        while page != 0 {
+            leaves.push(page);
+            page = BigEndian::read_u32(&writer.block(page).unwrap()[4..8]);
+        }
+        assert!(leaves.len() > 1);
+        writer.free_tree(tree).unwrap();
+        assert!(leaves.iter().all(|&leaf| writer.block(leaf).is_none()));
+    }
+
+    #[test]
+    fn test_build_multi_level_tree() {
+        let entries: Vec<(Vec<u8>, Vec<u8>)> = (0..500u32)
+            .rev()
+            .map(|i| (i.to_be_bytes().to_vec(), format!("v{i}").into_bytes()))
+            .collect();
+
+        // 64-byte pages hold 6 entries, so 500 entries need four levels:
+        // 84 leaves, then 14 and 3 branch pages, then the root.
+        let mut writer = BomWriter::new();
+        let tree_idx = writer.build_tree_with(&entries, 64, |a, b| a.cmp(b));
+        writer.add_variable("Paths", tree_idx);
+        let reversed_idx = writer.build_tree_with(&entries, 64, |a, b| b.cmp(a));
+        writer.add_variable("Reversed", reversed_idx);
+        let bom = Bom::load(writer.serialize()).unwrap();
+
+        let tree = bom.tree("Paths").unwrap();
+        assert_eq!((tree.block_size(), tree.path_count()), (64, 500));
+        let keys: Vec<u32> = tree.iter().map(|e| BigEndian::read_u32(e.unwrap().0)).collect();
+        assert_eq!(keys, (0..500).collect::<Vec<_>>());
+        for i in [0u32, 1, 250, 499] {
+            assert_eq!(tree.get(&i.to_be_bytes()).unwrap(), Some(format!("v{i}").as_bytes()));
+        }
+        assert_eq!(tree.get(&500u32.to_be_bytes()).unwrap(), None);
+
+        // Walk down the leftmost spine, then along the leaves checking the
+        // backward pointers and page sizes.
+        let mut page = tree.root();
+        let mut depth = 1;
+        while BigEndian::read_u16(&bom.index_get(page).unwrap()[0..2]) == 0 {
+            page = BigEndian::read_u32(&bom.index_get(page).unwrap()[12..16]);
+            depth += 1;
+        }
+        assert_eq!(depth, 4);
+        let mut previous = 0;
+        let mut leaves = 0;
+        while page != 0 {
+            let data = bom.index_get(page).unwrap();
+            assert_eq!(data.len(), 64);
+            assert_eq!(BigEndian::read_u32(&data[8..12]), previous);
+            assert!(BigEndian::read_u16(&data[2..4]) >= 5);
+            previous = page;
+            page = BigEndian::read_u32(&data[4..8]);
+            leaves += 1;
+        }
+        assert_eq!(leaves, 84);
+
+        let reversed = bom.tree("Reversed").unwrap();
+        let first = reversed.iter().next().unwrap().unwrap();
+        assert_eq!(first.0, &499u32.to_be_bytes());
+        let found = reversed.get_by(&7u32.to_be_bytes(), |a, b| b.cmp(a)).unwrap();
+        assert_eq!(found, Some(&b"v7"[..]));
+    }
+
+    #[test]
+    fn test_edit_existing_bom() {
+        let entries: Vec<(Vec<u8>, Vec<u8>)> = ["a", "b", "c"]
+            .iter()
+            .map(|k| (k.as_bytes().to_vec(), format!("value {k}").into_bytes()))
+            .collect();
+        let mut writer = BomWriter::new();
+        let header = writer.add_block(b"header v1".to_vec());
+        writer.add_variable("HEADER", header);
+        let tree = writer.build_tree(&entries);
+        writer.add_variable("Paths", tree);
+        let original = writer.serialize();
+
+        let bom = Bom::load(original.clone()).unwrap();
+        let mut editor = BomWriter::from_bom(&bom);
+        assert_eq!(editor.block(header), Some(&b"header v1"[..]));
+        editor.replace_block(header, b"header v2".to_vec()).unwrap();
+
+        let kept: Vec<(Vec<u8>, Vec<u8>)> = bom
+            .tree_entries("Paths")
+            .unwrap()
+            .into_iter()
+            .filter(|e| e.key != b"b")
+            .map(|e| (e.key, e.value))
+            .collect();
+        editor.replace_tree("Paths", &kept, DEFAULT_BLOCK_SIZE, |a, b| a.cmp(b)).unwrap();
+        assert!(editor.replace_block(0, Vec::new()).is_err());
+
+        let edited = editor.serialize();
+        let bom = Bom::load(edited.clone()).unwrap();
+        assert_eq!(bom.index_get(bom.variable_get("HEADER").unwrap()), Some(&b"header v2"[..]));
+        let keys: Vec<Vec<u8>> = bom.tree_entries("Paths").unwrap().into_iter().map(|e| e.key).collect();
+        assert_eq!(keys, [b"a".to_vec(), b"c".to_vec()]);
+
+        // The new tree reuses freed indices, and freed blocks take no space.
+        assert!(bom.indices().len() <= Bom::load(original.clone()).unwrap().indices().len());
+        assert!(edited.len() < original.len());
+    }
 }
@@ -0,0 +1,320 @@
+//! Lazy access to BOM B+trees.
+//!
+//! A tree block points at a root page. Branch pages hold (child, key)
+//! pairs and leaf pages hold (value, key) pairs, with leaves chained in
+//! key order through their forward pointers. Lookups descend through the
+//! branch pages and then walk the leaf chain, so only the pages on the way
+//! are touched.
+
+use crate::{Bom, BomError, TREE_ENTRY_HEADER_SIZE, TREE_ENTRY_INDEX_SIZE, TREE_HEADER_SIZE, TREE_MAGIC};
+use byteorder::{BigEndian, ByteOrder};
+use std::cmp::Ordering;
+
+/// A handle to one tree of a BOM.
+#[derive(Debug, Clone, Copy)]
+pub struct BomTree<'a> {
+    bom: &'a Bom,
+    root: u32,
+    block_size: u32,
+    path_count: u32,
+}
+
+/// One page of a tree.
+#[derive(Debug, Clone, Copy)]
+struct Page<'a> {
+    is_leaf: bool,
+    count: usize,
+    forward: u32,
+    data: &'a [u8],
+}
+
+impl<'a> Page<'a> {
+    fn load(bom: &'a Bom, index: u32) -> Result<Self, BomError> {
+        let data = bom.index_get(index).ok_or(BomError::IndexOutOfRange(index))?;
+        if data.len() < TREE_ENTRY_HEADER_SIZE {
+            return Err(BomError::DataOutOfBounds);
+        }
+        let count = BigEndian::read_u16(&data[2..4]) as usize;
+        if data.len() < TREE_ENTRY_HEADER_SIZE + count * TREE_ENTRY_INDEX_SIZE {
+            return Err(BomError::DataOutOfBounds);
+        }
+        Ok(Page {
+            is_leaf: BigEndian::read_u16(&data[0..2]) != 0,
+            count,
+            forward: BigEndian::read_u32(&data[4..8]),
+            data,
+        })
+    }
+
+    /// The (value or child, key) block indices of an entry.
+    fn entry(&self, i: usize) -> (u32, u32) {
+        let offset = TREE_ENTRY_HEADER_SIZE + i * TREE_ENTRY_INDEX_SIZE;
+        (
+            BigEndian::read_u32(&self.data[offset..offset + 4]),
+            BigEndian::read_u32(&self.data[offset + 4..offset + 8]),
+        )
+    }
+}
+
+impl<'a> BomTree<'a> {
+    pub(crate) fn load(bom: &'a Bom, index: u32) -> Result<Self, BomError> {
+        let data = bom.index_get(index).ok_or(BomError::IndexOutOfRange(index))?;
+        if data.len() < TREE_HEADER_SIZE || &data[0..4] != TREE_MAGIC {
+            return Err(BomError::InvalidTreeMagic);
+        }
+        if BigEndian::read_u32(&data[4..8]) != 1 {
+            return Err(BomError::InvalidTreeVersion);
+        }
+        Ok(BomTree {
+            bom,
+            root: BigEndian::read_u32(&data[8..12]),
+            block_size: BigEndian::read_u32(&data[12..16]),
+            path_count: BigEndian::read_u32(&data[16..20]),
+        })
+    }
+
+    /// Block index of the root page.
+    pub fn root(&self) -> u32 {
+        self.root
+    }
+
+    /// Page size declared in the tree header.
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    /// Entry count declared in the tree header.
+    pub fn path_count(&self) -> u32 {
+        self.path_count
+    }
+
+    /// Iterate over all entries in key order.
+    pub fn iter(&self) -> BomTreeIter<'a> {
+        // No key sorts before the empty one, so this starts at the leftmost leaf.
+        self.iter_from(&[])
+    }
+
+    /// Iterate from the first entry whose key is not less than `key`, with
+    /// keys compared bytewise.
+    pub fn iter_from(&self, key: &[u8]) -> BomTreeIter<'a> {
+        self.iter_by(key, |a, b| a.cmp(b))
+    }
+
+    /// Like [`BomTree::iter_from`], for trees ordered by `compare`.
+    pub fn iter_by(&self, key: &[u8], compare: impl Fn(&[u8], &[u8]) -> Ordering) -> BomTreeIter<'a> {
+        let mut iter = match self.leftmost_leaf_for(key, &compare) {
+            Ok(page) => BomTreeIter::new(self.bom, page),
+            Err(e) => return BomTreeIter::failed(self.bom, e),
+        };
+
+        // The descent may land up to one leaf early; skip smaller keys.
+        loop {
+            match iter.peek_key() {
+                Some(Ok(current)) if compare(current, key) == Ordering::Less => {
+                    iter.next();
+                }
+                _ => return iter,
+            }
+        }
+    }
+
+    /// Look up the value stored for `key`, with keys compared bytewise.
+    pub fn get(&self, key: &[u8]) -> Result<Option<&'a [u8]>, BomError> {
+        self.get_by(key, |a, b| a.cmp(b))
+    }
+
+    /// Like [`BomTree::get`], for trees ordered by `compare`.
+    pub fn get_by(
+        &self,
+        key: &[u8],
+        compare: impl Fn(&[u8], &[u8]) -> Ordering,
+    ) -> Result<Option<&'a [u8]>, BomError> {
+        match self.iter_by(key, &compare).next() {
+            Some(Ok((found, value))) if compare(found, key) == Ordering::Equal => Ok(Some(value)),
+            Some(Err(e)) => Err(e),
+            _ => Ok(None),
+        }
+    }
+
+    /// Iterate over the entries whose key starts with `prefix`, in a tree
+    /// ordered bytewise.
+    pub fn prefix(&self, prefix: &[u8]) -> impl Iterator<Item = Result<(&'a [u8], &'a [u8]), BomError>> + 'a {
+        let iter = self.iter_from(prefix);
+        let prefix = prefix.to_vec();
+        iter.take_while(move |entry| match entry {
+            Ok((key, _)) => key.starts_with(&prefix),
+            Err(_) => true,
+        })
+    }
+
+    /// Descend to the leaf that holds `key` or the one just before it.
+    /// Branch keys are only used to skip subtrees, which works whether
+    /// they hold the first or the last key of each child.
+    fn leftmost_leaf_for(
+        &self,
+        key: &[u8],
+        compare: &impl Fn(&[u8], &[u8]) -> Ordering,
+    ) -> Result<Page<'a>, BomError> {
+        let mut page = Page::load(self.bom, self.root)?;
+        let mut depth = 0;
+        while !page.is_leaf {
+            depth += 1;
+            if page.count == 0 || depth > self.bom.index_count {
+                return Err(BomError::DataOutOfBounds);
+            }
+            let below = (0..page.count)
+                .take_while(|&i| {
+                    let branch_key = self.bom.index_get(page.entry(i).1).unwrap_or(&[]);
+                    compare(branch_key, key) == Ordering::Less
+                })
+                .count();
+            let (child, _) = page.entry(below.saturating_sub(1));
+            page = Page::load(self.bom, child)?;
+        }
+        Ok(page)
+    }
+}
+
+/// An iterator over tree entries that follows the leaf chain lazily.
+/// Yields `(key, value)` slices borrowed from the BOM, and stops after the
+/// first error.
+#[derive(Debug)]
+pub struct BomTreeIter<'a> {
+    bom: &'a Bom,
+    page: Option<Page<'a>>,
+    position: usize,
+    leaves: u32,
+    error: Option<BomError>,
+}
+
+impl<'a> BomTreeIter<'a> {
+    fn new(bom: &'a Bom, page: Page<'a>) -> Self {
+        BomTreeIter {
+            bom,
+            page: Some(page),
+            position: 0,
+            leaves: 1,
+            error: None,
+        }
+    }
+
+    fn failed(bom: &'a Bom, error: BomError) -> Self {
+        BomTreeIter {
+            bom,
+            page: None,
+            position: 0,
+            leaves: 0,
+            error: Some(error),
+        }
+    }
+
+    /// Move to the next leaf with entries left, if the current one is done.
+    fn advance(&mut self) -> Result<(), BomError> {
+        while let Some(page) = self.page {
+            if self.position < page.count {
+                return Ok(());
+            }
+            self.page = None;
+            self.position = 0;
+            if page.forward != 0 {
+                // A well-formed chain visits each block at most once.
+                self.leaves += 1;
+                if self.leaves > self.bom.index_count {
+                    return Err(BomError::DataOutOfBounds);
+                }
+                self.page = Some(Page::load(self.bom, page.forward)?);
+            }
+        }
+        Ok(())
+    }
+
+    fn peek_key(&mut self) -> Option<Result<&'a [u8], BomError>> {
+        if let Err(e) = self.advance() {
+            self.error = Some(e);
+        }
+        if self.error.is_some() {
+            return None;
+        }
+        let page = self.page?;
+        let (_, key_index) = page.entry(self.position);
+        Some(Ok(self.bom.index_get(key_index).unwrap_or(&[])))
+    }
+}
+
+impl<'a> Iterator for BomTreeIter<'a> {
+    type Item = Result<(&'a [u8], &'a [u8]), BomError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = self.advance() {
+            self.page = None;
+            return Some(Err(e));
+        }
+        if let Some(e) = self.error.take() {
+            self.page = None;
+            return Some(Err(e));
+        }
+        let page = self.page?;
+        let (value_index, key_index) = page.entry(self.position);
+        self.position += 1;
+        let key = self.bom.index_get(key_index).unwrap_or(&[]);
+        let value = self.bom.index_get(value_index).unwrap_or(&[]);
+        Some(Ok((key, value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Bom, BomWriter};
+
+    fn page(writer: &mut BomWriter, is_leaf: bool, forward: u32, entries: &[(u32, u32)]) -> u32 {
+        let mut data = Vec::new();
+        data.extend_from_slice(&(is_leaf as u16).to_be_bytes());
+        data.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+        data.extend_from_slice(&forward.to_be_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes());
+        for (value, key) in entries {
+            data.extend_from_slice(&value.to_be_bytes());
+            data.extend_from_slice(&key.to_be_bytes());
+        }
+        writer.add_block(data)
+    }
+
+    #[test]
+    fn test_branch_lookup_and_prefix_scan() {
+        // Two leaves under a branch whose keys are the last key of each child.
+        let mut writer = BomWriter::new();
+        let mut leaf_entries = Vec::new();
+        for key in ["a/1", "a/2", "b/1", "b/2", "c/1"] {
+            let value = writer.add_block(format!("value {key}").into_bytes());
+            let key = writer.add_block(key.as_bytes().to_vec());
+            leaf_entries.push((value, key));
+        }
+        let second = page(&mut writer, true, 0, &leaf_entries[3..]);
+        let first = page(&mut writer, true, second, &leaf_entries[..3]);
+        let root = page(&mut writer, false, 0, &[(first, leaf_entries[2].1), (second, leaf_entries[4].1)]);
+        let mut header = b"tree".to_vec();
+        for field in [1, root, 4096, 5] {
+            header.extend_from_slice(&field.to_be_bytes());
+        }
+        header.push(0);
+        let tree = writer.add_block(header);
+        writer.add_variable("Paths", tree);
+
+        let bom = Bom::load(writer.serialize()).unwrap();
+        let tree = bom.tree("Paths").unwrap();
+        assert_eq!(tree.path_count(), 5);
+
+        let keys: Vec<&[u8]> = tree.iter().map(|e| e.unwrap().0).collect();
+        assert_eq!(keys, [&b"a/1"[..], b"a/2", b"b/1", b"b/2", b"c/1"]);
+
+        assert_eq!(tree.get(b"b/2").unwrap(), Some(&b"value b/2"[..]));
+        assert_eq!(tree.get(b"a/1").unwrap(), Some(&b"value a/1"[..]));
+        assert_eq!(tree.get(b"b/3").unwrap(), None);
+        assert_eq!(tree.get(b"z").unwrap(), None);
+
+        let b: Vec<&[u8]> = tree.prefix(b"b/").map(|e| e.unwrap().0).collect();
+        assert_eq!(b, [&b"b/1"[..], b"b/2"]);
+        let from: Vec<&[u8]> = tree.iter_from(b"b/15").map(|e| e.unwrap().0).collect();
+        assert_eq!(from, [&b"b/2"[..], b"c/1"]);
+    }
+}
@@ -1,5 +1,7 @@
 use std::fs;
 use std::path::Path;
+use xcbuild_plist::strings::{self, TextEncoding};
+use xcbuild_plist::PlistFormat;
 
 /// Options for the builtin-copyStrings tool.
 pub struct CopyStringsOptions {
@@ -68,9 +70,18 @@ impl CopyStringsOptions {
     }
 }
 
+/// Output encodings accepted by `--output-encoding`.
+enum OutputEncoding {
+    Binary,
+    Text(TextEncoding),
+    /// Encodings that .strings text is not written in (e.g. UTF-32) fall
+    /// back to an XML plist.
+    Xml,
+}
+
 /// Run the builtin-copyStrings command.
-/// This copies .strings and .stringsdict files, optionally converting between
-/// plist formats (binary, XML) which handles encoding conversion.
+/// This copies .strings and .stringsdict files. Text .strings files are
+/// transcoded (keeping comments and ordering); plists are re-serialized.
 pub fn run(args: &[String]) -> i32 {
     let opts = match CopyStringsOptions::parse(args) {
         Ok(o) => o,
@@ -93,6 +104,29 @@ pub fn run(args: &[String]) -> i32 {
         return 1;
     }
 
+    let input_encoding = match opts.input_encoding.as_deref() {
+        None => None,
+        Some(enc) => match TextEncoding::parse(enc) {
+            Some(e) => Some(e),
+            None => {
+                eprintln!("error: unknown input encoding '{enc}'");
+                return 1;
+            }
+        },
+    };
+
+    let output_encoding = match opts.output_encoding.as_deref() {
+        None | Some("binary") => OutputEncoding::Binary,
+        Some("utf-32") => OutputEncoding::Xml,
+        Some(enc) => match TextEncoding::parse(enc) {
+            Some(e) => OutputEncoding::Text(e),
+            None => {
+                eprintln!("error: unknown output encoding '{enc}'");
+                return 1;
+            }
+        },
+    };
+
     let out_path = Path::new(&output_dir);
     if let Err(e) = fs::create_dir_all(out_path) {
         eprintln!("error: failed to create output dir: {e}");
@@ -108,10 +142,23 @@ pub fn run(args: &[String]) -> i32 {
             }
         };
 
-        // For strings files, parse as plist and re-serialize
-        // (the standard behavior for Xcode's builtin-copyStrings)
-        let output_data = match xcbuild_plist::deserialize(&data) {
-            Ok((value, _format)) => {
+        // Decode text input so UTF-16 files (with or without a BOM) parse;
+        // an explicit input encoding only matters when there is no BOM.
+        let text = match input_encoding {
+            Some(enc) if strings::detect_encoding(&data) == TextEncoding::Utf8
+                && !data.starts_with(&[0xEF, 0xBB, 0xBF]) =>
+            {
+                strings::decode_with_encoding(&data, enc).ok()
+            }
+            _ => strings::decode(&data).ok().map(|(text, _)| text),
+        };
+        let parsed = match &text {
+            Some(text) => xcbuild_plist::deserialize(text.as_bytes()),
+            None => xcbuild_plist::deserialize(&data),
+        };
+
+        let output_data = match parsed {
+            Ok((value, format)) => {
                 if opts.validate {
                     if let plist::Value::Dictionary(ref d) = value {
                         for (key, val) in d.iter() {
@@ -125,16 +172,19 @@ pub fn run(args: &[String]) -> i32 {
                         return 1;
                     }
                 }
-                let out_format = match opts.output_encoding.as_deref() {
-                    Some("binary") => xcbuild_plist::PlistFormat::Binary,
-                    Some("utf-8") | Some("utf-16") | Some("utf-32") => xcbuild_plist::PlistFormat::Xml,
-                    None => xcbuild_plist::PlistFormat::Binary,
-                    Some(enc) => {
-                        eprintln!("error: unknown output encoding '{enc}'");
-                        return 1;
+                let is_text = matches!(format, PlistFormat::Strings | PlistFormat::Ascii);
+                let result = match (&output_encoding, &text) {
+                    (OutputEncoding::Text(enc), Some(text)) if is_text => {
+                        Ok(strings::encode(text, *enc))
+                    }
+                    (OutputEncoding::Text(_), _) | (OutputEncoding::Xml, _) => {
+                        xcbuild_plist::serialize(&value, PlistFormat::Xml)
+                    }
+                    (OutputEncoding::Binary, _) => {
+                        xcbuild_plist::serialize(&value, PlistFormat::Binary)
                     }
                 };
-                match xcbuild_plist::serialize(&value, out_format) {
+                match result {
                     Ok(d) => d,
                     Err(e) => {
                         eprintln!("error: {input_path}: {e}");
@@ -173,12 +173,20 @@ pub fn run(args: &[String]) -> i32 {
                 return 1;
             }
         };
+        // Nested dictionaries (e.g. entitlements) are merged rather than
+        // replaced, and arrays gain the entries they don't already have.
         if let Ok((acf_value, _)) = xcbuild_plist::deserialize(&acf_data) {
-            if let (plist::Value::Dictionary(ref mut base), plist::Value::Dictionary(extra)) =
-                (&mut value, acf_value)
-            {
-                for (k, v) in extra.into_iter() {
-                    base.insert(k, v);
+            if matches!(
+                (&value, &acf_value),
+                (plist::Value::Dictionary(_), plist::Value::Dictionary(_))
+            ) {
+                let options = xcbuild_plist::MergeOptions {
+                    arrays: xcbuild_plist::ArrayMerge::Union,
+                    ..Default::default()
+                };
+                if let Err(e) = xcbuild_plist::merge(&mut value, acf_value, &options) {
+                    eprintln!("error: {acf}: {e}");
+                    return 1;
                 }
             }
         }
@@ -1,7 +1,7 @@
 use byteorder::{LittleEndian, ByteOrder};
 use std::collections::HashMap;
 use std::fs;
-use xcbuild_bom::Bom;
+use xcbuild_bom::{Bom, BomWriter};
 
 /// CAR header variable names.
 const CAR_HEADER_VAR: &str = "CARHEADER";
@@ -11,6 +11,7 @@ const CAR_RENDITIONS_VAR: &str = "RENDITIONS";
 
 /// CAR magic: "RATC"
 const CAR_MAGIC: &[u8; 4] = b"RATC";
+const CAR_HEADER_SIZE: usize = 436;
 
 /// Key format magic: "tmfk"
 const KEY_FORMAT_MAGIC: &[u8; 4] = b"tmfk";
@@ -118,12 +119,12 @@ pub struct CarHeader {
 }
 
 impl CarHeader {
-    fn parse(data: &[u8]) -> Option<Self> {
+    pub fn parse(data: &[u8]) -> Option<Self> {
         // car_header is: 4 magic + 4 ui_version + 4 storage_version + 4 storage_timestamp
         //   + 4 rendition_count + 128 file_creator + 256 other_creator + 16 uuid
         //   + 4 associated_checksum + 4 schema_version + 4 color_space_id + 4 key_semantics
-        // Total: 432 bytes
-        if data.len() < 432 {
+        // Total: 436 bytes
+        if data.len() < CAR_HEADER_SIZE {
             return None;
         }
         if &data[0..4] != CAR_MAGIC {
@@ -160,6 +161,82 @@ impl CarHeader {
             key_semantics: LittleEndian::read_u32(&data[432..436]),
         })
     }
+
+    /// Serialize the header in the layout read by [`CarHeader::parse`].
+    /// Creator strings are truncated to fit their fields.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![0u8; CAR_HEADER_SIZE];
+        data[0..4].copy_from_slice(CAR_MAGIC);
+        LittleEndian::write_u32(&mut data[4..8], self.ui_version);
+        LittleEndian::write_u32(&mut data[8..12], self.storage_version);
+        LittleEndian::write_u32(&mut data[12..16], self.storage_timestamp);
+        LittleEndian::write_u32(&mut data[16..20], self.rendition_count);
+        write_creator(&mut data[20..148], &self.file_creator);
+        write_creator(&mut data[148..404], &self.other_creator);
+        data[404..420].copy_from_slice(&self.uuid);
+        LittleEndian::write_u32(&mut data[420..424], self.associated_checksum);
+        LittleEndian::write_u32(&mut data[424..428], self.schema_version);
+        LittleEndian::write_u32(&mut data[428..432], self.color_space_id);
+        LittleEndian::write_u32(&mut data[432..436], self.key_semantics);
+        data
+    }
+
+    /// Write the fields that differ from `original` into `data`, a header
+    /// block `original` was parsed from. Every other byte is left alone, as
+    /// [`CarHeader::parse`] does not keep all of the block (text after a
+    /// newline in the creator, invalid UTF-8, bytes past the header).
+    fn patch(&self, original: &CarHeader, data: &mut [u8]) {
+        let words = [
+            (4, original.ui_version, self.ui_version),
+            (8, original.storage_version, self.storage_version),
+            (12, original.storage_timestamp, self.storage_timestamp),
+            (16, original.rendition_count, self.rendition_count),
+            (420, original.associated_checksum, self.associated_checksum),
+            (424, original.schema_version, self.schema_version),
+            (428, original.color_space_id, self.color_space_id),
+            (432, original.key_semantics, self.key_semantics),
+        ];
+        for (offset, old, new) in words {
+            if old != new {
+                LittleEndian::write_u32(&mut data[offset..offset + 4], new);
+            }
+        }
+        if self.file_creator != original.file_creator {
+            write_creator(&mut data[20..148], &self.file_creator);
+        }
+        if self.other_creator != original.other_creator {
+            write_creator(&mut data[148..404], &self.other_creator);
+        }
+        if self.uuid != original.uuid {
+            data[404..420].copy_from_slice(&self.uuid);
+        }
+    }
+}
+
+/// Fill a creator field with a string, truncated to leave a terminating NUL.
+fn write_creator(field: &mut [u8], value: &str) {
+    field.fill(0);
+    let len = value.len().min(field.len() - 1);
+    field[..len].copy_from_slice(&value.as_bytes()[..len]);
+}
+
+/// Rewrite the CARHEADER block of a CAR being edited. Only the fields
+/// `update` changes are written; the rest of the block is kept byte for
+/// byte. Returns false if the archive has no readable header.
+pub fn update_header(writer: &mut BomWriter, update: impl FnOnce(&mut CarHeader)) -> bool {
+    let Some(index) = writer.variable(CAR_HEADER_VAR) else {
+        return false;
+    };
+    let Some(mut data) = writer.block(index).map(<[u8]>::to_vec) else {
+        return false;
+    };
+    let Some(original) = CarHeader::parse(&data) else {
+        return false;
+    };
+    let mut header = original.clone();
+    update(&mut header);
+    header.patch(&original, &mut data);
+    writer.replace_block(index, data).is_ok()
 }
 
 /// A facet (named asset) in the CAR archive.
@@ -326,22 +403,17 @@ impl CarReader {
         let identifiers = parse_key_format(keyfmt_data)?;
 
         // Read FACETKEYS
-        let facets = if let Ok(entries) = bom.tree_entries(CAR_FACET_KEYS_VAR) {
-            entries
-                .iter()
-                .filter_map(|entry| {
-                    let name_end = entry
-                        .key
-                        .iter()
-                        .position(|&b| b == 0)
-                        .unwrap_or(entry.key.len());
-                    let name =
-                        String::from_utf8_lossy(&entry.key[..name_end]).to_string();
-                    let attrs = parse_facet_value(&entry.value);
-                    Some(Facet {
+        let facets = if let Ok(tree) = bom.tree(CAR_FACET_KEYS_VAR) {
+            tree.iter()
+                .map_while(Result::ok)
+                .map(|(key, value)| {
+                    let name_end = key.iter().position(|&b| b == 0).unwrap_or(key.len());
+                    let name = String::from_utf8_lossy(&key[..name_end]).to_string();
+                    let attrs = parse_facet_value(value);
+                    Facet {
                         name,
                         attributes: attrs,
-                    })
+                    }
                 })
                 .collect()
         } else {
@@ -349,12 +421,10 @@ impl CarReader {
         };
 
         // Read RENDITIONS
-        let renditions = if let Ok(entries) = bom.tree_entries(CAR_RENDITIONS_VAR) {
-            entries
-                .iter()
-                .filter_map(|entry| {
-                    Rendition::parse(&entry.key, &entry.value, &identifiers)
-                })
+        let renditions = if let Ok(tree) = bom.tree(CAR_RENDITIONS_VAR) {
+            tree.iter()
+                .map_while(Result::ok)
+                .filter_map(|(key, value)| Rendition::parse(key, value, &identifiers))
                 .collect()
         } else {
             Vec::new()
@@ -497,3 +567,46 @@ pub fn dump_rendition(rendition: &Rendition) {
         rendition.name,
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_header_keeps_unchanged_bytes() {
+        let mut block = CarHeader {
+            ui_version: 1,
+            storage_version: 15,
+            storage_timestamp: 100,
+            rendition_count: 2,
+            file_creator: String::new(),
+            other_creator: String::new(),
+            uuid: [7; 16],
+            associated_checksum: 3,
+            schema_version: 2,
+            color_space_id: 1,
+            key_semantics: 2,
+        }
+        .to_bytes();
+        // Bytes that parse does not keep: text after a newline, invalid
+        // UTF-8, and data past the header.
+        block[20..42].copy_from_slice(b"actool-1\nbuild 23A344\0");
+        block[148..152].copy_from_slice(&[0xff, 0xfe, b'x', 0]);
+        block.extend_from_slice(b"trailing");
+
+        let mut writer = BomWriter::new();
+        let index = writer.add_block(block.clone());
+        writer.add_variable(CAR_HEADER_VAR, index);
+        assert!(update_header(&mut writer, |header| header.storage_timestamp = 200));
+
+        let mut expected = block.clone();
+        LittleEndian::write_u32(&mut expected[12..16], 200);
+        assert_eq!(writer.block(index).unwrap(), expected.as_slice());
+
+        assert!(update_header(&mut writer, |header| header.file_creator = "xcbuild".to_string()));
+        let updated = writer.block(index).unwrap();
+        assert_eq!(&updated[20..28], b"xcbuild\0");
+        assert!(updated[28..148].iter().all(|&b| b == 0));
+        assert_eq!(&updated[148..], &expected[148..]);
+    }
+}
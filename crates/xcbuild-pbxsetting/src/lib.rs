@@ -21,6 +21,106 @@ impl Setting {
     }
 }
 
+/// An ordered set of settings forming one layer of build setting
+/// resolution (e.g. platform defaults, SDK, project, target).
+#[derive(Debug, Clone, Default)]
+pub struct Level {
+    pub settings: Vec<Setting>,
+}
+
+impl Level {
+    pub fn new(settings: Vec<Setting>) -> Level {
+        Level { settings }
+    }
+
+    /// Look up a setting by name. Later settings override earlier ones.
+    pub fn get(&self, name: &str) -> Option<&Setting> {
+        self.settings.iter().rev().find(|s| s.name == name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty()
+    }
+}
+
+/// A stack of levels, from highest to lowest precedence.
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    pub levels: Vec<Level>,
+}
+
+impl Environment {
+    /// Add a level above all existing levels.
+    pub fn insert_front(&mut self, level: Level) {
+        self.levels.insert(0, level);
+    }
+
+    /// Add a level below all existing levels.
+    pub fn insert_back(&mut self, level: Level) {
+        self.levels.push(level);
+    }
+
+    /// Resolve a setting, expanding `$(inherited)` from lower levels and
+    /// `$(NAME)` / `${NAME}` references. Unknown settings expand to "".
+    pub fn resolve(&self, name: &str) -> String {
+        self.resolve_from(name, 0, &mut Vec::new())
+    }
+
+    fn resolve_from(&self, name: &str, start: usize, stack: &mut Vec<String>) -> String {
+        if stack.iter().any(|n| n == name) {
+            return String::new();
+        }
+        for (index, level) in self.levels.iter().enumerate().skip(start) {
+            if let Some(setting) = level.get(name) {
+                stack.push(name.to_string());
+                let value = self.expand(&setting.value, name, index + 1, stack);
+                stack.pop();
+                return value;
+            }
+        }
+        String::new()
+    }
+
+    fn expand(&self, value: &str, name: &str, next: usize, stack: &mut Vec<String>) -> String {
+        let mut result = String::new();
+        let mut rest = value;
+        while let Some(pos) = rest.find('$') {
+            result.push_str(&rest[..pos]);
+            let after = &rest[pos + 1..];
+            let close = match after.chars().next() {
+                Some('(') => ')',
+                Some('{') => '}',
+                _ => {
+                    result.push('$');
+                    rest = after;
+                    continue;
+                }
+            };
+            let end = match after.find(close) {
+                Some(end) => end,
+                None => {
+                    result.push_str(&rest[pos..]);
+                    rest = "";
+                    break;
+                }
+            };
+            let reference = &after[1..end];
+            if reference == "inherited" {
+                // The setting being resolved is on the stack; pop it so the
+                // lower level's definition is not mistaken for a cycle.
+                let saved = stack.pop();
+                result.push_str(&self.resolve_from(name, next, stack));
+                stack.extend(saved);
+            } else {
+                result.push_str(&self.resolve_from(reference, 0, stack));
+            }
+            rest = &after[end + 1..];
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
 /// An entry in an xcconfig file.
 #[derive(Debug, Clone)]
 pub enum ConfigEntry {
@@ -128,10 +228,9 @@ fn parse_include_path(value: &str) -> Option<String> {
     }
     let path = &trimmed[1..trimmed.len() - 1];
 
-    let dev_prefix = "<DEVELOPER_DIR>";
-    if path.starts_with(dev_prefix) {
+    if let Some(rest) = path.strip_prefix("<DEVELOPER_DIR>") {
         if let Ok(dev_dir) = std::env::var("DEVELOPER_DIR") {
-            return Some(format!("{dev_dir}{}", &path[dev_prefix.len()..]));
+            return Some(format!("{dev_dir}{rest}"));
         }
     }
 
@@ -142,8 +241,7 @@ fn parse_directive(line: &str, directory: &str) -> Option<ConfigEntry> {
     let rest = &line[1..]; // skip '#'
     let rest = rest.trim();
 
-    if rest.starts_with("include") {
-        let value = &rest["include".len()..];
+    if let Some(value) = rest.strip_prefix("include") {
         if let Some(include_path) = parse_include_path(value) {
             let resolved = if Path::new(&include_path).is_absolute() {
                 include_path.clone()
@@ -177,6 +275,41 @@ mod tests {
         assert_eq!(s.value, "bar");
     }
 
+    fn level(settings: &[(&str, &str)]) -> Level {
+        Level::new(
+            settings
+                .iter()
+                .map(|(name, value)| Setting {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_environment_inherited() {
+        let mut env = Environment::default();
+        env.insert_back(level(&[("ARCHS", "arm64"), ("SDKROOT", "macosx")]));
+        env.insert_front(level(&[("ARCHS", "$(inherited) x86_64")]));
+        assert_eq!(env.resolve("ARCHS"), "arm64 x86_64");
+        assert_eq!(env.resolve("SDKROOT"), "macosx");
+        assert_eq!(env.resolve("MISSING"), "");
+    }
+
+    #[test]
+    fn test_environment_references() {
+        let mut env = Environment::default();
+        env.insert_back(level(&[
+            ("PLATFORM_NAME", "iphoneos"),
+            ("TARGET", "${PLATFORM_NAME}-$(ARCH)"),
+            ("ARCH", "arm64"),
+            ("LOOP", "$(LOOP)x"),
+        ]));
+        assert_eq!(env.resolve("TARGET"), "iphoneos-arm64");
+        assert_eq!(env.resolve("LOOP"), "x");
+    }
+
     #[test]
     fn test_parse_setting_no_spaces() {
         let s = Setting::parse("FOO=bar").unwrap();
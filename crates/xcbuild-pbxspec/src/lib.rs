@@ -107,10 +107,10 @@ impl Manager {
         if let Ok(entries) = fs::read_dir(dir_path) {
             for entry in entries.flatten() {
                 let p = entry.path();
-                if p.extension().map(|e| e == "xcspec").unwrap_or(false) {
-                    if self.register_domain(domain_name, &p.to_string_lossy()) {
-                        found = true;
-                    }
+                if p.extension().map(|e| e == "xcspec").unwrap_or(false)
+                    && self.register_domain(domain_name, &p.to_string_lossy())
+                {
+                    found = true;
                 }
             }
         }
@@ -1,15 +1,40 @@
 //! ASCII/OpenStep plist format parser and writer.
 //!
 //! Ported from Libraries/plist/Sources/Format/ASCIIParser.cpp and ASCIIWriter.cpp.
+//!
+//! The GNUstep dialect adds typed values (`<*I42>`, `<*R3.14>`, `<*BY>`,
+//! `<*D2024-01-01 00:00:00 +0000>`) so integers, reals, booleans and dates
+//! survive a round trip through text.
+
+mod de;
+
+pub use de::ValueDeserializer;
 
+use crate::PlistError;
 use plist::Value;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::fmt;
+use std::time::{Duration, SystemTime};
+
+/// ASCII property list dialects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    /// Classic OpenStep: every scalar is a string.
+    #[default]
+    OpenStep,
+    /// OpenStep plus GNUstep `<*...>` typed values.
+    GnuStep,
+}
 
 #[derive(Debug)]
 pub struct ParseError {
     pub message: String,
+    pub offset: usize,
     pub line: usize,
     pub column: usize,
+    /// Keys and array indices of the value being parsed.
+    pub key_path: Vec<String>,
 }
 
 impl fmt::Display for ParseError {
@@ -29,6 +54,12 @@ struct Lexer<'a> {
     pos: usize,
     line: usize,
     column: usize,
+    /// When set, the text of comments skipped since the last token is
+    /// collected into `comment` (used to preserve .strings comments).
+    capture_comments: bool,
+    comment: Option<String>,
+    dialect: Dialect,
+    path: Vec<String>,
 }
 
 impl<'a> Lexer<'a> {
@@ -38,6 +69,24 @@ impl<'a> Lexer<'a> {
             pos: 0,
             line: 1,
             column: 1,
+            capture_comments: false,
+            comment: None,
+            dialect: Dialect::OpenStep,
+            path: Vec::new(),
+        }
+    }
+
+    fn record_comment(&mut self, start: usize, end: usize) {
+        if !self.capture_comments {
+            return;
+        }
+        let text = String::from_utf8_lossy(&self.input[start..end]).trim().to_string();
+        match &mut self.comment {
+            Some(existing) => {
+                existing.push('\n');
+                existing.push_str(&text);
+            }
+            None => self.comment = Some(text),
         }
     }
 
@@ -51,7 +100,8 @@ impl<'a> Lexer<'a> {
         if c == b'\n' {
             self.line += 1;
             self.column = 1;
-        } else {
+        } else if c & 0xC0 != 0x80 {
+            // Count characters, not UTF-8 continuation bytes.
             self.column += 1;
         }
         Some(c)
@@ -70,29 +120,33 @@ impl<'a> Lexer<'a> {
                                 // Line comment
                                 self.advance();
                                 self.advance();
+                                let start = self.pos;
                                 while let Some(c) = self.peek() {
                                     if c == b'\n' {
                                         break;
                                     }
                                     self.advance();
                                 }
+                                self.record_comment(start, self.pos);
                             }
                             b'*' => {
                                 // Block comment
                                 self.advance();
                                 self.advance();
+                                let start = self.pos;
+                                let mut end = self.input.len();
                                 loop {
                                     match self.advance() {
-                                        Some(b'*') => {
-                                            if self.peek() == Some(b'/') {
-                                                self.advance();
-                                                break;
-                                            }
+                                        Some(b'*') if self.peek() == Some(b'/') => {
+                                            end = self.pos - 1;
+                                            self.advance();
+                                            break;
                                         }
                                         None => break,
                                         _ => {}
                                     }
                                 }
+                                self.record_comment(start, end);
                             }
                             _ => break,
                         }
@@ -108,20 +162,64 @@ impl<'a> Lexer<'a> {
     fn error(&self, message: &str) -> ParseError {
         ParseError {
             message: message.to_string(),
+            offset: self.pos,
             line: self.line,
             column: self.column,
+            key_path: self.path.clone(),
         }
     }
 }
 
 /// Parse an ASCII/OpenStep plist string into a plist Value.
 pub fn parse(input: &str) -> Result<Value, ParseError> {
+    parse_with_dialect(input, Dialect::OpenStep)
+}
+
+/// Parse an ASCII plist string, accepting the extensions of `dialect`.
+pub fn parse_with_dialect(input: &str, dialect: Dialect) -> Result<Value, ParseError> {
     let mut lexer = Lexer::new(input);
+    lexer.dialect = dialect;
     lexer.skip_whitespace_and_comments();
     let value = parse_value(&mut lexer)?;
     Ok(value)
 }
 
+/// Parse a .strings file: a dictionary whose top-level braces may be
+/// omitted (`"key" = "value";` pairs until the end of input).
+pub fn parse_strings(input: &str) -> Result<Value, ParseError> {
+    let mut lexer = Lexer::new(input);
+    lexer.skip_whitespace_and_comments();
+    if lexer.peek() == Some(b'{') {
+        return parse_value(&mut lexer);
+    }
+    let mut dict = plist::Dictionary::new();
+    parse_dictionary_entries(&mut lexer, None, &mut |key, value, _| {
+        dict.insert(key, value);
+    })?;
+    Ok(Value::Dictionary(dict))
+}
+
+/// Parse a braceless .strings file into ordered `(comment, key, value)`
+/// entries, keeping the comment that precedes each entry.
+pub(crate) fn parse_commented_entries(
+    input: &str,
+) -> Result<Vec<(Option<String>, String, Value)>, ParseError> {
+    let mut lexer = Lexer::new(input);
+    lexer.capture_comments = true;
+    lexer.skip_whitespace_and_comments();
+    let terminator = if lexer.peek() == Some(b'{') {
+        lexer.advance();
+        Some(b'}')
+    } else {
+        None
+    };
+    let mut entries = Vec::new();
+    parse_dictionary_entries(&mut lexer, terminator, &mut |key, value, comment| {
+        entries.push((comment, key, value));
+    })?;
+    Ok(entries)
+}
+
 fn parse_value(lexer: &mut Lexer) -> Result<Value, ParseError> {
     lexer.skip_whitespace_and_comments();
 
@@ -140,20 +238,34 @@ fn parse_dictionary(lexer: &mut Lexer) -> Result<Value, ParseError> {
     assert_eq!(lexer.advance(), Some(b'{'));
 
     let mut dict = plist::Dictionary::new();
+    parse_dictionary_entries(lexer, Some(b'}'), &mut |key, value, _| {
+        dict.insert(key, value);
+    })?;
+    Ok(Value::Dictionary(dict))
+}
 
+/// Parse `key = value;` entries up to `terminator`, or to the end of input
+/// when there is none (braceless .strings files).
+fn parse_dictionary_entries(
+    lexer: &mut Lexer,
+    terminator: Option<u8>,
+    insert: &mut dyn FnMut(String, Value, Option<String>),
+) -> Result<(), ParseError> {
     loop {
         lexer.skip_whitespace_and_comments();
 
-        match lexer.peek() {
-            Some(b'}') => {
+        match (lexer.peek(), terminator) {
+            (Some(c), Some(t)) if c == t => {
                 lexer.advance();
-                return Ok(Value::Dictionary(dict));
+                return Ok(());
             }
-            None => return Err(lexer.error("unexpected end of dictionary")),
+            (None, None) => return Ok(()),
+            (None, Some(_)) => return Err(lexer.error("unexpected end of dictionary")),
             _ => {}
         }
 
         // Parse key
+        let comment = lexer.comment.take();
         let key = parse_string_value(lexer)?;
 
         lexer.skip_whitespace_and_comments();
@@ -163,17 +275,22 @@ fn parse_dictionary(lexer: &mut Lexer) -> Result<Value, ParseError> {
             Some(b'=') => {
                 lexer.advance();
                 lexer.skip_whitespace_and_comments();
+                lexer.path.push(key.clone());
                 let value = parse_value(lexer)?;
-                dict.insert(key, value);
+                lexer.path.pop();
+                lexer.comment = None;
+                insert(key, value, comment);
 
                 lexer.skip_whitespace_and_comments();
                 if lexer.peek() == Some(b';') {
                     lexer.advance();
+                } else if terminator.is_none() {
+                    return Err(lexer.error("expected ';' after value"));
                 }
             }
             Some(b';') => {
                 // Key without value (shorthand for key = key)
-                dict.insert(key.clone(), Value::String(key));
+                insert(key.clone(), Value::String(key), comment);
                 lexer.advance();
             }
             _ => {
@@ -200,7 +317,9 @@ fn parse_array(lexer: &mut Lexer) -> Result<Value, ParseError> {
             _ => {}
         }
 
+        lexer.path.push(arr.len().to_string());
         let value = parse_value(lexer)?;
+        lexer.path.pop();
         arr.push(value);
 
         lexer.skip_whitespace_and_comments();
@@ -222,25 +341,38 @@ fn parse_string_value(lexer: &mut Lexer) -> Result<String, ParseError> {
 fn parse_quoted_string(lexer: &mut Lexer) -> Result<String, ParseError> {
     assert_eq!(lexer.advance(), Some(b'"'));
 
-    let mut s = String::new();
+    // Collect raw bytes so multi-byte UTF-8 sequences survive intact.
+    let mut bytes: Vec<u8> = Vec::new();
+    let push = |bytes: &mut Vec<u8>, ch: char| {
+        let mut buf = [0u8; 4];
+        bytes.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+    };
+    // A pending UTF-16 high surrogate from a previous \U escape.
+    let mut high_surrogate: Option<u32> = None;
 
     loop {
         match lexer.advance() {
-            Some(b'"') => return Ok(s),
+            Some(b'"') => {
+                return String::from_utf8(bytes)
+                    .map_err(|_| lexer.error("invalid UTF-8 in string"));
+            }
             Some(b'\\') => {
-                match lexer.advance() {
-                    Some(b'n') => s.push('\n'),
-                    Some(b'r') => s.push('\r'),
-                    Some(b't') => s.push('\t'),
-                    Some(b'\\') => s.push('\\'),
-                    Some(b'"') => s.push('"'),
-                    Some(b'a') => s.push('\x07'),
-                    Some(b'b') => s.push('\x08'),
-                    Some(b'f') => s.push('\x0C'),
-                    Some(b'v') => s.push('\x0B'),
-                    Some(b'0') => s.push('\0'),
+                let escape = lexer.advance();
+                if !matches!(escape, Some(b'U') | Some(b'u')) {
+                    high_surrogate = None;
+                }
+                match escape {
+                    Some(b'n') => bytes.push(b'\n'),
+                    Some(b'r') => bytes.push(b'\r'),
+                    Some(b't') => bytes.push(b'\t'),
+                    Some(b'\\') => bytes.push(b'\\'),
+                    Some(b'"') => bytes.push(b'"'),
+                    Some(b'a') => bytes.push(0x07),
+                    Some(b'b') => bytes.push(0x08),
+                    Some(b'f') => bytes.push(0x0C),
+                    Some(b'v') => bytes.push(0x0B),
                     Some(b'U') | Some(b'u') => {
-                        // Unicode escape: \Uxxxx
+                        // Unicode escape: \Uxxxx; surrogate pairs are combined
                         let mut hex = String::new();
                         for _ in 0..4 {
                             match lexer.advance() {
@@ -250,9 +382,20 @@ fn parse_quoted_string(lexer: &mut Lexer) -> Result<String, ParseError> {
                                 _ => return Err(lexer.error("invalid unicode escape")),
                             }
                         }
-                        if let Ok(code) = u32::from_str_radix(&hex, 16) {
-                            if let Some(ch) = char::from_u32(code) {
-                                s.push(ch);
+                        let code = u32::from_str_radix(&hex, 16)
+                            .map_err(|_| lexer.error("invalid unicode escape"))?;
+                        match (high_surrogate.take(), code) {
+                            (None, 0xD800..=0xDBFF) => high_surrogate = Some(code),
+                            (Some(high), 0xDC00..=0xDFFF) => {
+                                let combined = 0x10000 + ((high - 0xD800) << 10) + (code - 0xDC00);
+                                if let Some(ch) = char::from_u32(combined) {
+                                    push(&mut bytes, ch);
+                                }
+                            }
+                            (_, code) => {
+                                if let Some(ch) = char::from_u32(code) {
+                                    push(&mut bytes, ch);
+                                }
                             }
                         }
                     }
@@ -272,15 +415,18 @@ fn parse_quoted_string(lexer: &mut Lexer) -> Result<String, ParseError> {
                         }
                         if let Ok(code) = u32::from_str_radix(&oct, 8) {
                             if let Some(ch) = char::from_u32(code) {
-                                s.push(ch);
+                                push(&mut bytes, ch);
                             }
                         }
                     }
-                    Some(c) => s.push(c as char),
+                    Some(c) => bytes.push(c),
                     None => return Err(lexer.error("unexpected end of string escape")),
                 }
             }
-            Some(c) => s.push(c as char),
+            Some(c) => {
+                high_surrogate = None;
+                bytes.push(c);
+            }
             None => return Err(lexer.error("unterminated string")),
         }
     }
@@ -305,6 +451,11 @@ fn parse_unquoted_string(lexer: &mut Lexer) -> Result<String, ParseError> {
 fn parse_data(lexer: &mut Lexer) -> Result<Value, ParseError> {
     assert_eq!(lexer.advance(), Some(b'<'));
 
+    if lexer.dialect == Dialect::GnuStep && lexer.peek() == Some(b'*') {
+        lexer.advance();
+        return parse_typed(lexer);
+    }
+
     let mut hex = String::new();
     loop {
         lexer.skip_whitespace_and_comments();
@@ -323,7 +474,7 @@ fn parse_data(lexer: &mut Lexer) -> Result<Value, ParseError> {
     }
 
     // Pad with trailing 0 if odd length
-    if hex.len() % 2 != 0 {
+    if !hex.len().is_multiple_of(2) {
         hex.push('0');
     }
 
@@ -337,6 +488,78 @@ fn parse_data(lexer: &mut Lexer) -> Result<Value, ParseError> {
     Ok(Value::Data(bytes))
 }
 
+/// Parse the body of a GNUstep `<*Tvalue>` typed value (after `<*`).
+fn parse_typed(lexer: &mut Lexer) -> Result<Value, ParseError> {
+    let kind = lexer
+        .advance()
+        .ok_or_else(|| lexer.error("unterminated typed value"))?;
+    let mut body = Vec::new();
+    loop {
+        match lexer.advance() {
+            Some(b'>') => break,
+            Some(c) => body.push(c),
+            None => return Err(lexer.error("unterminated typed value")),
+        }
+    }
+    let body = String::from_utf8_lossy(&body).trim().to_string();
+
+    match kind {
+        b'I' => body
+            .parse::<i64>()
+            .map(|n| Value::Integer(n.into()))
+            .or_else(|_| body.parse::<u64>().map(|n| Value::Integer(n.into())))
+            .map_err(|_| lexer.error(&format!("invalid integer '{body}'"))),
+        b'R' => body
+            .parse::<f64>()
+            .map(Value::Real)
+            .map_err(|_| lexer.error(&format!("invalid real '{body}'"))),
+        b'B' => match body.as_str() {
+            "Y" | "YES" => Ok(Value::Boolean(true)),
+            "N" | "NO" => Ok(Value::Boolean(false)),
+            _ => Err(lexer.error(&format!("invalid boolean '{body}'"))),
+        },
+        b'D' => parse_gnustep_date(&body)
+            .map(Value::Date)
+            .ok_or_else(|| lexer.error(&format!("invalid date '{body}'"))),
+        other => Err(lexer.error(&format!("unknown typed value '<*{}'", other as char))),
+    }
+}
+
+/// Parse `YYYY-MM-DD HH:MM:SS +HHMM` into a date.
+fn parse_gnustep_date(text: &str) -> Option<plist::Date> {
+    let mut parts = text.split_whitespace();
+    let (date, time) = (parts.next()?, parts.next()?);
+    let offset = parts.next().unwrap_or("+0000");
+    let utc = plist::Date::from_xml_format(&format!("{date}T{time}Z")).ok()?;
+
+    let (sign, digits) = match offset.as_bytes().first()? {
+        b'+' => (1i64, &offset[1..]),
+        b'-' => (-1i64, &offset[1..]),
+        _ => return None,
+    };
+    if digits.len() != 4 || !digits.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i64 = digits[..2].parse().ok()?;
+    let minutes: i64 = digits[2..].parse().ok()?;
+    let seconds = sign * (hours * 3600 + minutes * 60);
+
+    // Local time = UTC + offset, so UTC = local - offset.
+    let time = SystemTime::from(utc);
+    let adjusted = if seconds >= 0 {
+        time.checked_sub(Duration::from_secs(seconds as u64))?
+    } else {
+        time.checked_add(Duration::from_secs((-seconds) as u64))?
+    };
+    Some(plist::Date::from(adjusted))
+}
+
+fn format_gnustep_date(date: &plist::Date) -> String {
+    // "2024-01-01T00:00:00Z" -> "2024-01-01 00:00:00 +0000"
+    let xml = date.to_xml_format();
+    format!("{} +0000", xml.trim_end_matches('Z').replacen('T', " ", 1))
+}
+
 fn is_unquoted_char(c: u8) -> bool {
     c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c == b'/' || c == b':' || c == b'.' || c == b'-'
 }
@@ -345,16 +568,75 @@ fn is_unquoted_char(c: u8) -> bool {
 
 /// Write a plist Value in ASCII/OpenStep format.
 pub fn write(value: &Value) -> String {
+    write_with_dialect(value, Dialect::OpenStep)
+}
+
+/// Write a plist Value in the given ASCII dialect.
+pub fn write_with_dialect(value: &Value, dialect: Dialect) -> String {
     let mut output = String::new();
-    write_value(&mut output, value, 0);
+    write_value(&mut output, value, 0, dialect);
     output.push('\n');
     output
 }
 
-fn write_value(output: &mut String, value: &Value, indent: usize) {
+/// Serialize a value to ASCII plist text.
+pub fn to_string<T: Serialize>(value: &T) -> Result<String, PlistError> {
+    to_string_with_dialect(value, Dialect::OpenStep)
+}
+
+/// Serialize a value to ASCII plist text in the given dialect.
+pub fn to_string_with_dialect<T: Serialize>(
+    value: &T,
+    dialect: Dialect,
+) -> Result<String, PlistError> {
+    Ok(write_with_dialect(&plist::to_value(value)?, dialect))
+}
+
+/// Deserialize a value from ASCII plist text. Strings are converted to
+/// numbers and booleans where the target type expects them.
+pub fn from_str<T: DeserializeOwned>(input: &str) -> Result<T, PlistError> {
+    from_str_with_dialect(input, Dialect::OpenStep)
+}
+
+/// Deserialize a value from ASCII plist text in the given dialect.
+pub fn from_str_with_dialect<T: DeserializeOwned>(
+    input: &str,
+    dialect: Dialect,
+) -> Result<T, PlistError> {
+    let value = parse_with_dialect(input, dialect)?;
+    T::deserialize(ValueDeserializer(value))
+}
+
+pub(crate) fn write_value(output: &mut String, value: &Value, indent: usize, dialect: Dialect) {
+    if dialect == Dialect::GnuStep {
+        match value {
+            Value::Integer(i) => {
+                if let Some(n) = i.as_signed() {
+                    output.push_str(&format!("<*I{n}>"));
+                } else if let Some(n) = i.as_unsigned() {
+                    output.push_str(&format!("<*I{n}>"));
+                }
+                return;
+            }
+            Value::Real(f) => {
+                output.push_str(&format!("<*R{f}>"));
+                return;
+            }
+            Value::Boolean(b) => {
+                output.push_str(if *b { "<*BY>" } else { "<*BN>" });
+                return;
+            }
+            Value::Date(d) => {
+                output.push_str(&format!("<*D{}>", format_gnustep_date(d)));
+                return;
+            }
+            _ => {}
+        }
+    }
+
     match value {
-        Value::Dictionary(dict) => write_dictionary(output, dict, indent),
-        Value::Array(arr) => write_array(output, arr, indent),
+        Value::Dictionary(dict) => write_dictionary(output, dict, indent, dialect),
+        Value::Array(arr) => write_array(output, arr, indent, dialect),
         Value::String(s) => write_string(output, s),
         Value::Integer(i) => {
             if let Some(n) = i.as_signed() {
@@ -372,39 +654,39 @@ fn write_value(output: &mut String, value: &Value, indent: usize) {
     }
 }
 
-fn write_dictionary(output: &mut String, dict: &plist::Dictionary, indent: usize) {
+fn write_dictionary(output: &mut String, dict: &plist::Dictionary, indent: usize, dialect: Dialect) {
     output.push_str("{\n");
     let child_indent = indent + 1;
     for (key, value) in dict.iter() {
         for _ in 0..child_indent {
-            output.push_str("\t");
+            output.push('\t');
         }
         write_string(output, key);
         output.push_str(" = ");
-        write_value(output, value, child_indent);
+        write_value(output, value, child_indent, dialect);
         output.push_str(";\n");
     }
     for _ in 0..indent {
-        output.push_str("\t");
+        output.push('\t');
     }
     output.push('}');
 }
 
-fn write_array(output: &mut String, arr: &[Value], indent: usize) {
+fn write_array(output: &mut String, arr: &[Value], indent: usize, dialect: Dialect) {
     output.push_str("(\n");
     let child_indent = indent + 1;
     for (i, value) in arr.iter().enumerate() {
         for _ in 0..child_indent {
-            output.push_str("\t");
+            output.push('\t');
         }
-        write_value(output, value, child_indent);
+        write_value(output, value, child_indent, dialect);
         if i + 1 < arr.len() {
             output.push(',');
         }
         output.push('\n');
     }
     for _ in 0..indent {
-        output.push_str("\t");
+        output.push('\t');
     }
     output.push(')');
 }
@@ -413,23 +695,28 @@ fn write_string(output: &mut String, s: &str) {
     if !s.is_empty() && s.bytes().all(is_unquoted_char) {
         output.push_str(s);
     } else {
-        output.push('"');
-        for c in s.chars() {
-            match c {
-                '"' => output.push_str("\\\""),
-                '\\' => output.push_str("\\\\"),
-                '\n' => output.push_str("\\n"),
-                '\r' => output.push_str("\\r"),
-                '\t' => output.push_str("\\t"),
-                '\x07' => output.push_str("\\a"),
-                '\x08' => output.push_str("\\b"),
-                '\x0C' => output.push_str("\\f"),
-                '\x0B' => output.push_str("\\v"),
-                _ => output.push(c),
-            }
+        write_quoted_string(output, s);
+    }
+}
+
+/// Write a string in double quotes, escaping as needed.
+pub(crate) fn write_quoted_string(output: &mut String, s: &str) {
+    output.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            '\x07' => output.push_str("\\a"),
+            '\x08' => output.push_str("\\b"),
+            '\x0C' => output.push_str("\\f"),
+            '\x0B' => output.push_str("\\v"),
+            _ => output.push(c),
         }
-        output.push('"');
     }
+    output.push('"');
 }
 
 fn write_data(output: &mut String, data: &[u8]) {
@@ -534,6 +821,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_gnustep_roundtrip() {
+        let mut dict = plist::Dictionary::new();
+        dict.insert("int".to_string(), Value::Integer(42.into()));
+        dict.insert("neg".to_string(), Value::Integer((-7).into()));
+        dict.insert("real".to_string(), Value::Real(2.5));
+        dict.insert("yes".to_string(), Value::Boolean(true));
+        dict.insert("no".to_string(), Value::Boolean(false));
+        dict.insert(
+            "date".to_string(),
+            Value::Date(plist::Date::from_xml_format("2024-01-01T00:00:00Z").unwrap()),
+        );
+        dict.insert("string".to_string(), Value::String("42".to_string()));
+        dict.insert("data".to_string(), Value::Data(vec![0xde, 0xad]));
+        let value = Value::Dictionary(dict);
+
+        let written = write_with_dialect(&value, Dialect::GnuStep);
+        assert!(written.contains("<*I42>"));
+        assert!(written.contains("<*BY>"));
+        assert!(written.contains("<*D2024-01-01 00:00:00 +0000>"));
+        assert_eq!(parse_with_dialect(&written, Dialect::GnuStep).unwrap(), value);
+
+        // OpenStep does not understand typed values
+        assert!(parse(&written).is_err());
+    }
+
+    #[test]
+    fn test_gnustep_date_offset() {
+        let value = parse_with_dialect("<*D2024-01-01 02:30:00 +0230>", Dialect::GnuStep).unwrap();
+        let expected = plist::Date::from_xml_format("2024-01-01T00:00:00Z").unwrap();
+        assert_eq!(value, Value::Date(expected));
+    }
+
     #[test]
     fn test_write_roundtrip() {
         let mut dict = plist::Dictionary::new();
@@ -557,4 +877,43 @@ mod tests {
             panic!("expected dictionary");
         }
     }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct Spec {
+            identifier: String,
+            version: u32,
+            enabled: bool,
+            scale: f64,
+            options: Vec<String>,
+            parent: Option<String>,
+            kind: Kind,
+        }
+
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        enum Kind {
+            Tool,
+            Compiler,
+        }
+
+        let input = r#"{
+            Identifier = "com.apple.compilers.gcc";
+            Version = 4;
+            Enabled = YES;
+            Scale = 1.5;
+            Options = (a, b);
+            Kind = Compiler;
+        }"#;
+        let spec: Spec = from_str(input).unwrap();
+        assert_eq!(spec.version, 4);
+        assert!(spec.enabled);
+        assert_eq!(spec.parent, None);
+        assert_eq!(spec.kind, Kind::Compiler);
+
+        let text = to_string(&spec).unwrap();
+        assert_eq!(from_str::<Spec>(&text).unwrap(), spec);
+        assert!(from_str::<Spec>("{ Version = four; }").is_err());
+    }
 }
@@ -0,0 +1,318 @@
+//! serde Deserializer over parsed ASCII plist values.
+//!
+//! OpenStep plists store every scalar as a string, so numbers and
+//! booleans are parsed from strings when the target type asks for them.
+
+use crate::PlistError;
+use plist::Value;
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{
+    self, DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, VariantAccess, Visitor,
+};
+use std::fmt;
+
+impl de::Error for PlistError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        PlistError::Serde(msg.to_string())
+    }
+}
+
+impl serde::ser::Error for PlistError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        PlistError::Serde(msg.to_string())
+    }
+}
+
+/// Deserializes Rust types from a plist value.
+pub struct ValueDeserializer(pub Value);
+
+impl<'de> IntoDeserializer<'de, PlistError> for ValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+fn unexpected(value: &Value) -> de::Unexpected<'_> {
+    match value {
+        Value::String(s) => de::Unexpected::Str(s),
+        Value::Boolean(b) => de::Unexpected::Bool(*b),
+        Value::Real(f) => de::Unexpected::Float(*f),
+        Value::Data(d) => de::Unexpected::Bytes(d),
+        Value::Array(_) => de::Unexpected::Seq,
+        Value::Dictionary(_) => de::Unexpected::Map,
+        _ => de::Unexpected::Other("plist value"),
+    }
+}
+
+macro_rules! deserialize_integer {
+    ($method:ident, $visit:ident, $ty:ty) => {
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+            let parsed = match &self.0 {
+                Value::String(s) => s.trim().parse::<$ty>().ok(),
+                Value::Integer(i) => i
+                    .as_signed()
+                    .and_then(|n| <$ty>::try_from(n).ok())
+                    .or_else(|| i.as_unsigned().and_then(|n| <$ty>::try_from(n).ok())),
+                Value::Boolean(b) => Some(*b as $ty),
+                _ => None,
+            };
+            match parsed {
+                Some(n) => visitor.$visit(n),
+                None => Err(de::Error::invalid_type(unexpected(&self.0), &visitor)),
+            }
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = PlistError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        match self.0 {
+            Value::String(s) => visitor.visit_string(s),
+            Value::Boolean(b) => visitor.visit_bool(b),
+            Value::Integer(i) => match i.as_signed() {
+                Some(n) => visitor.visit_i64(n),
+                None => visitor.visit_u64(i.as_unsigned().unwrap_or_default()),
+            },
+            Value::Real(f) => visitor.visit_f64(f),
+            Value::Data(d) => visitor.visit_byte_buf(d),
+            Value::Date(d) => visitor.visit_string(d.to_xml_format()),
+            Value::Uid(u) => visitor.visit_u64(u.get()),
+            Value::Array(items) => {
+                let mut seq = SeqDeserializer::new(items.into_iter().map(ValueDeserializer));
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Value::Dictionary(dict) => {
+                let mut map =
+                    MapDeserializer::new(dict.into_iter().map(|(k, v)| (k, ValueDeserializer(v))));
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            _ => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        let parsed = match &self.0 {
+            Value::Boolean(b) => Some(*b),
+            Value::String(s) => crate::parse_bool(s.trim()),
+            Value::Integer(i) => i.as_signed().map(|n| n != 0),
+            _ => None,
+        };
+        match parsed {
+            Some(b) => visitor.visit_bool(b),
+            None => Err(de::Error::invalid_type(unexpected(&self.0), &visitor)),
+        }
+    }
+
+    deserialize_integer!(deserialize_i8, visit_i8, i8);
+    deserialize_integer!(deserialize_i16, visit_i16, i16);
+    deserialize_integer!(deserialize_i32, visit_i32, i32);
+    deserialize_integer!(deserialize_i64, visit_i64, i64);
+    deserialize_integer!(deserialize_u8, visit_u8, u8);
+    deserialize_integer!(deserialize_u16, visit_u16, u16);
+    deserialize_integer!(deserialize_u32, visit_u32, u32);
+    deserialize_integer!(deserialize_u64, visit_u64, u64);
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        let parsed = match &self.0 {
+            Value::Real(f) => Some(*f),
+            Value::String(s) => s.trim().parse::<f64>().ok(),
+            Value::Integer(i) => i
+                .as_signed()
+                .map(|n| n as f64)
+                .or_else(|| i.as_unsigned().map(|n| n as f64)),
+            _ => None,
+        };
+        match parsed {
+            Some(f) => visitor.visit_f64(f),
+            None => Err(de::Error::invalid_type(unexpected(&self.0), &visitor)),
+        }
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        match self.0 {
+            Value::String(s) => visitor.visit_string(s),
+            Value::Integer(i) => visitor.visit_string(i.to_string()),
+            Value::Real(f) => visitor.visit_string(f.to_string()),
+            Value::Boolean(b) => visitor.visit_string(if b { "YES" } else { "NO" }.to_string()),
+            Value::Date(d) => visitor.visit_string(d.to_xml_format()),
+            other => Err(de::Error::invalid_type(unexpected(&other), &visitor)),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        match self.0 {
+            Value::Data(d) => visitor.visit_byte_buf(d),
+            Value::String(s) => visitor.visit_byte_buf(s.into_bytes()),
+            other => ValueDeserializer(other).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        // Options nested in collections are written by plist's serializer
+        // as `{ None = ""; }` and `{ Some = value; }`.
+        match self.0 {
+            Value::Dictionary(dict) if dict.len() == 1 && dict.contains_key("None") => {
+                visitor.visit_none()
+            }
+            Value::Dictionary(mut dict) if dict.len() == 1 && dict.contains_key("Some") => {
+                visitor.visit_some(ValueDeserializer(dict.remove("Some").unwrap()))
+            }
+            other => visitor.visit_some(ValueDeserializer(other)),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, PlistError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, PlistError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        match self.0 {
+            Value::Array(_) => self.deserialize_any(visitor),
+            other => Err(de::Error::invalid_type(unexpected(&other), &visitor)),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, PlistError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, PlistError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        match self.0 {
+            Value::Dictionary(_) => self.deserialize_any(visitor),
+            other => Err(de::Error::invalid_type(unexpected(&other), &visitor)),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, PlistError> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, PlistError> {
+        match self.0 {
+            Value::String(s) => {
+                let variant: de::value::StringDeserializer<PlistError> = s.into_deserializer();
+                visitor.visit_enum(variant)
+            }
+            Value::Dictionary(dict) if dict.len() == 1 => {
+                let (variant, value) = dict.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer { variant, value })
+            }
+            other => Err(de::Error::invalid_type(unexpected(&other), &visitor)),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PlistError> {
+        visitor.visit_unit()
+    }
+}
+
+/// An externally tagged enum variant: `{ Variant = value; }`.
+struct EnumDeserializer {
+    variant: String,
+    value: Value,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = PlistError;
+    type Variant = ValueDeserializer;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<(S::Value, ValueDeserializer), PlistError> {
+        let variant: de::value::StringDeserializer<PlistError> = self.variant.into_deserializer();
+        let variant = seed.deserialize(variant)?;
+        Ok((variant, ValueDeserializer(self.value)))
+    }
+}
+
+impl<'de> VariantAccess<'de> for ValueDeserializer {
+    type Error = PlistError;
+
+    fn unit_variant(self) -> Result<(), PlistError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, PlistError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, PlistError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, PlistError> {
+        self.deserialize_map(visitor)
+    }
+}
@@ -0,0 +1,359 @@
+//! Source locations for parse errors.
+//!
+//! Every text format reports errors with a byte offset, line, column and
+//! the key path of the value being parsed. The underlying XML and JSON
+//! parsers give at most a line/column, so the remaining fields are
+//! recovered by rescanning the input.
+
+use crate::{ascii, PlistError};
+use quick_xml::escape::unescape;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::fmt;
+
+/// Location of a parse error.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Span {
+    /// Byte offset into the (decoded) input.
+    pub offset: usize,
+    /// 1-based line, or 0 for binary data.
+    pub line: usize,
+    /// 1-based column, or 0 for binary data.
+    pub column: usize,
+    /// Dictionary keys and array indices leading to the value.
+    pub key_path: Vec<String>,
+}
+
+impl Span {
+    /// Build a span for `offset` in text, computing line and column.
+    pub fn at_offset(text: &[u8], offset: usize) -> Span {
+        let offset = offset.min(text.len());
+        let before = &text[..offset];
+        let line = before.iter().filter(|&&c| c == b'\n').count() + 1;
+        let line_start = before.iter().rposition(|&c| c == b'\n').map_or(0, |i| i + 1);
+        let column = String::from_utf8_lossy(&before[line_start..]).chars().count() + 1;
+        Span {
+            offset,
+            line,
+            column,
+            key_path: Vec::new(),
+        }
+    }
+
+    /// The key path in PlistBuddy notation (`:key:0:subkey`).
+    pub fn key_path_string(&self) -> String {
+        self.key_path.iter().map(|k| format!(":{k}")).collect()
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line > 0 {
+            write!(f, "line {}, column {}", self.line, self.column)?;
+        } else {
+            write!(f, "offset {}", self.offset)?;
+        }
+        if !self.key_path.is_empty() {
+            write!(f, " (key path {})", self.key_path_string())?;
+        }
+        Ok(())
+    }
+}
+
+impl From<ascii::ParseError> for PlistError {
+    fn from(error: ascii::ParseError) -> Self {
+        PlistError::Syntax {
+            message: error.message,
+            span: Span {
+                offset: error.offset,
+                line: error.line,
+                column: error.column,
+                key_path: error.key_path,
+            },
+        }
+    }
+}
+
+/// Attach a location to an error from the XML or binary reader.
+///
+/// The message is the reader's own. Its errors end with " (offset N)" when
+/// the reader knows where it stopped, and the XML is walked again to turn
+/// that offset into the start of the element and its key path. Binary
+/// errors and errors without an offset are passed through.
+pub(crate) fn from_plist_error(error: plist::Error, data: &[u8], binary: bool) -> PlistError {
+    if binary || data.starts_with(&[0xFE, 0xFF]) || data.starts_with(&[0xFF, 0xFE]) {
+        return PlistError::Plist(error);
+    }
+    let text = error.to_string();
+    let Some((message, offset)) = text
+        .strip_suffix(')')
+        .and_then(|t| t.rsplit_once(" (offset "))
+        .and_then(|(message, offset)| Some((message, offset.parse::<usize>().ok()?)))
+    else {
+        return PlistError::Plist(error);
+    };
+    let (offset, key_path) = xml_error_at(data, offset);
+    let mut span = Span::at_offset(data, offset);
+    span.key_path = key_path;
+    PlistError::Syntax {
+        message: message.to_string(),
+        span,
+    }
+}
+
+/// Attach a location to an error from the JSON parser.
+pub(crate) fn from_json_error(error: serde_json::Error, data: &[u8]) -> PlistError {
+    if error.line() == 0 {
+        return PlistError::Json(error);
+    }
+    let offset = offset_of(data, error.line(), error.column());
+    let mut span = Span::at_offset(data, offset);
+    span.key_path = json_key_path_at(data, span.offset);
+
+    // serde_json appends " at line L column C" to its messages.
+    let text = error.to_string();
+    let message = match text.rfind(" at line ") {
+        Some(i) => text[..i].to_string(),
+        None => text,
+    };
+    PlistError::Syntax { message, span }
+}
+
+/// Byte offset of a 1-based line and column, as serde_json reports them
+/// (the column counts bytes).
+fn offset_of(data: &[u8], line: usize, column: usize) -> usize {
+    let mut offset = 0;
+    for _ in 1..line {
+        match data[offset..].iter().position(|&c| c == b'\n') {
+            Some(i) => offset += i + 1,
+            None => return data.len(),
+        }
+    }
+    (offset + column.saturating_sub(1)).min(data.len())
+}
+
+enum Frame {
+    Dict { key: Option<String>, expect_key: bool },
+    Array { index: usize },
+}
+
+fn frames_to_path(frames: &[Frame]) -> Vec<String> {
+    let mut path = Vec::new();
+    for frame in frames {
+        match frame {
+            Frame::Dict { key: Some(key), .. } => path.push(key.clone()),
+            Frame::Dict { key: None, .. } => break,
+            Frame::Array { index } => path.push(index.to_string()),
+        }
+    }
+    path
+}
+
+/// Mark the current value of the innermost container as complete.
+fn finish_value(frames: &mut [Frame]) {
+    match frames.last_mut() {
+        Some(Frame::Dict { key, expect_key }) => {
+            *key = None;
+            *expect_key = true;
+        }
+        Some(Frame::Array { index }) => *index += 1,
+        None => {}
+    }
+}
+
+/// Start of the XML element containing `offset`, where the reader
+/// stopped, and the key path of the value there. Only the nesting is
+/// tracked; whether the document is valid is left to the reader.
+fn xml_error_at(data: &[u8], offset: usize) -> (usize, Vec<String>) {
+    let mut reader = Reader::from_reader(data);
+    let mut frames: Vec<Frame> = Vec::new();
+
+    loop {
+        let event = match reader.read_event() {
+            Ok(Event::Eof) => return (offset.min(data.len()), frames_to_path(&frames)),
+            Ok(event) => event,
+            Err(_) => return (reader.error_position() as usize, frames_to_path(&frames)),
+        };
+        // Start of the tag just read.
+        let end = reader.buffer_position() as usize;
+        let start = data[..end].iter().rposition(|&c| c == b'<').unwrap_or(end);
+
+        let (tag, empty) = match &event {
+            Event::Start(e) => (e.name(), false),
+            Event::Empty(e) => (e.name(), true),
+            Event::End(e) => {
+                if end >= offset {
+                    return (start, frames_to_path(&frames));
+                }
+                if matches!(e.name().as_ref(), b"dict" | b"array") {
+                    frames.pop();
+                    finish_value(&mut frames);
+                }
+                continue;
+            }
+            _ => {
+                if end >= offset {
+                    return (start, frames_to_path(&frames));
+                }
+                continue;
+            }
+        };
+        let name = String::from_utf8_lossy(tag.as_ref()).to_string();
+
+        // Read the whole element, so an offset inside its text maps to its
+        // start.
+        let text = if empty || matches!(name.as_str(), "plist" | "dict" | "array") {
+            None
+        } else {
+            match reader.read_text(tag) {
+                Ok(text) => Some(text),
+                Err(_) => return (reader.error_position() as usize, frames_to_path(&frames)),
+            }
+        };
+        if reader.buffer_position() as usize >= offset {
+            return (start, frames_to_path(&frames));
+        }
+
+        match name.as_str() {
+            "plist" => {}
+            "key" => {
+                let text = text.map_or_else(String::new, |raw| {
+                    unescape(&raw).map(|t| t.to_string()).unwrap_or_else(|_| raw.to_string())
+                });
+                if let Some(Frame::Dict { key, expect_key }) = frames.last_mut() {
+                    *key = Some(text);
+                    *expect_key = false;
+                }
+            }
+            "dict" if !empty => frames.push(Frame::Dict {
+                key: None,
+                expect_key: true,
+            }),
+            "array" if !empty => frames.push(Frame::Array { index: 0 }),
+            _ => finish_value(&mut frames),
+        }
+    }
+}
+
+/// Key path of the value being parsed at `offset` in a JSON document.
+fn json_key_path_at(data: &[u8], offset: usize) -> Vec<String> {
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut pos = 0;
+
+    while pos < offset {
+        match data[pos] {
+            b'{' => frames.push(Frame::Dict {
+                key: None,
+                expect_key: true,
+            }),
+            b'[' => frames.push(Frame::Array { index: 0 }),
+            b'}' | b']' => {
+                frames.pop();
+            }
+            b':' => {
+                if let Some(Frame::Dict { expect_key, .. }) = frames.last_mut() {
+                    *expect_key = false;
+                }
+            }
+            b',' => match frames.last_mut() {
+                Some(Frame::Dict { expect_key, .. }) => *expect_key = true,
+                Some(Frame::Array { index }) => *index += 1,
+                None => {}
+            },
+            b'"' => {
+                let start = pos + 1;
+                let mut end = start;
+                while end < data.len() && data[end] != b'"' {
+                    end += if data[end] == b'\\' { 2 } else { 1 };
+                }
+                if let Some(Frame::Dict {
+                    key,
+                    expect_key: true,
+                }) = frames.last_mut()
+                {
+                    let raw = &data[start..end.min(data.len())];
+                    *key = Some(
+                        serde_json::from_slice::<String>(&data[pos..(end + 1).min(data.len())])
+                            .unwrap_or_else(|_| String::from_utf8_lossy(raw).to_string()),
+                    );
+                }
+                pos = end;
+            }
+            _ => {}
+        }
+        pos += 1;
+    }
+
+    frames_to_path(&frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{deserialize_with_format, PlistError, PlistFormat};
+
+    #[test]
+    fn test_xml_error_span() {
+        let data = b"<?xml version=\"1.0\"?>\n<plist version=\"1.0\">\n<dict>\n\t<key>A</key>\n\t<array>\n\t\t<string>x</string>\n\t\t<integer>nope</integer>\n\t</array>\n</dict>\n</plist>\n";
+        let error = deserialize_with_format(data, PlistFormat::Xml).unwrap_err();
+        let span = error.span().expect("span");
+        assert_eq!(span.line, 7);
+        assert_eq!(span.key_path, vec!["A", "1"]);
+    }
+
+    #[test]
+    fn test_xml_error_keeps_reader_message() {
+        // Within u64's digits, but out of range for the reader.
+        let data = b"<plist version=\"1.0\">\n<dict>\n\t<key>A</key>\n\t<integer>-18446744073709551615</integer>\n</dict>\n</plist>\n";
+        let error = deserialize_with_format(data, PlistFormat::Xml).unwrap_err();
+        let PlistError::Syntax { message, span } = error else {
+            panic!("expected a syntax error, got {error:?}");
+        };
+        assert_eq!(message, "InvalidIntegerString");
+        assert_eq!((span.line, span.column), (4, 2));
+        assert_eq!(span.key_path, vec!["A"]);
+    }
+
+    #[test]
+    fn test_xml_syntax_error_span() {
+        let data = b"<plist version=\"1.0\">\n<dict>\n\t<key>A</key>\n\t<string>x</integer>\n</dict>\n</plist>\n";
+        let error = deserialize_with_format(data, PlistFormat::Xml).unwrap_err();
+        let span = error.span().expect("span");
+        assert_eq!(span.line, 4);
+        assert_eq!(span.key_path, vec!["A"]);
+    }
+
+    #[test]
+    fn test_json_error_span() {
+        let data = b"{\n  \"a\": {\n    \"b\": [1, 2,, 3]\n  }\n}\n";
+        let error = deserialize_with_format(data, PlistFormat::Json).unwrap_err();
+        let span = error.span().expect("span");
+        assert_eq!((span.line, span.column), (3, 16));
+        assert_eq!(span.offset, 26);
+        assert_eq!(span.key_path, vec!["a", "b", "2"]);
+    }
+
+    #[test]
+    fn test_ascii_error_span() {
+        let error =
+            deserialize_with_format(b"{\n  a = (1, {b = ;});\n}", PlistFormat::Ascii)
+                .unwrap_err();
+        let span = error.span().expect("span");
+        assert_eq!(span.line, 2);
+        assert_eq!(span.key_path, vec!["a", "1", "b"]);
+    }
+
+    #[test]
+    fn test_columns_count_characters() {
+        for format in [PlistFormat::Ascii, PlistFormat::Json] {
+            let (plain, accented) = match format {
+                PlistFormat::Ascii => ("{ \"e\" = ; }", "{ \"é\" = ; }"),
+                _ => ("{ \"e\": , }", "{ \"é\": , }"),
+            };
+            let plain = deserialize_with_format(plain.as_bytes(), format).unwrap_err();
+            let accented = deserialize_with_format(accented.as_bytes(), format).unwrap_err();
+            let (plain, accented) = (plain.span().expect("span"), accented.span().expect("span"));
+            assert_eq!(plain.column, accented.column);
+            assert_eq!(plain.offset + 1, accented.offset);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Structural comparison of plists.
+//!
+//! Values are compared by content, independent of the serialization
+//! format and of dictionary key order. Arrays are compared by index.
+
+use crate::{escape_key, json};
+use plist::Value;
+use std::fmt;
+
+/// How a value differs between the old and new plist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    TypeChanged,
+    ValueChanged,
+}
+
+impl ChangeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::TypeChanged => "type-changed",
+            ChangeKind::ValueChanged => "value-changed",
+        }
+    }
+}
+
+/// One difference, at a path of dictionary keys and array indices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub path: Vec<String>,
+    pub kind: ChangeKind,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+impl Change {
+    /// The path in plutil notation, with keys escaped as `escape_key` does.
+    pub fn key_path(&self) -> String {
+        self.path
+            .iter()
+            .map(|part| escape_key(part, '.'))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// The change as a JSON object, with values in the tagged dialect so
+    /// data, dates and UIDs are preserved.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut map = serde_json::Map::new();
+        map.insert(
+            "path".to_string(),
+            serde_json::Value::Array(self.path.iter().cloned().map(serde_json::Value::String).collect()),
+        );
+        map.insert("change".to_string(), serde_json::Value::String(self.kind.name().to_string()));
+        if let Some(old) = &self.old {
+            map.insert("old".to_string(), json::to_tagged(old));
+        }
+        if let Some(new) = &self.new {
+            map.insert("new".to_string(), json::to_tagged(new));
+        }
+        serde_json::Value::Object(map)
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() { "<root>".to_string() } else { self.key_path() };
+        let show = |value: &Option<Value>| value.as_ref().map(|v| json::to_tagged(v).to_string()).unwrap_or_default();
+        match self.kind {
+            ChangeKind::Added => write!(f, "+ {path}: {}", show(&self.new)),
+            ChangeKind::Removed => write!(f, "- {path}: {}", show(&self.old)),
+            ChangeKind::ValueChanged => {
+                write!(f, "~ {path}: {} -> {}", show(&self.old), show(&self.new))
+            }
+            ChangeKind::TypeChanged => write!(
+                f,
+                "! {path}: {} {} -> {} {}",
+                type_name(self.old.as_ref()),
+                show(&self.old),
+                type_name(self.new.as_ref()),
+                show(&self.new)
+            ),
+        }
+    }
+}
+
+fn type_name(value: Option<&Value>) -> &'static str {
+    match value {
+        Some(Value::Boolean(_)) => "bool",
+        Some(Value::Integer(_)) => "integer",
+        Some(Value::Real(_)) => "float",
+        Some(Value::String(_)) => "string",
+        Some(Value::Date(_)) => "date",
+        Some(Value::Data(_)) => "data",
+        Some(Value::Array(_)) => "array",
+        Some(Value::Dictionary(_)) => "dictionary",
+        Some(Value::Uid(_)) => "uid",
+        _ => "unknown",
+    }
+}
+
+/// Compare two plists and list their differences in document order.
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_into(&mut Vec::new(), old, new, &mut changes);
+    changes
+}
+
+fn change(path: &[String], kind: ChangeKind, old: Option<&Value>, new: Option<&Value>) -> Change {
+    Change {
+        path: path.to_vec(),
+        kind,
+        old: old.cloned(),
+        new: new.cloned(),
+    }
+}
+
+fn diff_into(path: &mut Vec<String>, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Dictionary(a), Value::Dictionary(b)) => {
+            for (key, value) in a.iter() {
+                path.push(key.clone());
+                match b.get(key) {
+                    Some(other) => diff_into(path, value, other, changes),
+                    None => changes.push(change(path, ChangeKind::Removed, Some(value), None)),
+                }
+                path.pop();
+            }
+            for (key, value) in b.iter().filter(|(key, _)| !a.contains_key(key)) {
+                path.push(key.clone());
+                changes.push(change(path, ChangeKind::Added, None, Some(value)));
+                path.pop();
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                path.push(i.to_string());
+                match (a.get(i), b.get(i)) {
+                    (Some(x), Some(y)) => diff_into(path, x, y, changes),
+                    (Some(x), None) => changes.push(change(path, ChangeKind::Removed, Some(x), None)),
+                    (None, Some(y)) => changes.push(change(path, ChangeKind::Added, None, Some(y))),
+                    (None, None) => {}
+                }
+                path.pop();
+            }
+        }
+        _ if std::mem::discriminant(old) != std::mem::discriminant(new) => {
+            changes.push(change(path, ChangeKind::TypeChanged, Some(old), Some(new)));
+        }
+        _ if !same_scalar(old, new) => {
+            changes.push(change(path, ChangeKind::ValueChanged, Some(old), Some(new)));
+        }
+        _ => {}
+    }
+}
+
+/// Scalar equality where reals compare by bits, so NaN equals itself.
+fn same_scalar(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Real(x), Value::Real(y)) => x.to_bits() == y.to_bits() || x == y,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_ignores_format_and_order() {
+        let (a, _) = crate::deserialize(
+            b"{ CFBundleVersion = 1; \"com.example.app\" = { x = 1; }; Items = (a, b); Gone = yes; }",
+        )
+        .unwrap();
+        let (b, _) = crate::deserialize(
+            br#"{"Items": ["a", "c", "d"], "com.example.app": {"x": "1"}, "CFBundleVersion": 2, "New": true}"#,
+        )
+        .unwrap();
+
+        let changes = diff(&a, &b);
+        let summary: Vec<(String, ChangeKind)> =
+            changes.iter().map(|c| (c.key_path(), c.kind)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("CFBundleVersion".to_string(), ChangeKind::TypeChanged),
+                ("Items.1".to_string(), ChangeKind::ValueChanged),
+                ("Items.2".to_string(), ChangeKind::Added),
+                ("Gone".to_string(), ChangeKind::Removed),
+                ("New".to_string(), ChangeKind::Added),
+            ]
+        );
+        assert!(diff(&a, &a).is_empty());
+        assert_eq!(changes[1].to_string(), "~ Items.1: \"b\" -> \"c\"");
+    }
+
+    #[test]
+    fn test_key_path_escapes_keys() {
+        let (a, _) = crate::deserialize(br#"{ "a.b" = { "*" = 1; "x\\y" = 1; }; }"#).unwrap();
+        let (b, _) = crate::deserialize(br#"{ "a.b" = { "*" = 2; "x\\y" = 2; }; }"#).unwrap();
+        let paths: Vec<String> = diff(&a, &b).iter().map(Change::key_path).collect();
+        assert_eq!(paths, vec![r"a\.b.\*", r"a\.b.x\\y"]);
+    }
+}
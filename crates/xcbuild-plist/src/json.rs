@@ -0,0 +1,175 @@
+//! Tagged JSON dialect: a lossless JSON mapping for plist values.
+//!
+//! Apple's JSON output has no representation for data, dates or UIDs. The
+//! tagged dialect wraps them in single-key objects (`{"$data": "..."}`,
+//! `{"$date": "..."}`, `{"$uid": 3}`) and non-finite reals in
+//! `{"$real": "nan"}`. Dictionary keys starting with `$` are escaped by
+//! doubling the leading `$` so they can't be mistaken for tags.
+
+use crate::{base64_decode, base64_encode, PlistError};
+use plist::Value;
+
+const DATA_TAG: &str = "$data";
+const DATE_TAG: &str = "$date";
+const UID_TAG: &str = "$uid";
+const REAL_TAG: &str = "$real";
+
+fn tagged(tag: &str, value: serde_json::Value) -> serde_json::Value {
+    let mut map = serde_json::Map::new();
+    map.insert(tag.to_string(), value);
+    serde_json::Value::Object(map)
+}
+
+fn escape_key(key: &str) -> String {
+    if key.starts_with('$') {
+        format!("${key}")
+    } else {
+        key.to_string()
+    }
+}
+
+fn unescape_key(key: String) -> String {
+    match key.strip_prefix("$$") {
+        Some(rest) => format!("${rest}"),
+        None => key,
+    }
+}
+
+/// Convert a plist value to tagged JSON.
+pub fn to_tagged(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Boolean(b) => serde_json::Value::Bool(*b),
+        Value::Integer(i) => {
+            if let Some(n) = i.as_signed() {
+                serde_json::Value::Number(serde_json::Number::from(n))
+            } else if let Some(n) = i.as_unsigned() {
+                serde_json::Value::Number(serde_json::Number::from(n))
+            } else {
+                serde_json::Value::Null
+            }
+        }
+        Value::Real(f) => match serde_json::Number::from_f64(*f) {
+            Some(n) => serde_json::Value::Number(n),
+            None => {
+                let name = if f.is_nan() {
+                    "nan"
+                } else if *f > 0.0 {
+                    "inf"
+                } else {
+                    "-inf"
+                };
+                tagged(REAL_TAG, serde_json::Value::String(name.to_string()))
+            }
+        },
+        Value::String(s) => serde_json::Value::String(s.clone()),
+        Value::Data(d) => tagged(DATA_TAG, serde_json::Value::String(base64_encode(d))),
+        Value::Date(d) => tagged(DATE_TAG, serde_json::Value::String(d.to_xml_format())),
+        Value::Uid(uid) => tagged(UID_TAG, serde_json::Value::Number(uid.get().into())),
+        Value::Array(arr) => serde_json::Value::Array(arr.iter().map(to_tagged).collect()),
+        Value::Dictionary(dict) => {
+            let mut map = serde_json::Map::new();
+            for (k, v) in dict.iter() {
+                map.insert(escape_key(k), to_tagged(v));
+            }
+            serde_json::Value::Object(map)
+        }
+        _ => serde_json::Value::Null,
+    }
+}
+
+/// Convert tagged JSON back to a plist value. Unlike the Apple mapping,
+/// `null` is rejected since no plist value produces it.
+pub fn from_tagged(json: serde_json::Value) -> Result<Value, PlistError> {
+    match json {
+        serde_json::Value::Null => Err(PlistError::UnsupportedType(
+            "null has no plist representation".to_string(),
+        )),
+        serde_json::Value::Bool(b) => Ok(Value::Boolean(b)),
+        serde_json::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Ok(Value::Integer(i.into()))
+            } else if let Some(u) = n.as_u64() {
+                Ok(Value::Integer(u.into()))
+            } else {
+                Ok(Value::Real(n.as_f64().unwrap_or(f64::NAN)))
+            }
+        }
+        serde_json::Value::String(s) => Ok(Value::String(s)),
+        serde_json::Value::Array(arr) => Ok(Value::Array(
+            arr.into_iter().map(from_tagged).collect::<Result<_, _>>()?,
+        )),
+        serde_json::Value::Object(obj) => {
+            if obj.len() == 1 {
+                let (key, value) = obj.into_iter().next().unwrap();
+                return match key.as_str() {
+                    DATA_TAG | DATE_TAG | UID_TAG | REAL_TAG => from_tag(&key, value),
+                    _ => {
+                        let mut dict = plist::Dictionary::new();
+                        dict.insert(unescape_key(key), from_tagged(value)?);
+                        Ok(Value::Dictionary(dict))
+                    }
+                };
+            }
+            let mut dict = plist::Dictionary::new();
+            for (k, v) in obj {
+                dict.insert(unescape_key(k), from_tagged(v)?);
+            }
+            Ok(Value::Dictionary(dict))
+        }
+    }
+}
+
+fn from_tag(tag: &str, value: serde_json::Value) -> Result<Value, PlistError> {
+    let invalid = || PlistError::UnsupportedType(format!("invalid {tag} value"));
+    match (tag, value) {
+        (DATA_TAG, serde_json::Value::String(s)) => {
+            base64_decode(&s).map(Value::Data).map_err(|_| invalid())
+        }
+        (DATE_TAG, serde_json::Value::String(s)) => plist::Date::from_xml_format(&s)
+            .map(Value::Date)
+            .map_err(|_| invalid()),
+        (UID_TAG, serde_json::Value::Number(n)) => n
+            .as_u64()
+            .map(|n| Value::Uid(plist::Uid::new(n)))
+            .ok_or_else(invalid),
+        (REAL_TAG, serde_json::Value::String(s)) => match s.as_str() {
+            "nan" => Ok(Value::Real(f64::NAN)),
+            "inf" => Ok(Value::Real(f64::INFINITY)),
+            "-inf" => Ok(Value::Real(f64::NEG_INFINITY)),
+            _ => Err(invalid()),
+        },
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tagged_roundtrip() {
+        let mut dict = plist::Dictionary::new();
+        dict.insert("data".to_string(), Value::Data(vec![0, 1, 2, 255]));
+        dict.insert(
+            "date".to_string(),
+            Value::Date(plist::Date::from_xml_format("2024-01-02T03:04:05Z").unwrap()),
+        );
+        dict.insert("uid".to_string(), Value::Uid(plist::Uid::new(3)));
+        dict.insert("$data".to_string(), Value::String("not a tag".to_string()));
+        dict.insert("real".to_string(), Value::Real(1.0));
+        dict.insert("inf".to_string(), Value::Real(f64::NEG_INFINITY));
+        let value = Value::Dictionary(dict);
+
+        let json = to_tagged(&value);
+        assert_eq!(json["data"], serde_json::json!({ "$data": "AAEC/w==" }));
+        assert_eq!(json["uid"], serde_json::json!({ "$uid": 3 }));
+        assert_eq!(json["$$data"], serde_json::json!("not a tag"));
+        assert_eq!(from_tagged(json).unwrap(), value);
+    }
+
+    #[test]
+    fn test_tagged_rejects_null() {
+        assert!(from_tagged(serde_json::json!([1, null])).is_err());
+        assert!(from_tagged(serde_json::json!({ "$uid": "x" })).is_err());
+    }
+}
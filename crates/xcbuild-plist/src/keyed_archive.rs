@@ -0,0 +1,778 @@
+//! NSKeyedArchiver object graphs.
+//!
+//! A keyed archive is a plist dictionary with `$archiver`, `$version`,
+//! `$top` and `$objects` keys. Objects refer to each other through UIDs
+//! indexing `$objects`; UID 0 is the `$null` object. Instances carry a
+//! `$class` UID pointing at a `{$classname, $classes}` dictionary.
+//!
+//! [`Archive`] is the decoded object table with UIDs resolved into
+//! [`Item::Ref`]s, [`unarchive`] and [`archive`] convert between archives
+//! and plain plist values using the Foundation collection classes, and
+//! [`Archive::pretty`] renders the graph like `plutil -p`.
+
+use crate::PlistError;
+use plist::{Dictionary, Uid, Value};
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::time::{Duration, SystemTime};
+
+/// The `$archiver` value written by Foundation.
+pub const ARCHIVER: &str = "NSKeyedArchiver";
+/// The `$version` value written by Foundation.
+pub const VERSION: u64 = 100000;
+
+/// Seconds between the Unix epoch and the NSDate reference date (2001-01-01).
+const REFERENCE_DATE_OFFSET: u64 = 978_307_200;
+
+/// A value stored in `$top` or in an instance field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    /// UID 0, the `$null` object.
+    Null,
+    /// A reference to another object by its `$objects` index.
+    Ref(usize),
+    /// An inline scalar (integers and booleans are often stored inline).
+    Value(Value),
+    Array(Vec<Item>),
+    Dictionary(Vec<(String, Item)>),
+}
+
+/// Class description referenced by instances.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Class {
+    pub name: String,
+    /// The class followed by its superclasses, as in `$classes`.
+    pub hierarchy: Vec<String>,
+}
+
+/// An entry of the `$objects` table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    /// The `$null` placeholder at index 0.
+    Null,
+    /// A plain plist object (strings, numbers, data).
+    Value(Value),
+    Class(Class),
+    Instance {
+        /// Index of the class object.
+        class: usize,
+        fields: Vec<(String, Item)>,
+    },
+}
+
+/// A decoded keyed archive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Archive {
+    pub archiver: String,
+    pub version: u64,
+    pub top: Vec<(String, Item)>,
+    pub objects: Vec<Object>,
+}
+
+fn archive_error(message: impl Into<String>) -> PlistError {
+    PlistError::Archive(message.into())
+}
+
+/// Whether a plist looks like a keyed archive.
+pub fn is_keyed_archive(value: &Value) -> bool {
+    value.as_dictionary().is_some_and(|dict| {
+        dict.get("$archiver").and_then(Value::as_string).is_some()
+            && dict.get("$objects").and_then(Value::as_array).is_some()
+            && dict.get("$top").and_then(Value::as_dictionary).is_some()
+    })
+}
+
+impl Archive {
+    /// Decode the object table of a keyed archive plist.
+    pub fn decode(value: &Value) -> Result<Archive, PlistError> {
+        let dict = value
+            .as_dictionary()
+            .ok_or_else(|| archive_error("archive is not a dictionary"))?;
+        let archiver = dict
+            .get("$archiver")
+            .and_then(Value::as_string)
+            .ok_or_else(|| archive_error("missing $archiver"))?
+            .to_string();
+        let version = dict
+            .get("$version")
+            .and_then(Value::as_unsigned_integer)
+            .unwrap_or(VERSION);
+        let raw_objects = dict
+            .get("$objects")
+            .and_then(Value::as_array)
+            .ok_or_else(|| archive_error("missing $objects"))?;
+        let raw_top = dict
+            .get("$top")
+            .and_then(Value::as_dictionary)
+            .ok_or_else(|| archive_error("missing $top"))?;
+
+        let count = raw_objects.len();
+        let mut objects = Vec::with_capacity(count);
+        for (index, raw) in raw_objects.iter().enumerate() {
+            objects.push(decode_object(index, raw, raw_objects)?);
+        }
+        let top = decode_fields(raw_top, count)?;
+
+        Ok(Archive {
+            archiver,
+            version,
+            top,
+            objects,
+        })
+    }
+
+    /// Encode the object table back into a keyed archive plist.
+    pub fn encode(&self) -> Value {
+        let objects = self
+            .objects
+            .iter()
+            .map(|object| match object {
+                Object::Null => Value::String("$null".to_string()),
+                Object::Value(value) => value.clone(),
+                Object::Class(class) => {
+                    let mut dict = Dictionary::new();
+                    dict.insert("$classname".to_string(), Value::String(class.name.clone()));
+                    dict.insert(
+                        "$classes".to_string(),
+                        Value::Array(class.hierarchy.iter().cloned().map(Value::String).collect()),
+                    );
+                    Value::Dictionary(dict)
+                }
+                Object::Instance { class, fields } => {
+                    let mut dict = encode_fields(fields);
+                    dict.insert("$class".to_string(), Value::Uid(Uid::new(*class as u64)));
+                    Value::Dictionary(dict)
+                }
+            })
+            .collect();
+
+        let mut dict = Dictionary::new();
+        dict.insert("$archiver".to_string(), Value::String(self.archiver.clone()));
+        dict.insert("$objects".to_string(), Value::Array(objects));
+        dict.insert("$top".to_string(), Value::Dictionary(encode_fields(&self.top)));
+        dict.insert("$version".to_string(), Value::Integer(self.version.into()));
+        Value::Dictionary(dict)
+    }
+
+    /// The class of an instance object.
+    pub fn class_of(&self, index: usize) -> Option<&Class> {
+        match self.objects.get(index)? {
+            Object::Instance { class, .. } => match self.objects.get(*class)? {
+                Object::Class(class) => Some(class),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn field<'a>(&'a self, index: usize, name: &str) -> Option<&'a Item> {
+        match self.objects.get(index)? {
+            Object::Instance { fields, .. } => {
+                fields.iter().find(|(key, _)| key == name).map(|(_, item)| item)
+            }
+            _ => None,
+        }
+    }
+
+    /// Resolve an item into a plain plist value, turning Foundation
+    /// collections, strings, data and dates into their plist equivalents.
+    /// Other instances become dictionaries with a `$class` key. Each
+    /// collection or other instance is expanded once; later references to
+    /// it, including references back into it, become UIDs.
+    pub fn resolve(&self, item: &Item) -> Value {
+        self.resolve_item(item, &mut HashSet::new())
+    }
+
+    fn resolve_item(&self, item: &Item, resolved: &mut HashSet<usize>) -> Value {
+        match item {
+            Item::Null => Value::String("$null".to_string()),
+            Item::Value(value) => value.clone(),
+            Item::Array(items) => {
+                Value::Array(items.iter().map(|i| self.resolve_item(i, resolved)).collect())
+            }
+            Item::Dictionary(fields) => {
+                let mut dict = Dictionary::new();
+                for (key, item) in fields {
+                    dict.insert(key.clone(), self.resolve_item(item, resolved));
+                }
+                Value::Dictionary(dict)
+            }
+            Item::Ref(index) => {
+                // Strings, data and dates are small enough to repeat.
+                let scalar = matches!(
+                    foundation_kind(self.class_of(*index)),
+                    Some(Foundation::String | Foundation::Data | Foundation::Date)
+                );
+                let instance = matches!(self.objects.get(*index), Some(Object::Instance { .. }));
+                if instance && !scalar && !resolved.insert(*index) {
+                    return Value::Uid(Uid::new(*index as u64));
+                }
+                self.resolve_object(*index, resolved)
+            }
+        }
+    }
+
+    fn resolve_object(&self, index: usize, resolved: &mut HashSet<usize>) -> Value {
+        let (class, fields) = match &self.objects[index] {
+            Object::Null => return Value::String("$null".to_string()),
+            Object::Value(value) => return value.clone(),
+            Object::Class(class) => return Value::String(class.name.clone()),
+            Object::Instance { fields, .. } => (self.class_of(index), fields),
+        };
+        let class_name = class.map_or("", |c| c.name.as_str());
+
+        match foundation_kind(class) {
+            Some(Foundation::Array) => {
+                let items = self.field(index, "NS.objects").cloned().unwrap_or(Item::Array(Vec::new()));
+                return self.resolve_item(&items, resolved);
+            }
+            Some(Foundation::Dictionary) => {
+                if let (Some(Item::Array(keys)), Some(Item::Array(values))) =
+                    (self.field(index, "NS.keys"), self.field(index, "NS.objects"))
+                {
+                    let mut dict = Dictionary::new();
+                    for (key, value) in keys.iter().zip(values) {
+                        let key = match self.resolve_item(key, resolved) {
+                            Value::String(s) => s,
+                            other => format!("{other:?}"),
+                        };
+                        dict.insert(key, self.resolve_item(value, resolved));
+                    }
+                    return Value::Dictionary(dict);
+                }
+            }
+            Some(Foundation::String) => {
+                if let Some(item) = self.field(index, "NS.string") {
+                    return self.resolve_item(item, resolved);
+                }
+            }
+            Some(Foundation::Data) => {
+                if let Some(item) = self.field(index, "NS.data") {
+                    return self.resolve_item(item, resolved);
+                }
+            }
+            Some(Foundation::Date) => {
+                if let Some(date) = self
+                    .field(index, "NS.time")
+                    .and_then(|item| match item {
+                        Item::Value(value) => value.as_real(),
+                        _ => None,
+                    })
+                    .and_then(date_from_reference_seconds)
+                {
+                    return Value::Date(date);
+                }
+            }
+            None => {}
+        }
+
+        let mut dict = Dictionary::new();
+        dict.insert("$class".to_string(), Value::String(class_name.to_string()));
+        for (key, item) in fields {
+            dict.insert(key.clone(), self.resolve_item(item, resolved));
+        }
+        Value::Dictionary(dict)
+    }
+
+    /// Render the object graph in the style of `plutil -p`. Instances are
+    /// shown as `ClassName #index`; an object already printed is shown as
+    /// a back reference instead of being expanded again.
+    pub fn pretty(&self) -> String {
+        let mut output = String::new();
+        let mut printed = HashSet::new();
+        output.push_str("{\n");
+        for (key, item) in &self.top {
+            let _ = write!(output, "  {} => ", quote(key));
+            self.pretty_item(&mut output, item, 1, &mut printed);
+            output.push('\n');
+        }
+        output.push_str("}\n");
+        output
+    }
+
+    fn pretty_item(&self, output: &mut String, item: &Item, depth: usize, printed: &mut HashSet<usize>) {
+        match item {
+            Item::Null => output.push_str("nil"),
+            Item::Value(value) => pretty_value(output, value, depth),
+            Item::Array(items) => {
+                output.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    let _ = write!(output, "{}{i} => ", indent(depth + 1));
+                    self.pretty_item(output, item, depth + 1, printed);
+                    output.push('\n');
+                }
+                let _ = write!(output, "{}]", indent(depth));
+            }
+            Item::Dictionary(fields) => self.pretty_fields(output, fields, depth, printed),
+            Item::Ref(index) => self.pretty_object(output, *index, depth, printed),
+        }
+    }
+
+    fn pretty_object(&self, output: &mut String, index: usize, depth: usize, printed: &mut HashSet<usize>) {
+        match &self.objects[index] {
+            Object::Null => output.push_str("nil"),
+            Object::Value(value) => pretty_value(output, value, depth),
+            Object::Class(class) => output.push_str(&class.name),
+            Object::Instance { fields, .. } => {
+                let name = self.class_of(index).map_or("?", |c| c.name.as_str());
+                let _ = write!(output, "{name} #{index}");
+                if !printed.insert(index) {
+                    output.push_str(" (see above)");
+                    return;
+                }
+                output.push(' ');
+                match foundation_kind(self.class_of(index)) {
+                    Some(Foundation::Array) => {
+                        let items = self.field(index, "NS.objects").cloned().unwrap_or(Item::Array(Vec::new()));
+                        self.pretty_item(output, &items, depth, printed);
+                    }
+                    Some(Foundation::Dictionary) => {
+                        let entries = match (self.field(index, "NS.keys"), self.field(index, "NS.objects")) {
+                            (Some(Item::Array(keys)), Some(Item::Array(values))) => keys
+                                .iter()
+                                .zip(values)
+                                .map(|(key, value)| {
+                                    let key = match self.resolve(key) {
+                                        Value::String(s) => s,
+                                        other => format!("{other:?}"),
+                                    };
+                                    (key, value.clone())
+                                })
+                                .collect(),
+                            _ => Vec::new(),
+                        };
+                        self.pretty_fields(output, &entries, depth, printed);
+                    }
+                    _ => self.pretty_fields(output, fields, depth, printed),
+                }
+            }
+        }
+    }
+
+    fn pretty_fields(
+        &self,
+        output: &mut String,
+        fields: &[(String, Item)],
+        depth: usize,
+        printed: &mut HashSet<usize>,
+    ) {
+        output.push_str("{\n");
+        for (key, item) in fields {
+            let _ = write!(output, "{}{} => ", indent(depth + 1), quote(key));
+            self.pretty_item(output, item, depth + 1, printed);
+            output.push('\n');
+        }
+        let _ = write!(output, "{}}}", indent(depth));
+    }
+}
+
+enum Foundation {
+    Array,
+    Dictionary,
+    String,
+    Data,
+    Date,
+}
+
+fn foundation_kind(class: Option<&Class>) -> Option<Foundation> {
+    match class?.name.as_str() {
+        "NSArray" | "NSMutableArray" | "NSSet" | "NSMutableSet" | "NSOrderedSet"
+        | "NSMutableOrderedSet" => Some(Foundation::Array),
+        "NSDictionary" | "NSMutableDictionary" => Some(Foundation::Dictionary),
+        "NSString" | "NSMutableString" => Some(Foundation::String),
+        "NSData" | "NSMutableData" => Some(Foundation::Data),
+        "NSDate" => Some(Foundation::Date),
+        _ => None,
+    }
+}
+
+fn decode_object(index: usize, raw: &Value, raw_objects: &[Value]) -> Result<Object, PlistError> {
+    if index == 0 && raw.as_string() == Some("$null") {
+        return Ok(Object::Null);
+    }
+    let Some(dict) = raw.as_dictionary() else {
+        return Ok(Object::Value(raw.clone()));
+    };
+
+    if let Some(name) = dict.get("$classname").and_then(Value::as_string) {
+        let hierarchy = dict
+            .get("$classes")
+            .and_then(Value::as_array)
+            .map(|classes| classes.iter().filter_map(|c| c.as_string().map(str::to_string)).collect())
+            .unwrap_or_else(|| vec![name.to_string()]);
+        return Ok(Object::Class(Class {
+            name: name.to_string(),
+            hierarchy,
+        }));
+    }
+
+    match dict.get("$class") {
+        Some(Value::Uid(uid)) => {
+            let class = uid.get() as usize;
+            let is_class = raw_objects
+                .get(class)
+                .and_then(Value::as_dictionary)
+                .is_some_and(|d| d.contains_key("$classname"));
+            if !is_class {
+                return Err(archive_error(format!(
+                    "object {index} has $class {class} which is not a class"
+                )));
+            }
+            let mut fields = Vec::with_capacity(dict.len() - 1);
+            for (key, value) in dict.iter().filter(|(key, _)| *key != "$class") {
+                fields.push((key.clone(), decode_item(value, raw_objects.len())?));
+            }
+            Ok(Object::Instance { class, fields })
+        }
+        Some(_) => Err(archive_error(format!("object {index} has an invalid $class"))),
+        None => Ok(Object::Value(raw.clone())),
+    }
+}
+
+fn decode_fields(dict: &Dictionary, count: usize) -> Result<Vec<(String, Item)>, PlistError> {
+    dict.iter()
+        .map(|(key, value)| Ok((key.clone(), decode_item(value, count)?)))
+        .collect()
+}
+
+fn decode_item(value: &Value, count: usize) -> Result<Item, PlistError> {
+    match value {
+        Value::Uid(uid) => match uid.get() as usize {
+            0 => Ok(Item::Null),
+            index if index < count => Ok(Item::Ref(index)),
+            index => Err(archive_error(format!("UID {index} is out of range"))),
+        },
+        Value::Array(items) => Ok(Item::Array(
+            items.iter().map(|v| decode_item(v, count)).collect::<Result<_, _>>()?,
+        )),
+        Value::Dictionary(dict) => Ok(Item::Dictionary(decode_fields(dict, count)?)),
+        other => Ok(Item::Value(other.clone())),
+    }
+}
+
+fn encode_fields(fields: &[(String, Item)]) -> Dictionary {
+    let mut dict = Dictionary::new();
+    for (key, item) in fields {
+        dict.insert(key.clone(), encode_item(item));
+    }
+    dict
+}
+
+fn encode_item(item: &Item) -> Value {
+    match item {
+        Item::Null => Value::Uid(Uid::new(0)),
+        Item::Ref(index) => Value::Uid(Uid::new(*index as u64)),
+        Item::Value(value) => value.clone(),
+        Item::Array(items) => Value::Array(items.iter().map(encode_item).collect()),
+        Item::Dictionary(fields) => Value::Dictionary(encode_fields(fields)),
+    }
+}
+
+/// Decode a keyed archive and resolve its `root` object (or the whole
+/// `$top` dictionary when there is no `root`) into a plain plist value.
+pub fn unarchive(value: &Value) -> Result<Value, PlistError> {
+    let archive = Archive::decode(value)?;
+    match archive.top.iter().find(|(key, _)| key == "root") {
+        Some((_, item)) => Ok(archive.resolve(item)),
+        None => Ok(archive.resolve(&Item::Dictionary(archive.top.clone()))),
+    }
+}
+
+/// Archive a plain plist value as Foundation objects under the `root` key.
+pub fn archive(value: &Value) -> Value {
+    let mut builder = Builder {
+        objects: vec![Object::Null],
+    };
+    let root = builder.add(value);
+    Archive {
+        archiver: ARCHIVER.to_string(),
+        version: VERSION,
+        top: vec![("root".to_string(), root)],
+        objects: builder.objects,
+    }
+    .encode()
+}
+
+struct Builder {
+    objects: Vec<Object>,
+}
+
+impl Builder {
+    fn push(&mut self, object: Object) -> Item {
+        self.objects.push(object);
+        Item::Ref(self.objects.len() - 1)
+    }
+
+    fn class(&mut self, hierarchy: &[&str]) -> usize {
+        let class = Class {
+            name: hierarchy[0].to_string(),
+            hierarchy: hierarchy.iter().map(|s| s.to_string()).collect(),
+        };
+        if let Some(index) = self.objects.iter().position(|o| *o == Object::Class(class.clone())) {
+            return index;
+        }
+        self.objects.push(Object::Class(class));
+        self.objects.len() - 1
+    }
+
+    fn add(&mut self, value: &Value) -> Item {
+        match value {
+            Value::Array(items) => {
+                let index = self.reserve();
+                let objects = items.iter().map(|v| self.add(v)).collect();
+                self.finish(index, &["NSArray", "NSObject"], vec![("NS.objects".to_string(), Item::Array(objects))])
+            }
+            Value::Dictionary(dict) => {
+                let index = self.reserve();
+                let mut keys = Vec::with_capacity(dict.len());
+                let mut objects = Vec::with_capacity(dict.len());
+                for (key, value) in dict.iter() {
+                    keys.push(self.push(Object::Value(Value::String(key.clone()))));
+                    objects.push(self.add(value));
+                }
+                self.finish(
+                    index,
+                    &["NSDictionary", "NSObject"],
+                    vec![
+                        ("NS.keys".to_string(), Item::Array(keys)),
+                        ("NS.objects".to_string(), Item::Array(objects)),
+                    ],
+                )
+            }
+            Value::Date(date) => {
+                let index = self.reserve();
+                let seconds = reference_seconds_from_date(*date);
+                self.finish(
+                    index,
+                    &["NSDate", "NSObject"],
+                    vec![("NS.time".to_string(), Item::Value(Value::Real(seconds)))],
+                )
+            }
+            Value::Uid(uid) => Item::Value(Value::Uid(*uid)),
+            other => self.push(Object::Value(other.clone())),
+        }
+    }
+
+    /// Reserve a slot so an instance precedes its children, as in
+    /// archives written by Foundation.
+    fn reserve(&mut self) -> usize {
+        self.objects.push(Object::Null);
+        self.objects.len() - 1
+    }
+
+    fn finish(&mut self, index: usize, hierarchy: &[&str], fields: Vec<(String, Item)>) -> Item {
+        let class = self.class(hierarchy);
+        self.objects[index] = Object::Instance { class, fields };
+        Item::Ref(index)
+    }
+}
+
+/// Convert `NS.time`, or `None` if it is not finite or not representable.
+fn date_from_reference_seconds(seconds: f64) -> Option<plist::Date> {
+    let epoch = SystemTime::UNIX_EPOCH + Duration::from_secs(REFERENCE_DATE_OFFSET);
+    let time = if seconds >= 0.0 {
+        epoch.checked_add(Duration::try_from_secs_f64(seconds).ok()?)?
+    } else {
+        epoch.checked_sub(Duration::try_from_secs_f64(-seconds).ok()?)?
+    };
+    Some(time.into())
+}
+
+fn reference_seconds_from_date(date: plist::Date) -> f64 {
+    let epoch = SystemTime::UNIX_EPOCH + Duration::from_secs(REFERENCE_DATE_OFFSET);
+    let time: SystemTime = date.into();
+    match time.duration_since(epoch) {
+        Ok(d) => d.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    }
+}
+
+fn indent(depth: usize) -> String {
+    "  ".repeat(depth)
+}
+
+fn quote(s: &str) -> String {
+    format!("{s:?}")
+}
+
+fn pretty_value(output: &mut String, value: &Value, depth: usize) {
+    match value {
+        Value::String(s) => output.push_str(&quote(s)),
+        Value::Boolean(b) => output.push_str(if *b { "true" } else { "false" }),
+        Value::Integer(i) => {
+            let _ = write!(output, "{i}");
+        }
+        Value::Real(f) => {
+            let _ = write!(output, "{f}");
+        }
+        Value::Data(data) => {
+            let _ = write!(output, "{{length = {}, bytes = 0x", data.len());
+            for byte in data.iter().take(24) {
+                let _ = write!(output, "{byte:02x}");
+            }
+            if data.len() > 24 {
+                output.push_str("...");
+            }
+            output.push('}');
+        }
+        Value::Date(date) => {
+            let text = date.to_xml_format();
+            output.push_str(&text.replace('T', " ").replace('Z', " +0000"));
+        }
+        Value::Uid(uid) => {
+            let _ = write!(output, "<CFKeyedArchiverUID>{{value = {}}}", uid.get());
+        }
+        Value::Array(items) => {
+            output.push_str("[\n");
+            for (i, item) in items.iter().enumerate() {
+                let _ = write!(output, "{}{i} => ", indent(depth + 1));
+                pretty_value(output, item, depth + 1);
+                output.push('\n');
+            }
+            let _ = write!(output, "{}]", indent(depth));
+        }
+        Value::Dictionary(dict) => {
+            output.push_str("{\n");
+            for (key, item) in dict.iter() {
+                let _ = write!(output, "{}{} => ", indent(depth + 1), quote(key));
+                pretty_value(output, item, depth + 1);
+                output.push('\n');
+            }
+            let _ = write!(output, "{}}}", indent(depth));
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let mut inner = Dictionary::new();
+        inner.insert("name".to_string(), Value::String("Main".to_string()));
+        inner.insert("data".to_string(), Value::Data(vec![1, 2, 3]));
+        inner.insert(
+            "when".to_string(),
+            Value::Date(plist::Date::from_xml_format("2024-01-02T03:04:05Z").unwrap()),
+        );
+        let value = Value::Array(vec![
+            Value::Dictionary(inner),
+            Value::Integer(7.into()),
+            Value::Boolean(true),
+        ]);
+
+        let archived = archive(&value);
+        assert!(is_keyed_archive(&archived));
+        let bytes = crate::serialize(&archived, crate::PlistFormat::Binary).unwrap();
+        let (reread, _) = crate::deserialize(&bytes).unwrap();
+        assert_eq!(unarchive(&reread).unwrap(), value);
+
+        let decoded = Archive::decode(&reread).unwrap();
+        assert_eq!(decoded.encode(), reread);
+    }
+
+    #[test]
+    fn test_cycles_and_pretty() {
+        // A custom object whose "parent" field points back at itself.
+        let archive = Archive {
+            archiver: ARCHIVER.to_string(),
+            version: VERSION,
+            top: vec![("root".to_string(), Item::Ref(1))],
+            objects: vec![
+                Object::Null,
+                Object::Instance {
+                    class: 3,
+                    fields: vec![
+                        ("title".to_string(), Item::Ref(2)),
+                        ("parent".to_string(), Item::Ref(1)),
+                        ("missing".to_string(), Item::Null),
+                    ],
+                },
+                Object::Value(Value::String("Window".to_string())),
+                Object::Class(Class {
+                    name: "IBWindow".to_string(),
+                    hierarchy: vec!["IBWindow".to_string(), "NSObject".to_string()],
+                }),
+            ],
+        };
+        let decoded = Archive::decode(&archive.encode()).unwrap();
+        assert_eq!(decoded.class_of(1).unwrap().name, "IBWindow");
+
+        let resolved = unarchive(&archive.encode()).unwrap();
+        let dict = resolved.as_dictionary().unwrap();
+        assert_eq!(dict.get("$class"), Some(&Value::String("IBWindow".to_string())));
+        assert_eq!(dict.get("parent"), Some(&Value::Uid(Uid::new(1))));
+
+        let pretty = archive.pretty();
+        assert!(pretty.contains("\"root\" => IBWindow #1 {"));
+        assert!(pretty.contains("\"parent\" => IBWindow #1 (see above)"));
+        assert!(pretty.contains("\"missing\" => nil"));
+    }
+
+    #[test]
+    fn test_shared_objects_resolve_once() {
+        // Views 1..=40 each reference the next one twice, as with
+        // NSNextKeyView chains alongside NSSubviews. Expanding every
+        // reference would take 2^40 steps.
+        let depth = 40;
+        let class = depth + 2;
+        let mut objects = vec![Object::Null];
+        for view in 1..=depth {
+            let next = if view < depth { Item::Ref(view + 1) } else { Item::Null };
+            objects.push(Object::Instance {
+                class,
+                fields: vec![
+                    ("subview".to_string(), next.clone()),
+                    ("nextKeyView".to_string(), next),
+                    ("title".to_string(), Item::Ref(depth + 1)),
+                ],
+            });
+        }
+        objects.push(Object::Value(Value::String("View".to_string())));
+        objects.push(Object::Class(Class {
+            name: "NSView".to_string(),
+            hierarchy: vec!["NSView".to_string(), "NSObject".to_string()],
+        }));
+        let archive = Archive {
+            archiver: ARCHIVER.to_string(),
+            version: VERSION,
+            top: vec![("root".to_string(), Item::Ref(1))],
+            objects,
+        };
+
+        let resolved = unarchive(&archive.encode()).unwrap();
+        let root = resolved.as_dictionary().unwrap();
+        let subview = root.get("subview").and_then(Value::as_dictionary).unwrap();
+        assert_eq!(subview.get("title"), Some(&Value::String("View".to_string())));
+        assert_eq!(root.get("nextKeyView"), Some(&Value::Uid(Uid::new(2))));
+        assert_eq!(subview.get("nextKeyView"), Some(&Value::Uid(Uid::new(3))));
+    }
+
+    #[test]
+    fn test_invalid_date_is_left_unresolved() {
+        for seconds in [f64::NAN, f64::INFINITY, -1e300] {
+            let archive = Archive {
+                archiver: ARCHIVER.to_string(),
+                version: VERSION,
+                top: vec![("root".to_string(), Item::Ref(1))],
+                objects: vec![
+                    Object::Null,
+                    Object::Instance {
+                        class: 2,
+                        fields: vec![("NS.time".to_string(), Item::Value(Value::Real(seconds)))],
+                    },
+                    Object::Class(Class {
+                        name: "NSDate".to_string(),
+                        hierarchy: vec!["NSDate".to_string(), "NSObject".to_string()],
+                    }),
+                ],
+            };
+            let resolved = unarchive(&archive.encode()).unwrap();
+            let dict = resolved.as_dictionary().unwrap();
+            assert_eq!(dict.get("$class"), Some(&Value::String("NSDate".to_string())));
+            assert!(archive.pretty().contains("NSDate #1"));
+        }
+    }
+}
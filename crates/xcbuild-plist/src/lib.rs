@@ -1,5 +1,14 @@
 pub mod ascii;
+pub mod diagnostic;
+pub mod diff;
+pub mod json;
+pub mod keyed_archive;
+pub mod merge;
+pub mod schema;
+pub mod strings;
 
+pub use diagnostic::Span;
+pub use merge::{merge, ArrayMerge, Conflict, MergeOptions};
 use plist::Value;
 use std::io::Cursor;
 use thiserror::Error;
@@ -14,12 +23,41 @@ pub enum PlistError {
     Json(#[from] serde_json::Error),
     #[error("ASCII parse error: {0}")]
     AsciiParse(String),
+    #[error("{message} at {span}")]
+    Syntax { message: String, span: Span },
     #[error("unknown format")]
     UnknownFormat,
     #[error("invalid key path: {0}")]
     InvalidKeyPath(String),
+    #[error("invalid escape in key path: {0}")]
+    InvalidEscape(String),
     #[error("unsupported type: {0}")]
     UnsupportedType(String),
+    #[error("keyed archive error: {0}")]
+    Archive(String),
+    #[error("serialization error: {0}")]
+    Serde(String),
+    #[error("merge conflict at {0}")]
+    MergeConflict(String),
+}
+
+impl PlistError {
+    /// Location of the error in the input, for parse errors.
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            PlistError::Syntax { span, .. } => Some(span),
+            _ => None,
+        }
+    }
+
+    /// The error without its location, for diagnostics that print the
+    /// span separately.
+    pub fn message(&self) -> String {
+        match self {
+            PlistError::Syntax { message, .. } => message.clone(),
+            other => other.to_string(),
+        }
+    }
 }
 
 /// Plist formats supported by this crate.
@@ -29,9 +67,16 @@ pub enum PlistFormat {
     Binary,
     Ascii,
     Json,
+    /// Lossless JSON with tagged data, date and UID values; see [`json`].
+    TaggedJson,
+    Strings,
     Raw,
     Swift,
     Objc,
+    /// Swift `Codable` structs inferred from the value's shape.
+    SwiftCodable,
+    /// Objective-C interfaces inferred from the value's shape.
+    ObjcInterface,
 }
 
 impl PlistFormat {
@@ -41,9 +86,13 @@ impl PlistFormat {
             PlistFormat::Binary => "binary1",
             PlistFormat::Ascii => "openstep1",
             PlistFormat::Json => "json",
+            PlistFormat::TaggedJson => "json-tagged",
+            PlistFormat::Strings => "strings",
             PlistFormat::Raw => "raw",
             PlistFormat::Swift => "swift",
             PlistFormat::Objc => "objc",
+            PlistFormat::SwiftCodable => "swift-codable",
+            PlistFormat::ObjcInterface => "objc-interface",
         }
     }
 
@@ -53,9 +102,13 @@ impl PlistFormat {
             "binary1" => Some(PlistFormat::Binary),
             "openstep1" | "ascii1" => Some(PlistFormat::Ascii),
             "json" => Some(PlistFormat::Json),
+            "json-tagged" => Some(PlistFormat::TaggedJson),
+            "strings" => Some(PlistFormat::Strings),
             "raw" => Some(PlistFormat::Raw),
             "swift" => Some(PlistFormat::Swift),
             "objc" => Some(PlistFormat::Objc),
+            "swift-codable" => Some(PlistFormat::SwiftCodable),
+            "objc-interface" => Some(PlistFormat::ObjcInterface),
             _ => None,
         }
     }
@@ -96,6 +149,16 @@ pub fn identify_format(data: &[u8]) -> Option<PlistFormat> {
         return Some(PlistFormat::Binary);
     }
 
+    // UTF-16 text (typically .strings files): identify the decoded text
+    if strings::detect_encoding(data) != strings::TextEncoding::Utf8 {
+        let (text, _) = strings::decode(data).ok()?;
+        return identify_text_format(text.as_bytes());
+    }
+
+    identify_text_format(data)
+}
+
+fn identify_text_format(data: &[u8]) -> Option<PlistFormat> {
     let trimmed = skip_whitespace_and_bom(data);
 
     if trimmed.starts_with(b"<?xml")
@@ -105,22 +168,96 @@ pub fn identify_format(data: &[u8]) -> Option<PlistFormat> {
         return Some(PlistFormat::Xml);
     }
 
-    // JSON starts with { or [ and uses : for key-value separation
-    if !trimmed.is_empty() && (trimmed[0] == b'{' || trimmed[0] == b'[') {
+    // JSON starts with { or [ and uses : for key-value separation;
+    // ASCII dictionaries also start with { but use =
+    if trimmed.starts_with(b"[") {
         return Some(PlistFormat::Json);
     }
+    if trimmed.starts_with(b"{") {
+        return Some(if looks_like_json_object(trimmed) {
+            PlistFormat::Json
+        } else {
+            PlistFormat::Ascii
+        });
+    }
 
-    // ASCII/OpenStep format
+    // ASCII/OpenStep format, or a .strings file (braceless dictionary,
+    // often starting with a comment)
     if !trimmed.is_empty() {
         let first = trimmed[0];
-        if first == b'"' || first.is_ascii_alphanumeric() || first == b'(' {
-            return Some(PlistFormat::Ascii);
+        if first == b'"' || first.is_ascii_alphanumeric() || first == b'(' || first == b'/' {
+            return Some(if looks_like_strings(trimmed) {
+                PlistFormat::Strings
+            } else {
+                PlistFormat::Ascii
+            });
         }
     }
 
     None
 }
 
+fn skip_comments(mut data: &[u8]) -> &[u8] {
+    loop {
+        data = skip_whitespace_and_bom(data);
+        if let Some(rest) = data.strip_prefix(b"//") {
+            let end = rest.iter().position(|&c| c == b'\n').unwrap_or(rest.len());
+            data = &rest[end..];
+        } else if let Some(rest) = data.strip_prefix(b"/*") {
+            let end = rest.windows(2).position(|w| w == b"*/").map(|p| p + 2);
+            data = &rest[end.unwrap_or(rest.len())..];
+        } else {
+            return data;
+        }
+    }
+}
+
+/// Skip a leading quoted string, returning the rest of the input.
+fn skip_quoted(data: &[u8]) -> Option<&[u8]> {
+    let mut i = 1;
+    while i < data.len() {
+        match data[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(&data[i + 1..]),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+fn looks_like_json_object(data: &[u8]) -> bool {
+    let rest = skip_whitespace_and_bom(&data[1..]);
+    match rest.first() {
+        Some(b'}') => true,
+        Some(b'"') => skip_quoted(rest)
+            .map(|after| skip_whitespace_and_bom(after).starts_with(b":"))
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// A .strings file is a sequence of `key = value;` without enclosing braces.
+fn looks_like_strings(data: &[u8]) -> bool {
+    let data = skip_comments(data);
+    let after_key = match data.first() {
+        // A file holding only comments is an empty strings file
+        None => return true,
+        Some(b'"') => match skip_quoted(data) {
+            Some(rest) => rest,
+            None => return false,
+        },
+        Some(c) if c.is_ascii_alphanumeric() || *c == b'_' => {
+            let end = data
+                .iter()
+                .position(|&c| !(c.is_ascii_alphanumeric() || b"_$/:.-".contains(&c)))
+                .unwrap_or(data.len());
+            &data[end..]
+        }
+        _ => return false,
+    };
+    matches!(skip_comments(after_key).first(), Some(b'=') | Some(b';'))
+}
+
 fn skip_whitespace_and_bom(data: &[u8]) -> &[u8] {
     let mut start = 0;
     if data.len() >= 3 && data[0] == 0xEF && data[1] == 0xBB && data[2] == 0xBF {
@@ -142,20 +279,28 @@ pub fn deserialize(data: &[u8]) -> Result<(Value, PlistFormat), PlistError> {
 /// Deserialize plist data with a known format.
 pub fn deserialize_with_format(data: &[u8], format: PlistFormat) -> Result<Value, PlistError> {
     match format {
-        PlistFormat::Xml | PlistFormat::Binary => {
-            let value = Value::from_reader(Cursor::new(data))?;
-            Ok(value)
-        }
+        PlistFormat::Xml | PlistFormat::Binary => Value::from_reader(Cursor::new(data))
+            .map_err(|e| diagnostic::from_plist_error(e, data, format == PlistFormat::Binary)),
         PlistFormat::Ascii => {
-            let text =
-                std::str::from_utf8(data).map_err(|e| PlistError::AsciiParse(e.to_string()))?;
-            ascii::parse(text).map_err(|e| PlistError::AsciiParse(e.to_string()))
+            let (text, _) = strings::decode(data)?;
+            Ok(ascii::parse(&text)?)
         }
+        PlistFormat::Strings => strings::parse(data),
         PlistFormat::Json => {
-            let json_value: serde_json::Value = serde_json::from_slice(data)?;
+            let json_value: serde_json::Value = serde_json::from_slice(data)
+                .map_err(|e| diagnostic::from_json_error(e, data))?;
             Ok(json_to_plist(json_value))
         }
-        PlistFormat::Raw | PlistFormat::Swift | PlistFormat::Objc => Err(PlistError::UnknownFormat),
+        PlistFormat::TaggedJson => {
+            let json_value: serde_json::Value = serde_json::from_slice(data)
+                .map_err(|e| diagnostic::from_json_error(e, data))?;
+            json::from_tagged(json_value)
+        }
+        PlistFormat::Raw
+        | PlistFormat::Swift
+        | PlistFormat::Objc
+        | PlistFormat::SwiftCodable
+        | PlistFormat::ObjcInterface => Err(PlistError::UnknownFormat),
     }
 }
 
@@ -173,14 +318,37 @@ pub fn serialize(value: &Value, format: PlistFormat) -> Result<Vec<u8>, PlistErr
             Ok(buf)
         }
         PlistFormat::Ascii => Ok(ascii::write(value).into_bytes()),
+        PlistFormat::Strings => strings::serialize(value, strings::TextEncoding::Utf8),
         PlistFormat::Json => {
             let json_value = plist_to_json(value);
             let buf = serde_json::to_vec_pretty(&json_value)?;
             Ok(buf)
         }
+        PlistFormat::TaggedJson => {
+            let buf = serde_json::to_vec_pretty(&json::to_tagged(value))?;
+            Ok(buf)
+        }
         PlistFormat::Raw => Ok(serialize_raw(value)),
         PlistFormat::Swift => Ok(serialize_swift(value, true)),
         PlistFormat::Objc => Ok(serialize_objc(value, true)),
+        PlistFormat::SwiftCodable | PlistFormat::ObjcInterface => {
+            serialize_schema(value, format, "Root")
+        }
+    }
+}
+
+/// Serialize the inferred schema of a value as Swift or ObjC declarations
+/// with the given root type name.
+pub fn serialize_schema(
+    value: &Value,
+    format: PlistFormat,
+    root_name: &str,
+) -> Result<Vec<u8>, PlistError> {
+    let inferred = schema::infer(value);
+    match format {
+        PlistFormat::SwiftCodable => Ok(schema::to_swift(&inferred, root_name).into_bytes()),
+        PlistFormat::ObjcInterface => Ok(schema::to_objc(&inferred, root_name)?.into_bytes()),
+        _ => serialize(value, format),
     }
 }
 
@@ -524,31 +692,145 @@ fn base64_encode(data: &[u8]) -> String {
 
 // --- Key path navigation for PlistBuddy ---
 
-/// Parse a PlistBuddy-style key path (`:key:subkey:0:` syntax).
+/// One component of a key path: a dictionary key or array index, or an
+/// unescaped `*` matching every key or index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyPathSegment {
+    Key(String),
+    Wildcard,
+}
+
+/// Split a key path on `separator`. A backslash makes the next character
+/// literal, so `\:` (PlistBuddy) or `\.` (plutil) is part of a key and `\*`
+/// is a literal star rather than a wildcard. Empty segments are kept.
+pub fn parse_key_path_segments(path: &str, separator: char) -> Vec<KeyPathSegment> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut escaped = false;
+    let mut literal = false;
+    let finish = |current: &mut String, literal: &mut bool, segments: &mut Vec<KeyPathSegment>| {
+        let text = std::mem::take(current);
+        segments.push(if text == "*" && !*literal {
+            KeyPathSegment::Wildcard
+        } else {
+            KeyPathSegment::Key(text)
+        });
+        *literal = false;
+    };
+
+    for c in path.chars() {
+        if escaped {
+            current.push(c);
+            literal = true;
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == separator {
+            finish(&mut current, &mut literal, &mut segments);
+        } else {
+            current.push(c);
+        }
+    }
+    if escaped {
+        current.push('\\');
+    }
+    finish(&mut current, &mut literal, &mut segments);
+    segments
+}
+
+/// Escape a key for use in a key path with the given separator.
+pub fn escape_key(key: &str, separator: char) -> String {
+    let mut escaped = String::with_capacity(key.len());
+    for c in key.chars() {
+        if c == '\\' || c == separator || (c == '*' && key == "*") {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn segment_keys(segments: Vec<KeyPathSegment>) -> Vec<String> {
+    segments
+        .into_iter()
+        .map(|segment| match segment {
+            KeyPathSegment::Key(key) => key,
+            KeyPathSegment::Wildcard => "*".to_string(),
+        })
+        .collect()
+}
+
+/// Parse a PlistBuddy-style key path (`:key:subkey:0:` syntax), where
+/// `\:` escapes a colon inside a key.
 pub fn parse_key_path(path: &str) -> Vec<String> {
-    let mut keys = Vec::new();
-    let mut prev = 0;
+    let mut keys = segment_keys(parse_key_path_segments(path, ':'));
+    // A leading or trailing separator doesn't introduce an empty key.
+    if path.starts_with(':') && !keys.is_empty() {
+        keys.remove(0);
+    }
+    if keys.last().is_some_and(|k| k.is_empty()) {
+        keys.pop();
+    }
+    keys
+}
 
-    loop {
-        let pos = path[prev..].find(':');
-        match pos {
-            Some(p) => {
-                let end = prev + p;
-                if !(prev == 0 && end == 0) {
-                    keys.push(path[prev..end].to_string());
-                }
-                prev = end + 1;
+/// Parse a plutil-style key path (`key.subkey.0`), where `\.` escapes a
+/// dot inside a key such as a bundle identifier.
+pub fn parse_dot_key_path(path: &str) -> Result<Vec<String>, PlistError> {
+    if path.is_empty() {
+        return Ok(Vec::new());
+    }
+    if path.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1 {
+        return Err(PlistError::InvalidEscape(path.to_string()));
+    }
+    let keys = segment_keys(parse_key_path_segments(path, '.'));
+    if keys.iter().any(String::is_empty) {
+        return Err(PlistError::InvalidKeyPath(path.to_string()));
+    }
+    Ok(keys)
+}
+
+/// Find every value matching a key path whose segments may be wildcards,
+/// with the concrete path of each match.
+pub fn query<'a>(value: &'a Value, segments: &[KeyPathSegment]) -> Vec<(Vec<String>, &'a Value)> {
+    let mut matches = Vec::new();
+    query_into(value, segments, &mut Vec::new(), &mut matches);
+    matches
+}
+
+fn query_into<'a>(
+    value: &'a Value,
+    segments: &[KeyPathSegment],
+    path: &mut Vec<String>,
+    matches: &mut Vec<(Vec<String>, &'a Value)>,
+) {
+    let Some((segment, rest)) = segments.split_first() else {
+        matches.push((path.clone(), value));
+        return;
+    };
+    let mut visit = |key: String, child: &'a Value| {
+        path.push(key);
+        query_into(child, rest, path, matches);
+        path.pop();
+    };
+    match (segment, value) {
+        (KeyPathSegment::Wildcard, Value::Dictionary(dict)) => {
+            for (key, child) in dict.iter() {
+                visit(key.clone(), child);
             }
-            None => {
-                if prev < path.len() {
-                    keys.push(path[prev..].to_string());
-                }
-                break;
+        }
+        (KeyPathSegment::Wildcard, Value::Array(items)) => {
+            for (i, child) in items.iter().enumerate() {
+                visit(i.to_string(), child);
             }
         }
+        (KeyPathSegment::Key(key), _) => {
+            if let Some(child) = get_at_key_path(value, std::slice::from_ref(key)) {
+                visit(key.clone(), child);
+            }
+        }
+        _ => {}
     }
-
-    keys
 }
 
 /// Navigate to a value at a key path, returning a reference.
@@ -675,11 +957,56 @@ mod tests {
         assert_eq!(identify_format(data), Some(PlistFormat::Json));
     }
 
+    #[test]
+    fn test_identify_ascii_and_strings() {
+        assert_eq!(identify_format(b"{ key = value; }"), Some(PlistFormat::Ascii));
+        assert_eq!(identify_format(b"{ \"key\" = 1; }"), Some(PlistFormat::Ascii));
+        assert_eq!(identify_format(b"(a, b)"), Some(PlistFormat::Ascii));
+        assert_eq!(
+            identify_format(b"/* c */\n\"key\" = \"value\";"),
+            Some(PlistFormat::Strings)
+        );
+        assert_eq!(
+            identify_format(&strings::encode("a = b;", strings::TextEncoding::Utf16Be)),
+            Some(PlistFormat::Strings)
+        );
+    }
+
     #[test]
     fn test_key_path_parsing() {
         assert_eq!(parse_key_path(":key:subkey"), vec!["key", "subkey"]);
         assert_eq!(parse_key_path("key"), vec!["key"]);
         assert_eq!(parse_key_path(":key:0:name"), vec!["key", "0", "name"]);
+        assert_eq!(parse_key_path(":a\\:b:c:"), vec!["a:b", "c"]);
+        assert_eq!(parse_key_path(":a\\\\:"), vec!["a\\"]);
+        assert_eq!(parse_key_path(":a:\\:"), vec!["a", ":"]);
+        assert!(matches!(parse_dot_key_path("a.b\\"), Err(PlistError::InvalidEscape(_))));
+        assert_eq!(parse_dot_key_path("a.b\\\\").unwrap(), vec!["a", "b\\"]);
+        assert_eq!(
+            parse_dot_key_path("NSExceptionDomains.example\\.com.0").unwrap(),
+            vec!["NSExceptionDomains", "example.com", "0"]
+        );
+        assert!(parse_dot_key_path("a..b").is_err());
+        assert_eq!(escape_key("example.com", '.'), "example\\.com");
+    }
+
+    #[test]
+    fn test_wildcard_query() {
+        let value = ascii::parse(
+            "{ CFBundleURLTypes = ({ CFBundleURLSchemes = (a); }, { Other = 1; }, \
+             { CFBundleURLSchemes = (b, c); }); \"*\" = star; }",
+        )
+        .unwrap();
+        let segments = parse_key_path_segments("CFBundleURLTypes.*.CFBundleURLSchemes", '.');
+        let found: Vec<String> = query(&value, &segments)
+            .into_iter()
+            .map(|(path, _)| path.join("."))
+            .collect();
+        assert_eq!(found, vec!["CFBundleURLTypes.0.CFBundleURLSchemes", "CFBundleURLTypes.2.CFBundleURLSchemes"]);
+
+        let literal = query(&value, &parse_key_path_segments("\\*", '.'));
+        assert_eq!(literal.len(), 1);
+        assert_eq!(literal[0].1, &Value::String("star".to_string()));
     }
 
     #[test]
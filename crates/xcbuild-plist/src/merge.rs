@@ -0,0 +1,169 @@
+//! Merging one plist into another.
+//!
+//! Dictionaries are merged key by key. When both sides have a value for a
+//! key and [`MergeOptions::deep`] is set, nested dictionaries are merged
+//! recursively and arrays follow [`MergeOptions::arrays`]; anything else is
+//! a conflict resolved by [`MergeOptions::conflict`]. Root arrays are always
+//! combined according to [`MergeOptions::arrays`].
+
+use crate::{escape_key, PlistError};
+use plist::Value;
+
+/// What to do when both plists have a different value for a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Conflict {
+    /// The merged-in value replaces the existing one.
+    #[default]
+    Overwrite,
+    /// The existing value is kept.
+    KeepExisting,
+    /// Merging fails with [`PlistError::MergeConflict`].
+    Error,
+}
+
+/// How two arrays at the same key are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArrayMerge {
+    /// Arrays are treated like any other conflicting value.
+    #[default]
+    Replace,
+    /// Merged-in elements are appended.
+    Append,
+    /// Merged-in elements are appended unless already present.
+    Union,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MergeOptions {
+    pub conflict: Conflict,
+    /// Merge nested dictionaries instead of treating them as conflicts.
+    pub deep: bool,
+    pub arrays: ArrayMerge,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            conflict: Conflict::Overwrite,
+            deep: true,
+            arrays: ArrayMerge::Replace,
+        }
+    }
+}
+
+/// Merge `source` into `target`. Returns the key paths (PlistBuddy
+/// notation) of the conflicts that were resolved, including keys whose
+/// values were equal, so callers can report overwritten or skipped keys.
+pub fn merge(target: &mut Value, source: Value, options: &MergeOptions) -> Result<Vec<String>, PlistError> {
+    let mut conflicts = Vec::new();
+    merge_at(&mut Vec::new(), target, source, options, true, &mut conflicts)?;
+    Ok(conflicts)
+}
+
+fn merge_at(
+    path: &mut Vec<String>,
+    target: &mut Value,
+    source: Value,
+    options: &MergeOptions,
+    root: bool,
+    conflicts: &mut Vec<String>,
+) -> Result<(), PlistError> {
+    match (&mut *target, source) {
+        (Value::Dictionary(existing), Value::Dictionary(incoming)) if root || options.deep => {
+            for (key, value) in incoming {
+                path.push(key.clone());
+                match existing.get_mut(&key) {
+                    Some(current) => merge_at(path, current, value, options, false, conflicts)?,
+                    None => {
+                        existing.insert(key, value);
+                    }
+                }
+                path.pop();
+            }
+            Ok(())
+        }
+        (Value::Array(existing), Value::Array(incoming))
+            if root || (options.deep && options.arrays != ArrayMerge::Replace) =>
+        {
+            for value in incoming {
+                if options.arrays != ArrayMerge::Union || !existing.contains(&value) {
+                    existing.push(value);
+                }
+            }
+            Ok(())
+        }
+        (_, source) => {
+            let key_path: String = path.iter().map(|k| format!(":{}", escape_key(k, ':'))).collect();
+            match options.conflict {
+                _ if *target == source => {}
+                Conflict::Overwrite => *target = source,
+                Conflict::KeepExisting => {}
+                Conflict::Error => return Err(PlistError::MergeConflict(key_path)),
+            }
+            conflicts.push(key_path);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Value {
+        crate::ascii::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_deep_merge_policies() {
+        let base = parse("{ a = { x = 1; y = 2; }; list = (p, q); name = old; }");
+        let extra = parse("{ a = { y = 3; z = 4; }; list = (q, r); name = new; }");
+
+        let mut merged = base.clone();
+        let options = MergeOptions {
+            arrays: ArrayMerge::Union,
+            ..MergeOptions::default()
+        };
+        let conflicts = merge(&mut merged, extra.clone(), &options).unwrap();
+        assert_eq!(merged, parse("{ a = { x = 1; y = 3; z = 4; }; list = (p, q, r); name = new; }"));
+        assert_eq!(conflicts, vec![":a:y", ":name"]);
+
+        let mut kept = base.clone();
+        let options = MergeOptions {
+            conflict: Conflict::KeepExisting,
+            deep: false,
+            arrays: ArrayMerge::Append,
+        };
+        let conflicts = merge(&mut kept, extra.clone(), &options).unwrap();
+        assert_eq!(kept, base);
+        assert_eq!(conflicts, vec![":a", ":list", ":name"]);
+
+        let mut root = parse("(p, q)");
+        merge(&mut root, parse("(q, r)"), &options).unwrap();
+        assert_eq!(root, parse("(p, q, q, r)"));
+
+        let mut same = base.clone();
+        let conflicts = merge(&mut same, base.clone(), &MergeOptions::default()).unwrap();
+        assert_eq!(same, base);
+        assert_eq!(conflicts, vec![":a:x", ":a:y", ":list", ":name"]);
+
+        let mut strict = base.clone();
+        let options = MergeOptions {
+            conflict: Conflict::Error,
+            ..MergeOptions::default()
+        };
+        match merge(&mut strict, extra, &options) {
+            Err(PlistError::MergeConflict(path)) => assert_eq!(path, ":a:y"),
+            other => panic!("expected conflict, got {other:?}"),
+        }
+        let mut unchanged = base.clone();
+        assert!(merge(&mut unchanged, base, &options).is_ok());
+    }
+
+    #[test]
+    fn test_conflict_paths_are_escaped() {
+        let mut target = parse("{ \"a:b\" = { \"c\\\\d\" = 1; }; }");
+        let conflicts = merge(&mut target, parse("{ \"a:b\" = { \"c\\\\d\" = 2; }; }"), &MergeOptions::default()).unwrap();
+        assert_eq!(conflicts, vec![":a\\:b:c\\\\d"]);
+    }
+}
@@ -0,0 +1,489 @@
+//! Schema inference and typed Swift/ObjC declarations.
+//!
+//! [`infer`] walks a plist and describes its shape. Array elements are
+//! merged into one element schema: dictionary keys missing from some
+//! elements become optional, integers and reals widen to reals, and
+//! otherwise incompatible types become [`Schema::Mixed`]. [`to_swift`]
+//! and [`to_objc`] turn the schema into a Swift `Codable` struct or an
+//! Objective-C interface.
+
+use crate::PlistError;
+use plist::Value;
+use std::collections::HashSet;
+
+/// The inferred shape of a plist value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    Boolean,
+    Integer,
+    Real,
+    String,
+    Data,
+    Date,
+    Uid,
+    Array(Box<Schema>),
+    Dictionary(Vec<Field>),
+    /// The element type of an empty array.
+    Unknown,
+    /// Values of incompatible types.
+    Mixed,
+}
+
+/// A dictionary key and the schema of its value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub key: String,
+    pub schema: Schema,
+    /// Whether the key is missing from some of the merged dictionaries.
+    pub optional: bool,
+}
+
+/// Infer the schema of a plist value.
+pub fn infer(value: &Value) -> Schema {
+    match value {
+        Value::Boolean(_) => Schema::Boolean,
+        Value::Integer(_) => Schema::Integer,
+        Value::Real(_) => Schema::Real,
+        Value::String(_) => Schema::String,
+        Value::Data(_) => Schema::Data,
+        Value::Date(_) => Schema::Date,
+        Value::Uid(_) => Schema::Uid,
+        Value::Array(items) => Schema::Array(Box::new(
+            items.iter().map(infer).fold(Schema::Unknown, merge),
+        )),
+        Value::Dictionary(dict) => Schema::Dictionary(
+            dict.iter()
+                .map(|(key, value)| Field {
+                    key: key.clone(),
+                    schema: infer(value),
+                    optional: false,
+                })
+                .collect(),
+        ),
+        _ => Schema::Mixed,
+    }
+}
+
+/// Merge two schemas into one that describes values of either.
+pub fn merge(a: Schema, b: Schema) -> Schema {
+    match (a, b) {
+        (Schema::Unknown, other) | (other, Schema::Unknown) => other,
+        (Schema::Integer, Schema::Real) | (Schema::Real, Schema::Integer) => Schema::Real,
+        (Schema::Array(a), Schema::Array(b)) => Schema::Array(Box::new(merge(*a, *b))),
+        (Schema::Dictionary(a), Schema::Dictionary(b)) => {
+            let mut fields = a;
+            for field in &mut fields {
+                if !b.iter().any(|f| f.key == field.key) {
+                    field.optional = true;
+                }
+            }
+            for other in b {
+                match fields.iter_mut().find(|f| f.key == other.key) {
+                    Some(field) => {
+                        let schema = std::mem::replace(&mut field.schema, Schema::Unknown);
+                        field.schema = merge(schema, other.schema);
+                        field.optional |= other.optional;
+                    }
+                    None => fields.push(Field {
+                        optional: true,
+                        ..other
+                    }),
+                }
+            }
+            Schema::Dictionary(fields)
+        }
+        (a, b) if a == b => a,
+        _ => Schema::Mixed,
+    }
+}
+
+/// A struct or interface to emit, with its fields' resolved type names.
+struct Declaration {
+    name: String,
+    properties: Vec<Property>,
+}
+
+struct Property {
+    key: String,
+    name: String,
+    schema: Schema,
+    /// Type name of the nested declaration for dictionaries (or arrays of
+    /// dictionaries).
+    nested: Option<String>,
+    optional: bool,
+}
+
+/// Collects declarations depth first, so nested types precede their users.
+struct Collector {
+    declarations: Vec<Declaration>,
+    names: HashSet<String>,
+    /// Renames property names the target language reserves.
+    escape: fn(String) -> String,
+}
+
+impl Collector {
+    fn unique(&mut self, base: &str) -> String {
+        let mut name = base.to_string();
+        let mut n = 2;
+        while !self.names.insert(name.clone()) {
+            name = format!("{base}{n}");
+            n += 1;
+        }
+        name
+    }
+
+    /// Declare the dictionary at the bottom of `schema`, if any, and return
+    /// its type name.
+    fn declare(&mut self, type_name: &str, schema: &Schema) -> Option<String> {
+        match schema {
+            Schema::Dictionary(fields) => {
+                let name = self.unique(type_name);
+                let mut used = HashSet::new();
+                let properties = fields
+                    .iter()
+                    .map(|field| {
+                        let nested = self.declare(&element_type_name(&field.key, &field.schema), &field.schema);
+                        let mut property = (self.escape)(property_name(&field.key));
+                        let base = property.clone();
+                        let mut n = 2;
+                        while !used.insert(property.clone()) {
+                            property = format!("{base}{n}");
+                            n += 1;
+                        }
+                        Property {
+                            key: field.key.clone(),
+                            name: property,
+                            schema: field.schema.clone(),
+                            nested,
+                            optional: field.optional,
+                        }
+                    })
+                    .collect();
+                self.declarations.push(Declaration { name: name.clone(), properties });
+                Some(name)
+            }
+            Schema::Array(element) => self.declare(type_name, element),
+            _ => None,
+        }
+    }
+}
+
+fn collect(
+    schema: &Schema,
+    root_name: &str,
+    escape: fn(String) -> String,
+) -> (Vec<Declaration>, Option<String>) {
+    let mut collector = Collector {
+        declarations: Vec::new(),
+        names: HashSet::new(),
+        escape,
+    };
+    let root = match schema {
+        Schema::Array(_) => collector.declare(&format!("{root_name}Element"), schema),
+        _ => collector.declare(root_name, schema),
+    };
+    (collector.declarations, root)
+}
+
+/// Split a key into words at case changes and non-alphanumerics,
+/// keeping acronyms (`CFBundleURLTypes` is `CF Bundle URL Types`).
+fn words(key: &str) -> Vec<String> {
+    let chars: Vec<char> = key.chars().collect();
+    let mut words = Vec::new();
+    let mut current = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_ascii_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        let prev = i.checked_sub(1).map(|j| chars[j]);
+        let next = chars.get(i + 1);
+        let boundary = c.is_ascii_uppercase()
+            && prev.is_some_and(|p| {
+                p.is_ascii_lowercase()
+                    || p.is_ascii_digit()
+                    || (p.is_ascii_uppercase() && next.is_some_and(|n| n.is_ascii_lowercase()))
+            });
+        if boundary && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+/// `CFBundleURLTypes` becomes `CFBundleURLTypes`; `build-settings`
+/// becomes `BuildSettings`.
+fn type_name(key: &str) -> String {
+    let name: String = words(key).iter().map(|w| capitalize(w)).collect();
+    match name.chars().next() {
+        None => "Value".to_string(),
+        Some(c) if c.is_ascii_digit() => format!("_{name}"),
+        Some(_) => name,
+    }
+}
+
+/// Type name for the elements of an array field: a trailing plural `s`
+/// is dropped (`Items` gives `Item`), otherwise `Element` is appended.
+fn element_type_name(key: &str, schema: &Schema) -> String {
+    let name = type_name(key);
+    if !matches!(schema, Schema::Array(_)) {
+        return name;
+    }
+    match name.strip_suffix('s') {
+        Some(singular) if singular.len() > 1 && !singular.ends_with('s') => singular.to_string(),
+        _ => format!("{name}Element"),
+    }
+}
+
+/// `CFBundleURLTypes` becomes `cfBundleURLTypes`.
+fn property_name(key: &str) -> String {
+    let words = words(key);
+    let mut name = String::new();
+    for (i, word) in words.iter().enumerate() {
+        if i == 0 {
+            name.push_str(&word.to_ascii_lowercase());
+        } else {
+            name.push_str(&capitalize(word));
+        }
+    }
+    match name.chars().next() {
+        None => "value".to_string(),
+        Some(c) if c.is_ascii_digit() => format!("_{name}"),
+        Some(_) => name,
+    }
+}
+
+const SWIFT_KEYWORDS: &[&str] = &[
+    "associatedtype", "class", "default", "deinit", "enum", "extension", "func", "import",
+    "init", "inout", "internal", "let", "operator", "private", "protocol", "public", "repeat",
+    "return", "self", "static", "struct", "subscript", "super", "switch", "true", "false",
+    "nil", "try", "typealias", "var", "where", "while", "do", "else", "for", "if", "in", "is",
+    "as", "break", "case", "catch", "continue", "defer", "fallthrough", "guard", "throw",
+    "throws", "Type", "fileprivate", "precedencegroup", "rethrows", "Any", "Self",
+];
+
+/// C and Objective-C keywords, and NSObject methods a property would
+/// override.
+const OBJC_RESERVED: &[&str] = &[
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long", "register",
+    "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "unsigned", "void", "volatile", "while", "id", "self", "super", "nil", "Nil",
+    "YES", "NO", "BOOL", "SEL", "IMP", "Class", "in", "out", "inout", "bycopy", "byref",
+    "oneway", "class", "description", "debugDescription", "hash", "superclass", "zone",
+    "copy", "mutableCopy", "init", "new", "alloc", "dealloc", "retain", "release",
+    "autorelease", "retainCount", "isProxy",
+];
+
+/// Suffix a reserved Objective-C property name (`description` becomes
+/// `descriptionValue`).
+fn objc_property_name(name: String) -> String {
+    if OBJC_RESERVED.contains(&name.as_str()) {
+        format!("{name}Value")
+    } else {
+        name
+    }
+}
+
+fn swift_type(schema: &Schema, nested: Option<&str>) -> Option<String> {
+    Some(match schema {
+        Schema::Boolean => "Bool".to_string(),
+        Schema::Integer => "Int".to_string(),
+        Schema::Real => "Double".to_string(),
+        Schema::String | Schema::Unknown => "String".to_string(),
+        Schema::Data => "Data".to_string(),
+        Schema::Date => "Date".to_string(),
+        Schema::Array(element) => format!("[{}]", swift_type(element, nested)?),
+        Schema::Dictionary(_) => nested?.to_string(),
+        // PropertyListDecoder has no type a keyed-archive UID decodes to.
+        Schema::Uid | Schema::Mixed => return None,
+    })
+}
+
+/// Generate Swift `Codable` structs describing `schema`, with the root
+/// struct named `root_name`. An array root becomes a typealias.
+pub fn to_swift(schema: &Schema, root_name: &str) -> String {
+    let (declarations, root) = collect(schema, root_name, std::convert::identity);
+    let mut out = String::from("import Foundation\n");
+
+    for declaration in &declarations {
+        out.push('\n');
+        out.push_str(&format!("struct {}: Codable {{\n", declaration.name));
+        let mut renamed = false;
+        for property in &declaration.properties {
+            let Some(ty) = swift_type(&property.schema, property.nested.as_deref()) else {
+                let reason = if holds_uid(&property.schema) {
+                    "holds keyed-archive UIDs, which Codable cannot decode"
+                } else {
+                    "has values of mixed types"
+                };
+                out.push_str(&format!("    // {:?} {reason}\n", property.key));
+                continue;
+            };
+            let name = if SWIFT_KEYWORDS.contains(&property.name.as_str()) {
+                format!("`{}`", property.name)
+            } else {
+                property.name.clone()
+            };
+            let optional = if property.optional { "?" } else { "" };
+            out.push_str(&format!("    let {name}: {ty}{optional}\n"));
+            renamed |= property.name != property.key;
+        }
+        if renamed {
+            out.push_str("\n    enum CodingKeys: String, CodingKey {\n");
+            for property in &declaration.properties {
+                if swift_type(&property.schema, property.nested.as_deref()).is_none() {
+                    continue;
+                }
+                let name = if SWIFT_KEYWORDS.contains(&property.name.as_str()) {
+                    format!("`{}`", property.name)
+                } else {
+                    property.name.clone()
+                };
+                if property.name == property.key {
+                    out.push_str(&format!("        case {name}\n"));
+                } else {
+                    out.push_str(&format!("        case {name} = {:?}\n", property.key));
+                }
+            }
+            out.push_str("    }\n");
+        }
+        out.push_str("}\n");
+    }
+
+    if !matches!(schema, Schema::Dictionary(_)) {
+        let ty = swift_type(schema, root.as_deref()).unwrap_or_else(|| "Any".to_string());
+        out.push_str(&format!("\ntypealias {root_name} = {ty}\n"));
+    }
+    out
+}
+
+fn holds_uid(schema: &Schema) -> bool {
+    match schema {
+        Schema::Uid => true,
+        Schema::Array(element) => holds_uid(element),
+        _ => false,
+    }
+}
+
+fn objc_type(schema: &Schema, nested: Option<&str>, optional: bool) -> (String, &'static str) {
+    let object = |name: String| (format!("{name} *"), "copy");
+    match schema {
+        Schema::Boolean if !optional => ("BOOL ".to_string(), "assign"),
+        Schema::Integer if !optional => ("NSInteger ".to_string(), "assign"),
+        Schema::Real if !optional => ("double ".to_string(), "assign"),
+        Schema::Boolean | Schema::Integer | Schema::Real => object("NSNumber".to_string()),
+        Schema::String | Schema::Unknown => object("NSString".to_string()),
+        Schema::Data => object("NSData".to_string()),
+        Schema::Date => object("NSDate".to_string()),
+        Schema::Array(element) => {
+            let (element, _) = objc_type(element, nested, true);
+            object(format!("NSArray<{}>", element.trim_end()))
+        }
+        Schema::Dictionary(_) => match nested {
+            Some(name) => (format!("{name} *"), "strong"),
+            None => object("NSDictionary<NSString *, id>".to_string()),
+        },
+        Schema::Uid | Schema::Mixed => ("id ".to_string(), "strong"),
+    }
+}
+
+/// Generate Objective-C interfaces describing `schema`, with the root
+/// interface named `root_name`. The root must be a dictionary or an array
+/// of dictionaries.
+pub fn to_objc(schema: &Schema, root_name: &str) -> Result<String, PlistError> {
+    let (declarations, root) = collect(schema, root_name, objc_property_name);
+    if root.is_none() {
+        return Err(PlistError::UnsupportedType(
+            "ObjC interfaces require a dictionary".to_string(),
+        ));
+    }
+
+    let mut out = String::from("#import <Foundation/Foundation.h>\n\nNS_ASSUME_NONNULL_BEGIN\n");
+    for declaration in &declarations {
+        out.push('\n');
+        out.push_str(&format!("@interface {} : NSObject\n", declaration.name));
+        for property in &declaration.properties {
+            let (ty, attribute) =
+                objc_type(&property.schema, property.nested.as_deref(), property.optional);
+            let nullable = if property.optional && ty.ends_with('*') { ", nullable" } else { "" };
+            out.push_str(&format!(
+                "@property (nonatomic, {attribute}{nullable}) {ty}{}; // {:?}\n",
+                property.name, property.key
+            ));
+        }
+        out.push_str("@end\n");
+    }
+    out.push_str("\nNS_ASSUME_NONNULL_END\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_optional_fields() {
+        let (value, _) = crate::deserialize(
+            br#"{
+                "CFBundleName": "App",
+                "Items": [
+                    {"title": "a", "count": 1},
+                    {"title": "b", "count": 2.5, "enabled": true}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let swift = to_swift(&infer(&value), "Info");
+        assert!(swift.contains("struct Item: Codable {\n    let count: Double\n    let title: String\n    let enabled: Bool?\n}"));
+        assert!(swift.contains("struct Info: Codable {\n    let cfBundleName: String\n    let items: [Item]\n"));
+        assert!(swift.contains("case cfBundleName = \"CFBundleName\""));
+        assert!(swift.find("struct Item").unwrap() < swift.find("struct Info").unwrap());
+
+        let objc = to_objc(&infer(&value), "Info").unwrap();
+        assert!(objc.contains("@property (nonatomic, copy) NSArray<Item *> *items; // \"Items\""));
+        assert!(objc.contains("@property (nonatomic, copy, nullable) NSNumber *enabled;"));
+        assert!(objc.contains("@property (nonatomic, assign) double count;"));
+    }
+
+    #[test]
+    fn test_reserved_names_and_uids() {
+        let mut dict = plist::Dictionary::new();
+        dict.insert("description".to_string(), Value::String("d".to_string()));
+        dict.insert("class".to_string(), Value::String("c".to_string()));
+        dict.insert("ref".to_string(), Value::Uid(plist::Uid::new(1)));
+        let schema = infer(&Value::Dictionary(dict));
+
+        let swift = to_swift(&schema, "Info");
+        assert!(swift.contains("    let description: String\n"));
+        assert!(swift.contains("    let `class`: String\n"));
+        assert!(swift.contains("    // \"ref\" holds keyed-archive UIDs"));
+        assert!(!swift.contains("UInt64"));
+
+        let objc = to_objc(&schema, "Info").unwrap();
+        assert!(objc.contains("NSString *descriptionValue; // \"description\""));
+        assert!(objc.contains("NSString *classValue; // \"class\""));
+        assert!(objc.contains("(nonatomic, strong) id ref; // \"ref\""));
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(property_name("CFBundleURLTypes"), "cfBundleURLTypes");
+        assert_eq!(type_name("build-settings"), "BuildSettings");
+        assert_eq!(element_type_name("UIApplicationShortcutItems", &Schema::Array(Box::new(Schema::Unknown))), "UIApplicationShortcutItem");
+    }
+}
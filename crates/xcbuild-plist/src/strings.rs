@@ -0,0 +1,221 @@
+//! .strings file support: text encoding detection, braceless dictionaries
+//! and a writer that preserves entry comments.
+
+use crate::{ascii, PlistError};
+use plist::Value;
+
+/// Text encodings used by .strings and ASCII plist files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl TextEncoding {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(TextEncoding::Utf8),
+            "utf-16" | "utf16" | "utf-16le" | "utf16le" => Some(TextEncoding::Utf16Le),
+            "utf-16be" | "utf16be" => Some(TextEncoding::Utf16Be),
+            _ => None,
+        }
+    }
+}
+
+/// One `"key" = value;` entry of a .strings file and the comment before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub comment: Option<String>,
+    pub key: String,
+    pub value: Value,
+}
+
+/// Detect the encoding of text data from its byte order mark, falling back
+/// to looking for NUL bytes that betray BOM-less UTF-16.
+pub fn detect_encoding(data: &[u8]) -> TextEncoding {
+    if data.starts_with(&[0xFF, 0xFE]) {
+        return TextEncoding::Utf16Le;
+    }
+    if data.starts_with(&[0xFE, 0xFF]) {
+        return TextEncoding::Utf16Be;
+    }
+    if data.len() >= 2 && !data.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        if data[0] != 0 && data[1] == 0 {
+            return TextEncoding::Utf16Le;
+        }
+        if data[0] == 0 && data[1] != 0 {
+            return TextEncoding::Utf16Be;
+        }
+    }
+    TextEncoding::Utf8
+}
+
+/// Decode text data to a string, stripping any byte order mark.
+pub fn decode(data: &[u8]) -> Result<(String, TextEncoding), PlistError> {
+    let encoding = detect_encoding(data);
+    let text = decode_with_encoding(data, encoding)?;
+    Ok((text, encoding))
+}
+
+/// Decode text data in a known encoding, stripping any byte order mark.
+pub fn decode_with_encoding(data: &[u8], encoding: TextEncoding) -> Result<String, PlistError> {
+    match encoding {
+        TextEncoding::Utf8 => {
+            let data = data.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(data);
+            String::from_utf8(data.to_vec())
+                .map_err(|e| PlistError::AsciiParse(format!("invalid UTF-8: {e}")))
+        }
+        TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+            if !data.len().is_multiple_of(2) {
+                return Err(PlistError::AsciiParse("truncated UTF-16 data".to_string()));
+            }
+            let units: Vec<u16> = data
+                .chunks_exact(2)
+                .map(|pair| match encoding {
+                    TextEncoding::Utf16Be => u16::from_be_bytes([pair[0], pair[1]]),
+                    _ => u16::from_le_bytes([pair[0], pair[1]]),
+                })
+                .collect();
+            let units = units.strip_prefix(&[0xFEFF]).unwrap_or(&units);
+            String::from_utf16(units)
+                .map_err(|e| PlistError::AsciiParse(format!("invalid UTF-16: {e}")))
+        }
+    }
+}
+
+/// Encode text, writing a byte order mark for UTF-16 as Apple tools do.
+pub fn encode(text: &str, encoding: TextEncoding) -> Vec<u8> {
+    match encoding {
+        TextEncoding::Utf8 => text.as_bytes().to_vec(),
+        TextEncoding::Utf16Le => std::iter::once(0xFEFF)
+            .chain(text.encode_utf16())
+            .flat_map(u16::to_le_bytes)
+            .collect(),
+        TextEncoding::Utf16Be => std::iter::once(0xFEFF)
+            .chain(text.encode_utf16())
+            .flat_map(u16::to_be_bytes)
+            .collect(),
+    }
+}
+
+/// Parse .strings data in any supported encoding into a dictionary.
+pub fn parse(data: &[u8]) -> Result<Value, PlistError> {
+    let (text, _) = decode(data)?;
+    Ok(ascii::parse_strings(&text)?)
+}
+
+/// Parse .strings data into ordered entries, keeping their comments.
+pub fn parse_entries(data: &[u8]) -> Result<Vec<Entry>, PlistError> {
+    let (text, _) = decode(data)?;
+    let entries = ascii::parse_commented_entries(&text)?;
+    Ok(entries
+        .into_iter()
+        .map(|(comment, key, value)| Entry { comment, key, value })
+        .collect())
+}
+
+/// Write entries as a braceless .strings file.
+pub fn write_entries(entries: &[Entry]) -> String {
+    let mut output = String::new();
+    for (i, entry) in entries.iter().enumerate() {
+        if i > 0 {
+            output.push('\n');
+        }
+        match &entry.comment {
+            // A block comment can't contain its own terminator, so write
+            // those comments as line comments instead.
+            Some(comment) if comment.contains("*/") => {
+                for line in comment.lines() {
+                    output.push_str("// ");
+                    output.push_str(line);
+                    output.push('\n');
+                }
+            }
+            Some(comment) => {
+                output.push_str("/* ");
+                output.push_str(comment);
+                output.push_str(" */\n");
+            }
+            None => {}
+        }
+        ascii::write_quoted_string(&mut output, &entry.key);
+        output.push_str(" = ");
+        match &entry.value {
+            Value::String(s) => ascii::write_quoted_string(&mut output, s),
+            other => ascii::write_value(&mut output, other, 0, ascii::Dialect::OpenStep),
+        }
+        output.push_str(";\n");
+    }
+    output
+}
+
+/// Write a dictionary as a braceless .strings file.
+pub fn write(value: &Value) -> Result<String, PlistError> {
+    match value {
+        Value::Dictionary(dict) => {
+            let entries: Vec<Entry> = dict
+                .iter()
+                .map(|(key, value)| Entry {
+                    comment: None,
+                    key: key.clone(),
+                    value: value.clone(),
+                })
+                .collect();
+            Ok(write_entries(&entries))
+        }
+        _ => Err(PlistError::UnsupportedType(
+            "strings files require a dictionary".to_string(),
+        )),
+    }
+}
+
+/// Serialize a dictionary as a .strings file in the given encoding.
+pub fn serialize(value: &Value, encoding: TextEncoding) -> Result<Vec<u8>, PlistError> {
+    Ok(encode(&write(value)?, encoding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_braceless_utf16() {
+        let text = "/* Greeting */\n\"hello\" = \"Grüß dich\";\n\"bye\" = \"\\U0041\";\n";
+        let data = encode(text, TextEncoding::Utf16Le);
+        assert_eq!(detect_encoding(&data), TextEncoding::Utf16Le);
+
+        let value = parse(&data).unwrap();
+        let dict = value.as_dictionary().unwrap();
+        assert_eq!(dict.get("hello"), Some(&Value::String("Grüß dich".to_string())));
+        assert_eq!(dict.get("bye"), Some(&Value::String("A".to_string())));
+    }
+
+    #[test]
+    fn test_entries_roundtrip_comments() {
+        let text = "/* First */\n\"a\" = \"1\";\n\n// Second\n\"b\" = \"😀 \\UD83D\\UDE00\";\n";
+        let entries = parse_entries(text.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].comment.as_deref(), Some("First"));
+        assert_eq!(entries[1].comment.as_deref(), Some("Second"));
+        assert_eq!(entries[1].value, Value::String("😀 😀".to_string()));
+
+        let written = write_entries(&entries);
+        assert_eq!(parse_entries(written.as_bytes()).unwrap(), entries);
+    }
+
+    #[test]
+    fn test_comment_with_block_terminator_roundtrips() {
+        let text = "// Ends */ early\n\"a\" = \"1\";\n/* Two\nlines */\n\"b\" = \"2\";\n";
+        let mut entries = parse_entries(text.as_bytes()).unwrap();
+        entries.push(Entry {
+            comment: Some("*/ first\nthen */ second".to_string()),
+            key: "c".to_string(),
+            value: Value::String("3".to_string()),
+        });
+
+        let written = write_entries(&entries);
+        assert!(written.starts_with("// Ends */ early\n\"a\" = \"1\";\n\n/* Two\nlines */\n"));
+        assert_eq!(parse_entries(written.as_bytes()).unwrap(), entries);
+    }
+}
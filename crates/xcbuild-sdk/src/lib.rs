@@ -2,6 +2,7 @@ use plist::Value;
 use std::fs;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
+use xcbuild_pbxsetting::{Level, Setting};
 
 #[derive(Error, Debug)]
 pub enum SdkError {
@@ -41,15 +42,13 @@ impl Configuration {
     pub fn load(paths: &[String]) -> Option<Configuration> {
         for path in paths {
             if let Ok(data) = fs::read(path) {
-                if let Ok((value, _)) = xcbuild_plist::deserialize(&data) {
-                    if let Value::Dictionary(dict) = value {
-                        let platforms = string_array_from_dict(&dict, "ExtraPlatformsPaths");
-                        let toolchains = string_array_from_dict(&dict, "ExtraToolchainsPaths");
-                        return Some(Configuration {
-                            extra_platforms_paths: platforms,
-                            extra_toolchains_paths: toolchains,
-                        });
-                    }
+                if let Ok((Value::Dictionary(dict), _)) = xcbuild_plist::deserialize(&data) {
+                    let platforms = string_array_from_dict(&dict, "ExtraPlatformsPaths");
+                    let toolchains = string_array_from_dict(&dict, "ExtraToolchainsPaths");
+                    return Some(Configuration {
+                        extra_platforms_paths: platforms,
+                        extra_toolchains_paths: toolchains,
+                    });
                 }
             }
         }
@@ -72,39 +71,96 @@ fn resolve_developer_root(path: &str) -> String {
         .unwrap_or_else(|_| path.to_string())
 }
 
+/// Where the developer root was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeveloperRootSource {
+    /// The `DEVELOPER_DIR` environment variable.
+    Environment,
+    /// The per-user link (see [`LinkScope::User`]).
+    UserLink(String),
+    /// One of the system-wide links.
+    SystemLink(String),
+    /// A well-known installation path.
+    Default,
+}
+
+impl std::fmt::Display for DeveloperRootSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeveloperRootSource::Environment => write!(f, "DEVELOPER_DIR environment variable"),
+            DeveloperRootSource::UserLink(link) => write!(f, "user link '{link}'"),
+            DeveloperRootSource::SystemLink(link) => write!(f, "system link '{link}'"),
+            DeveloperRootSource::Default => write!(f, "default location"),
+        }
+    }
+}
+
+/// Which xcode-select link to read or write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkScope {
+    /// `/var/db/xcode_select_link`; usually requires root to write.
+    System,
+    /// `$XCSDK_SELECT_LINK`, or `~/.xcsdk/xcode_select_link` if unset.
+    User,
+}
+
+impl LinkScope {
+    /// The scope writes default to: the user link when its location is
+    /// set explicitly through `XCSDK_SELECT_LINK`, otherwise the system link.
+    pub fn default_for_environment() -> LinkScope {
+        match std::env::var("XCSDK_SELECT_LINK") {
+            Ok(path) if !path.is_empty() => LinkScope::User,
+            _ => LinkScope::System,
+        }
+    }
+
+    /// Path of the symlink for this scope.
+    pub fn link_path(&self) -> Option<String> {
+        match self {
+            LinkScope::System => Some("/var/db/xcode_select_link".to_string()),
+            LinkScope::User => match std::env::var("XCSDK_SELECT_LINK") {
+                Ok(path) if !path.is_empty() => Some(path),
+                _ => home_dir().map(|home| format!("{home}/.xcsdk/xcode_select_link")),
+            },
+        }
+    }
+}
+
 /// Find the developer root directory.
 pub fn find_developer_root() -> Option<String> {
+    find_developer_root_with_source().map(|(path, _)| path)
+}
+
+/// Find the developer root directory and report which lookup step found it.
+pub fn find_developer_root_with_source() -> Option<(String, DeveloperRootSource)> {
     // 1. DEVELOPER_DIR environment variable
     if let Ok(path) = std::env::var("DEVELOPER_DIR") {
         if !path.is_empty() {
-            return Some(resolve_developer_root(&path));
+            return Some((resolve_developer_root(&path), DeveloperRootSource::Environment));
         }
     }
 
     // 2. User-specific link
-    if let Some(home) = home_dir() {
-        let user_link = format!("{home}/.xcsdk/xcode_select_link");
+    if let Some(user_link) = LinkScope::User.link_path() {
         if let Ok(target) = fs::read_link(&user_link) {
-            return Some(target.to_string_lossy().to_string());
-        }
-    }
-
-    // 3. Primary system link
-    let primary = "/var/db/xcode_select_link";
-    if let Ok(target) = fs::read_link(primary) {
-        if let Ok(canonical) = fs::canonicalize(&target) {
-            return Some(canonical.to_string_lossy().to_string());
+            return Some((
+                target.to_string_lossy().to_string(),
+                DeveloperRootSource::UserLink(user_link),
+            ));
         }
-        return Some(target.to_string_lossy().to_string());
     }
 
-    // 4. Secondary system link
-    let secondary = "/usr/share/xcode-select/xcode_dir_path";
-    if let Ok(target) = fs::read_link(secondary) {
-        if let Ok(canonical) = fs::canonicalize(&target) {
-            return Some(canonical.to_string_lossy().to_string());
+    // 3. Primary system link, then 4. secondary system link
+    let primary = LinkScope::System.link_path().unwrap_or_default();
+    let secondary = "/usr/share/xcode-select/xcode_dir_path".to_string();
+    for link in [primary, secondary] {
+        if let Ok(target) = fs::read_link(&link) {
+            let path = fs::canonicalize(&target).unwrap_or(target);
+            return Some((
+                path.to_string_lossy().to_string(),
+                DeveloperRootSource::SystemLink(link),
+            ));
         }
-        return Some(target.to_string_lossy().to_string());
     }
 
     // 5. Well-known fallback paths
@@ -114,22 +170,24 @@ pub fn find_developer_root() -> Option<String> {
     ];
     for path in &defaults {
         if Path::new(path).is_dir() {
-            return Some(path.to_string());
+            return Some((path.to_string(), DeveloperRootSource::Default));
         }
     }
 
     None
 }
 
-/// Write the developer root symlink.
-pub fn write_developer_root(path: Option<&str>) -> bool {
-    let link_path = "/var/db/xcode_select_link";
+/// Write the developer root symlink for the given scope. Passing `None`
+/// removes the link.
+pub fn write_developer_root(path: Option<&str>, scope: LinkScope) -> bool {
+    let link_path = match scope.link_path() {
+        Some(p) => p,
+        None => return false,
+    };
 
     // Remove existing link if present
-    if Path::new(link_path).exists() || fs::symlink_metadata(link_path).is_ok() {
-        if fs::remove_file(link_path).is_err() {
-            return false;
-        }
+    if fs::symlink_metadata(&link_path).is_ok() && fs::remove_file(&link_path).is_err() {
+        return false;
     }
 
     let path = match path {
@@ -143,10 +201,9 @@ pub fn write_developer_root(path: Option<&str>) -> bool {
         return false;
     }
 
-    // Create /var/db if needed
-    let var_db = Path::new("/var/db");
-    if !var_db.exists() {
-        if fs::create_dir_all(var_db).is_err() {
+    // Create the link's parent directory (/var/db or ~/.xcsdk) if needed
+    if let Some(parent) = Path::new(&link_path).parent() {
+        if !parent.exists() && fs::create_dir_all(parent).is_err() {
             return false;
         }
     }
@@ -154,7 +211,7 @@ pub fn write_developer_root(path: Option<&str>) -> bool {
     // Create symlink
     #[cfg(unix)]
     {
-        std::os::unix::fs::symlink(&resolved, link_path).is_ok()
+        std::os::unix::fs::symlink(&resolved, &link_path).is_ok()
     }
     #[cfg(not(unix))]
     {
@@ -170,14 +227,22 @@ pub struct Product {
     pub name: Option<String>,
     pub version: Option<String>,
     pub user_visible_version: Option<String>,
+    /// Rapid Security Response suffix, e.g. `(a)`.
+    pub version_extra: Option<String>,
     pub build_version: Option<String>,
     pub copyright: Option<String>,
 }
 
 impl Product {
     pub fn open(sdk_path: &str) -> Option<Product> {
-        let plist_path = format!("{sdk_path}/System/Library/CoreServices/SystemVersion.plist");
-        let data = fs::read(&plist_path).ok()?;
+        Product::open_file(&format!(
+            "{sdk_path}/System/Library/CoreServices/SystemVersion.plist"
+        ))
+    }
+
+    /// Read product information from a SystemVersion.plist at `plist_path`.
+    pub fn open_file(plist_path: &str) -> Option<Product> {
+        let data = fs::read(plist_path).ok()?;
         let (value, _) = xcbuild_plist::deserialize(&data).ok()?;
         let dict = match &value {
             Value::Dictionary(d) => d,
@@ -188,6 +253,7 @@ impl Product {
             name: get_string(dict, "ProductName"),
             version: get_string(dict, "ProductVersion"),
             user_visible_version: get_string(dict, "ProductUserVisibleVersion"),
+            version_extra: get_string(dict, "ProductVersionExtra"),
             build_version: get_string(dict, "ProductBuildVersion"),
             copyright: get_string(dict, "ProductCopyright"),
         })
@@ -232,6 +298,14 @@ pub struct Toolchain {
     pub identifier: Option<String>,
     pub display_name: Option<String>,
     pub version: Option<String>,
+    pub aliases: Vec<String>,
+    pub compatibility_version: Option<i64>,
+    pub report_problem_url: Option<String>,
+    /// Build settings that override those of the project when this
+    /// toolchain is selected.
+    pub override_build_settings: Level,
+    /// Environment variables to set for tools run from this toolchain.
+    pub override_environment: Vec<(String, String)>,
 }
 
 impl Toolchain {
@@ -289,6 +363,15 @@ impl Toolchain {
                 .or_else(|| get_string(dict, "CFBundleIdentifier")),
             display_name: get_string(dict, "DisplayName"),
             version: get_string(dict, "Version"),
+            aliases: string_array_from_dict(dict, "Aliases"),
+            compatibility_version: get_integer(dict, "CompatibilityVersion"),
+            report_problem_url: get_string(dict, "ReportProblemURL"),
+            override_build_settings: settings_from_dict(dict, "OverrideBuildSettings"),
+            override_environment: settings_from_dict(dict, "OverrideEnvironment")
+                .settings
+                .into_iter()
+                .map(|s| (s.name, s.value))
+                .collect(),
         })
     }
 }
@@ -303,11 +386,76 @@ pub struct Platform {
     pub description: Option<String>,
     pub version: Option<String>,
     pub family_identifier: Option<String>,
+    pub family_name: Option<String>,
     pub platform_version: Option<PlatformVersion>,
+    /// Settings applied below the SDK and project (`DefaultProperties`).
+    pub default_properties: Level,
+    /// Settings applied above the project (`OverrideProperties`).
+    pub override_properties: Level,
+    /// Extra keys merged into built products' Info.plist (`AdditionalInfo`),
+    /// kept as a plist so nested dictionaries and arrays survive.
+    pub additional_info: plist::Dictionary,
+    pub runtime_devices_path: Option<String>,
     pub targets: Vec<Target>,
 }
 
 impl Platform {
+    /// Settings describing the platform itself (`PLATFORM_NAME`,
+    /// `PLATFORM_DIR`, ...), layered between the defaults and the SDK.
+    pub fn settings(&self) -> Level {
+        let mut settings = vec![
+            Setting {
+                name: "PLATFORM_NAME".to_string(),
+                value: self.name.clone(),
+            },
+            Setting {
+                name: "PLATFORM_DIR".to_string(),
+                value: self.path.clone(),
+            },
+            Setting {
+                name: "PLATFORM_DEVELOPER_USR_DIR".to_string(),
+                value: format!("{}/Developer/usr", self.path),
+            },
+            Setting {
+                name: "PLATFORM_DEVELOPER_BIN_DIR".to_string(),
+                value: format!("{}/Developer/usr/bin", self.path),
+            },
+            Setting {
+                name: "PLATFORM_DEVELOPER_SDK_DIR".to_string(),
+                value: format!("{}/Developer/SDKs", self.path),
+            },
+        ];
+        if let Some(description) = &self.description {
+            settings.push(Setting {
+                name: "PLATFORM_DISPLAY_NAME".to_string(),
+                value: description.clone(),
+            });
+        }
+        if let Some(identifier) = &self.identifier {
+            settings.push(Setting {
+                name: "PLATFORM_IDENTIFIER".to_string(),
+                value: identifier.clone(),
+            });
+        }
+        if let Some(build) = self
+            .platform_version
+            .as_ref()
+            .and_then(|v| v.product_build_version.as_ref())
+        {
+            settings.push(Setting {
+                name: "PLATFORM_PRODUCT_BUILD_VERSION".to_string(),
+                value: build.clone(),
+            });
+        }
+        if let Some(family) = &self.family_identifier {
+            settings.push(Setting {
+                name: "PLATFORM_FAMILY_NAME".to_string(),
+                value: self.family_name.clone().unwrap_or_else(|| family.clone()),
+            });
+        }
+        Level::new(settings)
+    }
+
     pub fn executable_paths(&self) -> Vec<String> {
         vec![
             format!("{}/Developer/usr/bin", self.path),
@@ -360,7 +508,15 @@ impl Platform {
             description: get_string(dict, "Description"),
             version: get_string(dict, "Version"),
             family_identifier: get_string(dict, "FamilyIdentifier"),
+            family_name: get_string(dict, "FamilyName"),
             platform_version,
+            default_properties: settings_from_dict(dict, "DefaultProperties"),
+            override_properties: settings_from_dict(dict, "OverrideProperties"),
+            additional_info: match dict.get("AdditionalInfo") {
+                Some(Value::Dictionary(info)) => info.clone(),
+                _ => plist::Dictionary::new(),
+            },
+            runtime_devices_path: get_string(dict, "RuntimeDevicesPath"),
             targets,
         })
     }
@@ -377,9 +533,44 @@ pub struct Target {
     pub version: Option<String>,
     pub toolchain_identifiers: Vec<String>,
     pub product: Option<Product>,
+    /// SDK-provided settings from SDKSettings.plist `DefaultProperties`.
+    pub default_properties: Level,
 }
 
 impl Target {
+    /// Settings describing the SDK itself (`SDKROOT`, `SDK_NAME`, ...).
+    pub fn settings(&self) -> Level {
+        let mut settings = vec![
+            Setting {
+                name: "SDKROOT".to_string(),
+                value: self.path.clone(),
+            },
+            Setting {
+                name: "SDK_DIR".to_string(),
+                value: self.path.clone(),
+            },
+        ];
+        if let Some(name) = &self.canonical_name {
+            settings.push(Setting {
+                name: "SDK_NAME".to_string(),
+                value: name.clone(),
+            });
+        }
+        if let Some(version) = &self.version {
+            settings.push(Setting {
+                name: "SDK_VERSION".to_string(),
+                value: version.clone(),
+            });
+        }
+        if let Some(build) = self.product.as_ref().and_then(|p| p.build_version.as_ref()) {
+            settings.push(Setting {
+                name: "SDK_PRODUCT_BUILD_VERSION".to_string(),
+                value: build.clone(),
+            });
+        }
+        Level::new(settings)
+    }
+
     pub fn open(path: &str, toolchains: &[Toolchain]) -> Option<Target> {
         if path.is_empty() {
             return None;
@@ -437,6 +628,7 @@ impl Target {
             version: get_string(dict, "Version"),
             toolchain_identifiers: toolchain_ids,
             product,
+            default_properties: settings_from_dict(dict, "DefaultProperties"),
         })
     }
 }
@@ -531,16 +723,42 @@ impl Manager {
         None
     }
 
-    /// Find a toolchain by name, identifier, or path.
+    /// Find a toolchain by name, identifier, alias, or path.
     pub fn find_toolchain(&self, name: &str) -> Option<&Toolchain> {
         let resolved = resolve_path(name);
         self.toolchains.iter().find(|tc| {
             tc.name == name
                 || tc.identifier.as_deref() == Some(name)
+                || tc.aliases.iter().any(|alias| alias == name)
                 || tc.path == resolved
         })
     }
 
+    /// Build setting levels that sit below the project, highest precedence
+    /// first: SDK defaults, SDK settings, platform settings, then platform
+    /// defaults.
+    pub fn default_levels(platform: &Platform, target: Option<&Target>) -> Vec<Level> {
+        let mut levels = Vec::new();
+        if let Some(target) = target {
+            levels.push(target.default_properties.clone());
+            levels.push(target.settings());
+        }
+        levels.push(platform.settings());
+        levels.push(platform.default_properties.clone());
+        levels
+    }
+
+    /// Build setting levels that sit above the project, highest precedence
+    /// first: toolchain overrides, then platform overrides.
+    pub fn override_levels(platform: &Platform, toolchains: &[&Toolchain]) -> Vec<Level> {
+        let mut levels: Vec<Level> = toolchains
+            .iter()
+            .map(|tc| tc.override_build_settings.clone())
+            .collect();
+        levels.push(platform.override_properties.clone());
+        levels
+    }
+
     /// Base executable paths from the developer root.
     pub fn executable_paths(&self) -> Vec<String> {
         vec![
@@ -588,6 +806,52 @@ fn get_string(dict: &plist::Dictionary, key: &str) -> Option<String> {
     }
 }
 
+fn get_integer(dict: &plist::Dictionary, key: &str) -> Option<i64> {
+    match dict.get(key) {
+        Some(Value::Integer(i)) => i.as_signed(),
+        Some(Value::String(s)) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Convert a plist value into a build setting string. Arrays are joined
+/// with spaces and booleans become `YES`/`NO`, as Xcode does.
+fn setting_value_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Boolean(b) => Some(if *b { "YES" } else { "NO" }.to_string()),
+        Value::Integer(i) => i
+            .as_signed()
+            .map(|n| n.to_string())
+            .or_else(|| i.as_unsigned().map(|n| n.to_string())),
+        Value::Real(f) => Some(f.to_string()),
+        Value::Array(arr) => Some(
+            arr.iter()
+                .filter_map(setting_value_string)
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        _ => None,
+    }
+}
+
+fn settings_from_dict(dict: &plist::Dictionary, key: &str) -> Level {
+    match dict.get(key) {
+        Some(Value::Dictionary(settings)) => Level::new(
+            settings
+                .iter()
+                .filter_map(|(name, value)| {
+                    setting_value_string(value).map(|value| Setting {
+                        name: name.clone(),
+                        value,
+                    })
+                })
+                .collect(),
+        ),
+        _ => Level::default(),
+    }
+}
+
 fn string_array_from_dict(dict: &plist::Dictionary, key: &str) -> Vec<String> {
     match dict.get(key) {
         Some(Value::Array(arr)) => arr
@@ -607,7 +871,8 @@ fn resolve_path(path: &str) -> String {
         .unwrap_or_else(|_| path.to_string())
 }
 
-fn home_dir() -> Option<String> {
+/// The current user's home directory.
+pub fn home_dir() -> Option<String> {
     std::env::var("HOME").ok()
 }
 
@@ -634,3 +899,145 @@ pub fn find_executable(name: &str, search_paths: &[String]) -> Option<PathBuf> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A developer directory in the temp dir, removed on drop.
+    struct Fixture {
+        root: PathBuf,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Fixture {
+            let root = std::env::temp_dir().join(format!("xcbuild-sdk-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            Fixture { root }
+        }
+
+        fn write(&self, path: &str, contents: &str) {
+            let path = self.root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        fn manager(&self) -> Manager {
+            Manager::open(self.root.to_str().unwrap(), None).unwrap()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    const TOOLCHAIN_INFO: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>Identifier</key>
+	<string>org.example.swift.5</string>
+	<key>DisplayName</key>
+	<string>Swift 5</string>
+	<key>Aliases</key>
+	<array>
+		<string>swift</string>
+		<string>swift-latest</string>
+	</array>
+	<key>OverrideBuildSettings</key>
+	<dict>
+		<key>ENABLE_BITCODE</key>
+		<false/>
+		<key>SWIFT_USE_CUSTOM_TOOLCHAIN</key>
+		<string>YES</string>
+	</dict>
+	<key>OverrideEnvironment</key>
+	<dict>
+		<key>DYLD_LIBRARY_PATH</key>
+		<string>/opt/swift/lib</string>
+		<key>SWIFT_DEBUG_LEVEL</key>
+		<integer>2</integer>
+	</dict>
+</dict>
+</plist>
+"#;
+
+    #[test]
+    fn test_toolchain_info() {
+        let fixture = Fixture::new("toolchain");
+        fixture.write("Toolchains/Swift.xctoolchain/Info.plist", TOOLCHAIN_INFO);
+        let manager = fixture.manager();
+
+        let toolchain = manager.find_toolchain("swift-latest").unwrap();
+        assert_eq!(toolchain.name, "Swift");
+        assert_eq!(toolchain.identifier.as_deref(), Some("org.example.swift.5"));
+        assert_eq!(toolchain.aliases, vec!["swift", "swift-latest"]);
+        assert!(manager.find_toolchain("swift").is_some());
+        assert!(manager.find_toolchain("org.example.swift.5").is_some());
+        assert!(manager.find_toolchain("swift-4").is_none());
+
+        assert_eq!(
+            toolchain.override_environment,
+            vec![
+                ("DYLD_LIBRARY_PATH".to_string(), "/opt/swift/lib".to_string()),
+                ("SWIFT_DEBUG_LEVEL".to_string(), "2".to_string()),
+            ]
+        );
+        let settings = &toolchain.override_build_settings;
+        assert_eq!(settings.get("ENABLE_BITCODE").unwrap().value, "NO");
+        assert_eq!(settings.get("SWIFT_USE_CUSTOM_TOOLCHAIN").unwrap().value, "YES");
+    }
+
+    #[test]
+    fn test_platform_levels() {
+        let fixture = Fixture::new("platform");
+        fixture.write("Toolchains/Swift.xctoolchain/Info.plist", TOOLCHAIN_INFO);
+        fixture.write(
+            "Platforms/Test.platform/Info.plist",
+            r#"{
+                Name = test;
+                Identifier = org.example.platform.test;
+                DefaultProperties = { ARCHS = arm64; DEPLOYMENT_TARGET = "1.0"; };
+                OverrideProperties = { ARCHS = "$(inherited) arm64e"; ENABLE_BITCODE = YES; };
+                AdditionalInfo = { DTPlatformName = test; UIDeviceFamily = (1, 2); };
+                RuntimeDevicesPath = "/Library/Developer/Devices";
+            }"#,
+        );
+        fixture.write(
+            "Platforms/Test.platform/Developer/SDKs/Test1.0.sdk/SDKSettings.plist",
+            r#"{ CanonicalName = test1.0; Version = "1.0"; DefaultProperties = { DEPLOYMENT_TARGET = "$(SDK_VERSION)"; }; }"#,
+        );
+        let manager = fixture.manager();
+        let (platform, target) = manager.find_target("test1.0").unwrap();
+
+        assert_eq!(platform.runtime_devices_path.as_deref(), Some("/Library/Developer/Devices"));
+        assert_eq!(platform.additional_info.get("DTPlatformName"), Some(&Value::String("test".into())));
+        assert_eq!(
+            platform.additional_info.get("UIDeviceFamily"),
+            Some(&Value::Array(vec![Value::String("1".into()), Value::String("2".into())]))
+        );
+
+        // Highest precedence first: overrides, the project, then defaults.
+        let toolchain = manager.find_toolchain("swift").unwrap();
+        let mut environment = xcbuild_pbxsetting::Environment::default();
+        for level in Manager::override_levels(platform, &[toolchain]) {
+            environment.insert_back(level);
+        }
+        environment.insert_back(Level::new(vec![Setting {
+            name: "ARCHS".to_string(),
+            value: "$(inherited) x86_64".to_string(),
+        }]));
+        for level in Manager::default_levels(platform, Some(target)) {
+            environment.insert_back(level);
+        }
+
+        assert_eq!(environment.resolve("ARCHS"), "arm64 x86_64 arm64e");
+        assert_eq!(environment.resolve("DEPLOYMENT_TARGET"), "1.0");
+        assert_eq!(environment.resolve("PLATFORM_NAME"), "test");
+        // The toolchain's override wins over the platform's.
+        assert_eq!(environment.resolve("ENABLE_BITCODE"), "NO");
+    }
+}
@@ -0,0 +1,1053 @@
+//! Reading and writing xar archives, the container format of flat
+//! installer packages and .xip files.
+//!
+//! A xar archive is a binary header, a zlib-compressed XML table of
+//! contents (TOC) and a heap. The TOC describes each file and where its
+//! data lives in the heap, and the heap starts with a checksum of the
+//! compressed TOC.
+
+use byteorder::{BigEndian, ByteOrder};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum XarError {
+    #[error("file too small")]
+    FileTooSmall,
+    #[error("invalid magic (expected 'xar!')")]
+    InvalidMagic,
+    #[error("invalid version (expected 1, got {0})")]
+    InvalidVersion(u16),
+    #[error("invalid table of contents: {0}")]
+    InvalidToc(String),
+    #[error("unsupported checksum algorithm '{0}'")]
+    UnsupportedChecksum(String),
+    #[error("unsupported encoding '{0}'")]
+    UnsupportedEncoding(String),
+    #[error("checksum mismatch for {0}")]
+    ChecksumMismatch(String),
+    #[error("data extends beyond heap")]
+    DataOutOfBounds,
+    #[error("unsafe path '{0}'")]
+    UnsafePath(String),
+    #[error("hard link '{0}' refers to a file that was not extracted")]
+    MissingLinkTarget(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+const XAR_MAGIC: &[u8; 4] = b"xar!";
+const HEADER_SIZE: usize = 28;
+/// Header size when the checksum algorithm is given by name.
+const NAMED_HEADER_SIZE: usize = 64;
+
+/// Checksum algorithm of the TOC and of file data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    None,
+    Sha1,
+    Md5,
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    /// The name used in TOC `style` attributes.
+    pub fn name(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::None => "none",
+            ChecksumAlgorithm::Sha1 => "sha1",
+            ChecksumAlgorithm::Md5 => "md5",
+            ChecksumAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    fn from_name(name: &str) -> Result<Self, XarError> {
+        match name {
+            "none" => Ok(ChecksumAlgorithm::None),
+            "sha1" => Ok(ChecksumAlgorithm::Sha1),
+            "md5" => Ok(ChecksumAlgorithm::Md5),
+            "sha256" => Ok(ChecksumAlgorithm::Sha256),
+            _ => Err(XarError::UnsupportedChecksum(name.to_string())),
+        }
+    }
+
+    /// Size of a digest in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            ChecksumAlgorithm::None => 0,
+            ChecksumAlgorithm::Sha1 => 20,
+            ChecksumAlgorithm::Md5 => 16,
+            ChecksumAlgorithm::Sha256 => 32,
+        }
+    }
+
+    /// Digest `data`. MD5 is recognized for reading but not computed.
+    pub fn digest(self, data: &[u8]) -> Result<Vec<u8>, XarError> {
+        match self {
+            ChecksumAlgorithm::None => Ok(Vec::new()),
+            ChecksumAlgorithm::Sha1 => Ok(Sha1::digest(data).to_vec()),
+            ChecksumAlgorithm::Sha256 => Ok(Sha256::digest(data).to_vec()),
+            ChecksumAlgorithm::Md5 => Err(XarError::UnsupportedChecksum(self.name().to_string())),
+        }
+    }
+}
+
+/// How file data is stored in the heap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Encoding {
+    /// Stored as-is (`application/octet-stream`).
+    None,
+    /// A zlib stream, which xar calls `application/x-gzip`.
+    Zlib,
+    Other(String),
+}
+
+impl Encoding {
+    fn from_style(style: &str) -> Self {
+        match style {
+            "application/octet-stream" => Encoding::None,
+            "application/x-gzip" => Encoding::Zlib,
+            _ => Encoding::Other(style.to_string()),
+        }
+    }
+
+    pub fn style(&self) -> &str {
+        match self {
+            Encoding::None => "application/octet-stream",
+            Encoding::Zlib => "application/x-gzip",
+            Encoding::Other(style) => style,
+        }
+    }
+}
+
+/// A checksum recorded in the TOC, as a hex string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: ChecksumAlgorithm,
+    pub value: String,
+}
+
+/// Where a file's data lives in the heap.
+#[derive(Debug, Clone)]
+pub struct XarData {
+    /// Offset from the start of the heap.
+    pub offset: u64,
+    /// Archived (encoded) length.
+    pub length: u64,
+    /// Extracted size.
+    pub size: u64,
+    pub encoding: Encoding,
+    pub archived_checksum: Option<Checksum>,
+    pub extracted_checksum: Option<Checksum>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XarFileType {
+    File,
+    Directory,
+    Symlink,
+    Hardlink,
+    Other,
+}
+
+/// One file in the TOC.
+#[derive(Debug, Clone)]
+pub struct XarFile {
+    pub id: String,
+    pub name: String,
+    /// Path from the archive root, joined with `/`.
+    pub path: String,
+    pub file_type: XarFileType,
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub mtime: Option<String>,
+    /// Target of a symlink.
+    pub link: Option<String>,
+    /// The `link` attribute of a hard link's `<type>`: `original` for the
+    /// entry that holds the data, otherwise the id of that entry.
+    pub hard_link: Option<String>,
+    pub data: Option<XarData>,
+    parent: Option<usize>,
+}
+
+impl XarFile {
+    fn new(id: String, parent: Option<usize>) -> Self {
+        XarFile {
+            id,
+            name: String::new(),
+            path: String::new(),
+            file_type: XarFileType::File,
+            mode: None,
+            uid: None,
+            gid: None,
+            user: None,
+            group: None,
+            mtime: None,
+            link: None,
+            hard_link: None,
+            data: None,
+            parent,
+        }
+    }
+}
+
+/// The fixed-size header at the start of an archive.
+#[derive(Debug, Clone, Copy)]
+pub struct XarHeader {
+    pub header_size: u16,
+    pub version: u16,
+    pub toc_length_compressed: u64,
+    pub toc_length_uncompressed: u64,
+    pub checksum: ChecksumAlgorithm,
+}
+
+/// A read-only xar archive.
+#[derive(Debug)]
+pub struct Xar {
+    data: Vec<u8>,
+    header: XarHeader,
+    toc: String,
+    /// (offset, size) of the TOC checksum in the heap.
+    toc_checksum: Option<(u64, u64)>,
+    files: Vec<XarFile>,
+}
+
+impl Xar {
+    /// Parse an archive and verify its TOC checksum.
+    pub fn load(data: Vec<u8>) -> Result<Self, XarError> {
+        if data.len() < HEADER_SIZE {
+            return Err(XarError::FileTooSmall);
+        }
+        if &data[0..4] != XAR_MAGIC {
+            return Err(XarError::InvalidMagic);
+        }
+        let header_size = BigEndian::read_u16(&data[4..6]);
+        let version = BigEndian::read_u16(&data[6..8]);
+        if version != 1 {
+            return Err(XarError::InvalidVersion(version));
+        }
+        let checksum = match BigEndian::read_u32(&data[24..28]) {
+            0 => ChecksumAlgorithm::None,
+            1 => ChecksumAlgorithm::Sha1,
+            2 => ChecksumAlgorithm::Md5,
+            _ => {
+                let name = data
+                    .get(HEADER_SIZE..NAMED_HEADER_SIZE.min(header_size as usize))
+                    .ok_or(XarError::FileTooSmall)?;
+                let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+                ChecksumAlgorithm::from_name(&String::from_utf8_lossy(&name[..end]))?
+            }
+        };
+        let header = XarHeader {
+            header_size,
+            version,
+            toc_length_compressed: BigEndian::read_u64(&data[8..16]),
+            toc_length_uncompressed: BigEndian::read_u64(&data[16..24]),
+            checksum,
+        };
+
+        let toc_start = header_size as usize;
+        let toc_end = toc_start
+            .checked_add(header.toc_length_compressed as usize)
+            .filter(|&end| end <= data.len())
+            .ok_or(XarError::FileTooSmall)?;
+        let mut toc = String::new();
+        ZlibDecoder::new(&data[toc_start..toc_end])
+            .read_to_string(&mut toc)
+            .map_err(|e| XarError::InvalidToc(e.to_string()))?;
+
+        let (toc_checksum, files) = parse_toc(&toc)?;
+        let xar = Xar {
+            data,
+            header,
+            toc,
+            toc_checksum,
+            files,
+        };
+        xar.verify_toc()?;
+        Ok(xar)
+    }
+
+    pub fn header(&self) -> &XarHeader {
+        &self.header
+    }
+
+    /// The decompressed TOC XML.
+    pub fn toc(&self) -> &str {
+        &self.toc
+    }
+
+    /// Offset and size of the TOC checksum in the heap.
+    pub fn toc_checksum(&self) -> Option<(u64, u64)> {
+        self.toc_checksum
+    }
+
+    /// All files, with each directory before its contents.
+    pub fn files(&self) -> &[XarFile] {
+        &self.files
+    }
+
+    /// Find a file by its path from the archive root.
+    pub fn file(&self, path: &str) -> Option<&XarFile> {
+        self.files.iter().find(|f| f.path == path)
+    }
+
+    fn heap(&self) -> &[u8] {
+        let start = self.header.header_size as usize + self.header.toc_length_compressed as usize;
+        &self.data[start..]
+    }
+
+    fn heap_slice(&self, offset: u64, length: u64) -> Result<&[u8], XarError> {
+        let start = usize::try_from(offset).map_err(|_| XarError::DataOutOfBounds)?;
+        let end = usize::try_from(length)
+            .ok()
+            .and_then(|length| start.checked_add(length))
+            .ok_or(XarError::DataOutOfBounds)?;
+        self.heap().get(start..end).ok_or(XarError::DataOutOfBounds)
+    }
+
+    fn verify_toc(&self) -> Result<(), XarError> {
+        let algorithm = self.header.checksum;
+        if algorithm == ChecksumAlgorithm::None {
+            return Ok(());
+        }
+        let (offset, size) = self
+            .toc_checksum
+            .ok_or_else(|| XarError::InvalidToc("missing checksum".to_string()))?;
+        let stored = self.heap_slice(offset, size)?;
+        let start = self.header.header_size as usize;
+        let compressed = &self.data[start..start + self.header.toc_length_compressed as usize];
+        if algorithm.digest(compressed)? != stored {
+            return Err(XarError::ChecksumMismatch("table of contents".to_string()));
+        }
+        Ok(())
+    }
+
+    /// The data of `file` as stored in the heap, verified against its
+    /// archived checksum.
+    pub fn archived(&self, file: &XarFile) -> Result<&[u8], XarError> {
+        let Some(data) = &file.data else {
+            return Ok(&[]);
+        };
+        let archived = self.heap_slice(data.offset, data.length)?;
+        verify(&file.path, data.archived_checksum.as_ref(), archived)?;
+        Ok(archived)
+    }
+
+    /// The decoded contents of `file`, verified against its extracted
+    /// checksum.
+    pub fn read(&self, file: &XarFile) -> Result<Vec<u8>, XarError> {
+        let archived = self.archived(file)?;
+        let Some(data) = &file.data else {
+            return Ok(Vec::new());
+        };
+        let contents = match &data.encoding {
+            Encoding::None => archived.to_vec(),
+            Encoding::Zlib => {
+                // Decode at most one byte past the declared size, so a
+                // wrong <size> can neither size the buffer nor be exceeded.
+                let mut contents = Vec::new();
+                ZlibDecoder::new(archived)
+                    .take(data.size.saturating_add(1))
+                    .read_to_end(&mut contents)?;
+                if contents.len() as u64 > data.size {
+                    return Err(XarError::DataOutOfBounds);
+                }
+                contents
+            }
+            Encoding::Other(style) => return Err(XarError::UnsupportedEncoding(style.clone())),
+        };
+        verify(&file.path, data.extracted_checksum.as_ref(), &contents)?;
+        Ok(contents)
+    }
+
+    /// Extract every file below `destination`, creating it if needed.
+    /// Paths that would escape `destination`, directly or through a
+    /// symlink extracted earlier, are rejected, and existing files are
+    /// never overwritten.
+    pub fn extract(&self, destination: &Path) -> Result<(), XarError> {
+        fs::create_dir_all(destination)?;
+        // Extracted files by id, for the hard links that refer to them.
+        let mut extracted: HashMap<&str, PathBuf> = HashMap::new();
+        for file in &self.files {
+            let relative = Path::new(&file.path);
+            if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+                return Err(XarError::UnsafePath(file.path.clone()));
+            }
+            let target: PathBuf = destination.join(relative);
+            if passes_through_symlink(destination, relative)? {
+                return Err(XarError::UnsafePath(file.path.clone()));
+            }
+            match file.file_type {
+                XarFileType::Directory => fs::create_dir_all(&target)?,
+                XarFileType::Symlink => {
+                    let link = file.link.as_deref().unwrap_or_default();
+                    std::os::unix::fs::symlink(link, &target)?;
+                    continue;
+                }
+                XarFileType::Hardlink if file.hard_link.as_deref() != Some("original") => {
+                    // Only the original carries data; the others link to it.
+                    let original = file
+                        .hard_link
+                        .as_deref()
+                        .and_then(|id| extracted.get(id))
+                        .ok_or_else(|| XarError::MissingLinkTarget(file.path.clone()))?;
+                    fs::hard_link(original, &target)?;
+                    continue;
+                }
+                XarFileType::File | XarFileType::Hardlink => {
+                    let contents = self.read(file)?;
+                    // create_new does not follow a symlink at the target.
+                    fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(&target)?
+                        .write_all(&contents)?;
+                    extracted.insert(&file.id, target.clone());
+                }
+                XarFileType::Other => continue,
+            }
+            if let Some(mode) = file.mode {
+                fs::set_permissions(&target, fs::Permissions::from_mode(mode & 0o7777))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `relative` below `destination`, or any directory on the way to
+/// it, is a symlink.
+fn passes_through_symlink(destination: &Path, relative: &Path) -> Result<bool, XarError> {
+    let mut current = destination.to_path_buf();
+    for component in relative.components() {
+        current.push(component);
+        match fs::symlink_metadata(&current) {
+            Ok(metadata) if metadata.file_type().is_symlink() => return Ok(true),
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(false)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+fn verify(path: &str, checksum: Option<&Checksum>, data: &[u8]) -> Result<(), XarError> {
+    let Some(checksum) = checksum else {
+        return Ok(());
+    };
+    if checksum.algorithm == ChecksumAlgorithm::None {
+        return Ok(());
+    }
+    if hex(&checksum.algorithm.digest(data)?) != checksum.value.trim().to_ascii_lowercase() {
+        return Err(XarError::ChecksumMismatch(path.to_string()));
+    }
+    Ok(())
+}
+
+fn get_attr(e: &BytesStart, key: &str) -> Option<String> {
+    for attr in e.attributes().flatten() {
+        if attr.key.as_ref() == key.as_bytes() {
+            return Some(String::from_utf8_lossy(&attr.value).to_string());
+        }
+    }
+    None
+}
+
+fn parse_number<T: std::str::FromStr>(element: &str, text: &str) -> Result<T, XarError> {
+    text.trim()
+        .parse()
+        .map_err(|_| XarError::InvalidToc(format!("invalid {element} '{text}'")))
+}
+
+/// Parse the TOC into the heap location of its checksum and the files,
+/// in document order.
+#[allow(clippy::type_complexity)]
+fn parse_toc(toc: &str) -> Result<(Option<(u64, u64)>, Vec<XarFile>), XarError> {
+    let mut reader = Reader::from_str(toc);
+    let mut elements: Vec<String> = Vec::new();
+    // Index of the innermost open <file>, and the style of the open
+    // checksum element.
+    let mut open_files: Vec<usize> = Vec::new();
+    let mut style = String::new();
+    let mut files: Vec<XarFile> = Vec::new();
+    let mut toc_checksum = (None, None);
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| XarError::InvalidToc(e.to_string()))?;
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                let parent = elements.last().map(String::as_str);
+                match (parent, name.as_str()) {
+                    (_, "file") => {
+                        files.push(XarFile::new(get_attr(e, "id").unwrap_or_default(), open_files.last().copied()));
+                        open_files.push(files.len() - 1);
+                    }
+                    (Some("data"), "encoding") => {
+                        if let Some(data) = open_files.last().and_then(|&i| files[i].data.as_mut()) {
+                            data.encoding = Encoding::from_style(&get_attr(e, "style").unwrap_or_default());
+                        }
+                    }
+                    (Some("file"), "type") => {
+                        if let Some(&i) = open_files.last() {
+                            files[i].hard_link = get_attr(e, "link");
+                        }
+                    }
+                    (Some("file"), "data") => {
+                        if let Some(&i) = open_files.last() {
+                            files[i].data = Some(XarData {
+                                offset: 0,
+                                length: 0,
+                                size: 0,
+                                encoding: Encoding::None,
+                                archived_checksum: None,
+                                extracted_checksum: None,
+                            });
+                        }
+                    }
+                    _ => style = get_attr(e, "style").unwrap_or_default(),
+                }
+                if let Event::Start(_) = event {
+                    elements.push(name);
+                }
+            }
+            Event::End(_) => {
+                let closed = elements.pop();
+                if closed.as_deref() == Some("file") {
+                    open_files.pop();
+                }
+            }
+            Event::Text(ref t) => {
+                let text = t.unescape().map_err(|e| XarError::InvalidToc(e.to_string()))?;
+                let depth = elements.len();
+                let (Some(element), parent) = (elements.last(), depth.checked_sub(2).map(|i| elements[i].as_str()))
+                else {
+                    continue;
+                };
+                let grandparent = depth.checked_sub(3).map(|i| elements[i].as_str());
+
+                if open_files.is_empty() {
+                    if parent == Some("checksum") && grandparent == Some("toc") {
+                        match element.as_str() {
+                            "offset" => toc_checksum.0 = Some(parse_number(element, &text)?),
+                            "size" => toc_checksum.1 = Some(parse_number(element, &text)?),
+                            _ => {}
+                        }
+                    }
+                    continue;
+                }
+                let file = &mut files[*open_files.last().unwrap()];
+                match parent {
+                    Some("file") => match element.as_str() {
+                        "name" => file.name = text.to_string(),
+                        "type" => {
+                            file.file_type = match text.trim() {
+                                "file" => XarFileType::File,
+                                "directory" => XarFileType::Directory,
+                                "symlink" => XarFileType::Symlink,
+                                "hardlink" => XarFileType::Hardlink,
+                                _ => XarFileType::Other,
+                            }
+                        }
+                        "mode" => {
+                            let mode = u32::from_str_radix(text.trim(), 8)
+                                .map_err(|_| XarError::InvalidToc(format!("invalid mode '{text}'")))?;
+                            file.mode = Some(mode);
+                        }
+                        "uid" => file.uid = Some(parse_number(element, &text)?),
+                        "gid" => file.gid = Some(parse_number(element, &text)?),
+                        "user" => file.user = Some(text.to_string()),
+                        "group" => file.group = Some(text.to_string()),
+                        "mtime" => file.mtime = Some(text.to_string()),
+                        "link" => file.link = Some(text.to_string()),
+                        _ => {}
+                    },
+                    Some("data") if grandparent == Some("file") => {
+                        let Some(data) = file.data.as_mut() else {
+                            continue;
+                        };
+                        match element.as_str() {
+                            "offset" => data.offset = parse_number(element, &text)?,
+                            "length" => data.length = parse_number(element, &text)?,
+                            "size" => data.size = parse_number(element, &text)?,
+                            "archived-checksum" | "extracted-checksum" => {
+                                let checksum = Checksum {
+                                    algorithm: ChecksumAlgorithm::from_name(&style)?,
+                                    value: text.trim().to_string(),
+                                };
+                                if element == "archived-checksum" {
+                                    data.archived_checksum = Some(checksum);
+                                } else {
+                                    data.extracted_checksum = Some(checksum);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    // Parents precede their children, so their paths are already known.
+    for i in 0..files.len() {
+        files[i].path = match files[i].parent {
+            Some(parent) => format!("{}/{}", files[parent].path, files[i].name),
+            None => files[i].name.clone(),
+        };
+    }
+
+    let toc_checksum = match toc_checksum {
+        (Some(offset), Some(size)) => Some((offset, size)),
+        _ => None,
+    };
+    Ok((toc_checksum, files))
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+enum WriterContents {
+    File { data: Vec<u8>, encoding: Encoding },
+    Directory { children: Vec<WriterEntry> },
+    Symlink { target: String },
+}
+
+struct WriterEntry {
+    name: String,
+    mode: u32,
+    contents: WriterContents,
+}
+
+/// Builds a xar archive in memory. Files are added by path; missing
+/// parent directories are created with mode 0755.
+pub struct XarWriter {
+    checksum: ChecksumAlgorithm,
+    root: Vec<WriterEntry>,
+}
+
+impl XarWriter {
+    /// A writer whose TOC and file checksums use `checksum`.
+    pub fn new(checksum: ChecksumAlgorithm) -> Self {
+        XarWriter {
+            checksum,
+            root: Vec::new(),
+        }
+    }
+
+    fn insert(&mut self, path: &str, mode: u32, contents: WriterContents) {
+        let mut components: Vec<&str> = path.split('/').filter(|c| !c.is_empty() && *c != ".").collect();
+        let Some(name) = components.pop() else {
+            return;
+        };
+        let mut siblings = &mut self.root;
+        for component in components {
+            let index = match siblings.iter().position(|e| e.name == component) {
+                Some(index) => index,
+                None => {
+                    siblings.push(WriterEntry {
+                        name: component.to_string(),
+                        mode: 0o755,
+                        contents: WriterContents::Directory { children: Vec::new() },
+                    });
+                    siblings.len() - 1
+                }
+            };
+            let entry = &mut siblings[index];
+            if !matches!(entry.contents, WriterContents::Directory { .. }) {
+                entry.contents = WriterContents::Directory { children: Vec::new() };
+            }
+            let WriterContents::Directory { children } = &mut entry.contents else {
+                unreachable!();
+            };
+            siblings = children;
+        }
+
+        let entry = WriterEntry {
+            name: name.to_string(),
+            mode,
+            contents,
+        };
+        match siblings.iter_mut().find(|e| e.name == name) {
+            // Re-adding a directory keeps what was already added below it.
+            Some(existing) => match (&mut existing.contents, entry.contents) {
+                (WriterContents::Directory { .. }, WriterContents::Directory { .. }) => existing.mode = mode,
+                (_, contents) => {
+                    existing.mode = mode;
+                    existing.contents = contents;
+                }
+            },
+            None => siblings.push(entry),
+        }
+    }
+
+    /// Add a file, stored with `encoding` (`Encoding::None` or `Encoding::Zlib`).
+    pub fn add_file(&mut self, path: &str, data: Vec<u8>, mode: u32, encoding: Encoding) {
+        self.insert(path, mode, WriterContents::File { data, encoding });
+    }
+
+    pub fn add_directory(&mut self, path: &str, mode: u32) {
+        self.insert(path, mode, WriterContents::Directory { children: Vec::new() });
+    }
+
+    pub fn add_symlink(&mut self, path: &str, target: &str) {
+        self.insert(
+            path,
+            0o755,
+            WriterContents::Symlink {
+                target: target.to_string(),
+            },
+        );
+    }
+
+    fn write_entries(
+        &self,
+        entries: &[WriterEntry],
+        depth: usize,
+        next_id: &mut u64,
+        toc: &mut String,
+        heap: &mut Vec<u8>,
+    ) -> Result<(), XarError> {
+        let algorithm = self.checksum;
+        for entry in entries {
+            let indent = " ".repeat(depth + 1);
+            let _ = writeln!(toc, "{indent} <file id=\"{next_id}\">");
+            *next_id += 1;
+
+            let file_type = match &entry.contents {
+                WriterContents::File { data, encoding } => {
+                    let archived = match encoding {
+                        Encoding::None => data.clone(),
+                        Encoding::Zlib => {
+                            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                            encoder.write_all(data)?;
+                            encoder.finish()?
+                        }
+                        Encoding::Other(style) => return Err(XarError::UnsupportedEncoding(style.clone())),
+                    };
+                    let offset = algorithm.size() + heap.len();
+                    let _ = writeln!(toc, "{indent}  <data>");
+                    let _ = writeln!(toc, "{indent}   <length>{}</length>", archived.len());
+                    let _ = writeln!(toc, "{indent}   <offset>{offset}</offset>");
+                    let _ = writeln!(toc, "{indent}   <size>{}</size>", data.len());
+                    let _ = writeln!(toc, "{indent}   <encoding style=\"{}\"/>", encoding.style());
+                    if algorithm != ChecksumAlgorithm::None {
+                        let name = algorithm.name();
+                        let _ = writeln!(
+                            toc,
+                            "{indent}   <extracted-checksum style=\"{name}\">{}</extracted-checksum>",
+                            hex(&algorithm.digest(data)?)
+                        );
+                        let _ = writeln!(
+                            toc,
+                            "{indent}   <archived-checksum style=\"{name}\">{}</archived-checksum>",
+                            hex(&algorithm.digest(&archived)?)
+                        );
+                    }
+                    let _ = writeln!(toc, "{indent}  </data>");
+                    heap.extend_from_slice(&archived);
+                    "file"
+                }
+                WriterContents::Directory { .. } => "directory",
+                WriterContents::Symlink { target } => {
+                    let _ = writeln!(toc, "{indent}  <link type=\"file\">{}</link>", xml_escape(target));
+                    "symlink"
+                }
+            };
+            let _ = writeln!(toc, "{indent}  <name>{}</name>", xml_escape(&entry.name));
+            let _ = writeln!(toc, "{indent}  <type>{file_type}</type>");
+            let _ = writeln!(toc, "{indent}  <mode>{:04o}</mode>", entry.mode);
+            let _ = writeln!(toc, "{indent}  <uid>0</uid>\n{indent}  <gid>0</gid>");
+
+            if let WriterContents::Directory { children } = &entry.contents {
+                self.write_entries(children, depth + 1, next_id, toc, heap)?;
+            }
+            let _ = writeln!(toc, "{indent} </file>");
+        }
+        Ok(())
+    }
+
+    /// Serialize the archive.
+    pub fn serialize(&self) -> Result<Vec<u8>, XarError> {
+        let algorithm = self.checksum;
+        if algorithm == ChecksumAlgorithm::Md5 {
+            return Err(XarError::UnsupportedChecksum(algorithm.name().to_string()));
+        }
+
+        let mut toc = String::new();
+        toc.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<xar>\n <toc>\n");
+        if algorithm != ChecksumAlgorithm::None {
+            let _ = writeln!(
+                toc,
+                "  <checksum style=\"{}\">\n   <offset>0</offset>\n   <size>{}</size>\n  </checksum>",
+                algorithm.name(),
+                algorithm.size()
+            );
+        }
+        let mut heap = Vec::new();
+        let mut next_id = 1;
+        self.write_entries(&self.root, 0, &mut next_id, &mut toc, &mut heap)?;
+        toc.push_str(" </toc>\n</xar>\n");
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(toc.as_bytes())?;
+        let compressed_toc = encoder.finish()?;
+
+        // SHA-256 has no algorithm number and is named in a longer header.
+        let (header_size, algorithm_number) = match algorithm {
+            ChecksumAlgorithm::None => (HEADER_SIZE, 0u32),
+            ChecksumAlgorithm::Sha1 => (HEADER_SIZE, 1),
+            ChecksumAlgorithm::Md5 => (HEADER_SIZE, 2),
+            ChecksumAlgorithm::Sha256 => (NAMED_HEADER_SIZE, 3),
+        };
+        let mut out = Vec::new();
+        out.extend_from_slice(XAR_MAGIC);
+        out.extend_from_slice(&(header_size as u16).to_be_bytes());
+        out.extend_from_slice(&1u16.to_be_bytes());
+        out.extend_from_slice(&(compressed_toc.len() as u64).to_be_bytes());
+        out.extend_from_slice(&(toc.len() as u64).to_be_bytes());
+        out.extend_from_slice(&algorithm_number.to_be_bytes());
+        if header_size == NAMED_HEADER_SIZE {
+            let mut name = [0u8; NAMED_HEADER_SIZE - HEADER_SIZE];
+            name[..algorithm.name().len()].copy_from_slice(algorithm.name().as_bytes());
+            out.extend_from_slice(&name);
+        }
+        out.extend_from_slice(&compressed_toc);
+        out.extend_from_slice(&algorithm.digest(&compressed_toc)?);
+        out.extend_from_slice(&heap);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    fn sample(checksum: ChecksumAlgorithm) -> Vec<u8> {
+        let mut writer = XarWriter::new(checksum);
+        writer.add_file("Payload", vec![7; 100], 0o644, Encoding::None);
+        writer.add_file("Resources/en.lproj/Info & <More>.txt", b"hello hello hello".to_vec(), 0o600, Encoding::Zlib);
+        writer.add_symlink("Resources/Current", "en.lproj");
+        writer.serialize().unwrap()
+    }
+
+    #[test]
+    fn test_toc_and_heap_layout() {
+        let mut writer = XarWriter::new(ChecksumAlgorithm::Sha1);
+        writer.add_file("Payload", vec![7; 100], 0o644, Encoding::None);
+        writer.add_file("Resources/Info & <More>.txt", b"hello".to_vec(), 0o600, Encoding::Zlib);
+        let data = writer.serialize().unwrap();
+
+        assert_eq!(&data[0..4], b"xar!");
+        assert_eq!(u16::from_be_bytes([data[4], data[5]]), HEADER_SIZE as u16);
+        assert_eq!(u32::from_be_bytes(data[24..28].try_into().unwrap()), 1);
+        let compressed = u64::from_be_bytes(data[8..16].try_into().unwrap()) as usize;
+        let toc_end = HEADER_SIZE + compressed;
+
+        let mut toc = String::new();
+        ZlibDecoder::new(&data[HEADER_SIZE..toc_end]).read_to_string(&mut toc).unwrap();
+        assert_eq!(toc.len() as u64, u64::from_be_bytes(data[16..24].try_into().unwrap()));
+        assert!(toc.contains("<name>Resources</name>"));
+        assert!(toc.contains("<name>Info &amp; &lt;More&gt;.txt</name>"));
+        assert!(toc.contains("<mode>0600</mode>"));
+
+        // The heap starts with the TOC checksum, then the stored payload.
+        let heap = &data[toc_end..];
+        assert_eq!(&heap[..20], Sha1::digest(&data[HEADER_SIZE..toc_end]).as_slice());
+        assert_eq!(&heap[20..120], &[7; 100][..]);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for checksum in [ChecksumAlgorithm::Sha1, ChecksumAlgorithm::Sha256, ChecksumAlgorithm::None] {
+            let xar = Xar::load(sample(checksum)).unwrap();
+            assert_eq!(xar.header().checksum, checksum);
+
+            let paths: Vec<&str> = xar.files().iter().map(|f| f.path.as_str()).collect();
+            assert_eq!(
+                paths,
+                [
+                    "Payload",
+                    "Resources",
+                    "Resources/en.lproj",
+                    "Resources/en.lproj/Info & <More>.txt",
+                    "Resources/Current"
+                ]
+            );
+
+            let payload = xar.file("Payload").unwrap();
+            assert_eq!(xar.read(payload).unwrap(), vec![7; 100]);
+
+            let info = xar.file("Resources/en.lproj/Info & <More>.txt").unwrap();
+            assert_eq!(info.mode, Some(0o600));
+            assert_eq!(info.data.as_ref().unwrap().encoding, Encoding::Zlib);
+            assert_eq!(xar.read(info).unwrap(), b"hello hello hello");
+
+            let link = xar.file("Resources/Current").unwrap();
+            assert_eq!(link.file_type, XarFileType::Symlink);
+            assert_eq!(link.link.as_deref(), Some("en.lproj"));
+            assert_eq!(xar.file("Resources").unwrap().file_type, XarFileType::Directory);
+        }
+    }
+
+    #[test]
+    fn test_detects_corruption() {
+        let mut data = sample(ChecksumAlgorithm::Sha1);
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        let xar = Xar::load(data).unwrap();
+        let info = xar.file("Resources/en.lproj/Info & <More>.txt").unwrap();
+        assert!(matches!(xar.read(info), Err(XarError::ChecksumMismatch(_))));
+        assert_eq!(xar.read(xar.file("Payload").unwrap()).unwrap(), vec![7; 100]);
+
+        // Corrupting the stored TOC checksum fails the load.
+        let mut data = sample(ChecksumAlgorithm::Sha256);
+        let toc_length = BigEndian::read_u64(&data[8..16]) as usize;
+        data[NAMED_HEADER_SIZE + toc_length] ^= 1;
+        assert!(matches!(Xar::load(data), Err(XarError::ChecksumMismatch(_))));
+    }
+
+    #[test]
+    fn test_extract() {
+        let xar = Xar::load(sample(ChecksumAlgorithm::Sha1)).unwrap();
+        let destination = std::env::temp_dir().join(format!("xcbuild-xar-test-{}", std::process::id()));
+        xar.extract(&destination).unwrap();
+        let info = destination.join("Resources/en.lproj/Info & <More>.txt");
+        assert_eq!(fs::read(&info).unwrap(), b"hello hello hello");
+        assert_eq!(fs::metadata(&info).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::read_link(destination.join("Resources/Current")).unwrap(), Path::new("en.lproj"));
+        fs::remove_dir_all(&destination).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_unsafe_paths() {
+        let mut writer = XarWriter::new(ChecksumAlgorithm::Sha1);
+        writer.add_file("..", b"x".to_vec(), 0o644, Encoding::None);
+        let xar = Xar::load(writer.serialize().unwrap()).unwrap();
+        let destination = std::env::temp_dir().join(format!("xcbuild-xar-unsafe-{}", std::process::id()));
+        assert!(matches!(xar.extract(&destination), Err(XarError::UnsafePath(_))));
+        let _ = fs::remove_dir_all(&destination);
+    }
+
+    /// Rewrite the TOC of an archive without checksums.
+    fn edit_toc(data: &[u8], edit: impl Fn(&str) -> String) -> Vec<u8> {
+        let compressed = BigEndian::read_u64(&data[8..16]) as usize;
+        let mut toc = String::new();
+        ZlibDecoder::new(&data[HEADER_SIZE..HEADER_SIZE + compressed])
+            .read_to_string(&mut toc)
+            .unwrap();
+        let toc = edit(&toc);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(toc.as_bytes()).unwrap();
+        let compressed_toc = encoder.finish().unwrap();
+
+        let mut out = data[..HEADER_SIZE].to_vec();
+        out[8..16].copy_from_slice(&(compressed_toc.len() as u64).to_be_bytes());
+        out[16..24].copy_from_slice(&(toc.len() as u64).to_be_bytes());
+        out.extend_from_slice(&compressed_toc);
+        out.extend_from_slice(&data[HEADER_SIZE + compressed..]);
+        out
+    }
+
+    #[test]
+    fn test_rejects_writes_through_symlinks() {
+        let base = std::env::temp_dir().join(format!("xcbuild-xar-symlink-{}", std::process::id()));
+        let destination = base.join("destination");
+        let outside = base.join("outside");
+        fs::create_dir_all(&outside).unwrap();
+
+        // A symlink `a` to a directory outside the destination, followed
+        // by a directory also named `a` holding `pwned`.
+        let mut writer = XarWriter::new(ChecksumAlgorithm::None);
+        writer.add_symlink("a", outside.to_str().unwrap());
+        writer.add_file("b/pwned", b"x".to_vec(), 0o644, Encoding::None);
+        let data = edit_toc(&writer.serialize().unwrap(), |toc| toc.replace("<name>b</name>", "<name>a</name>"));
+        let xar = Xar::load(data).unwrap();
+        assert_eq!(xar.files()[2].path, "a/pwned");
+
+        assert!(matches!(xar.extract(&destination), Err(XarError::UnsafePath(_))));
+        assert!(!outside.join("pwned").exists());
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_extracts_hard_links() {
+        let mut writer = XarWriter::new(ChecksumAlgorithm::None);
+        writer.add_file("original", b"shared contents".to_vec(), 0o644, Encoding::Zlib);
+        writer.add_file("link", Vec::new(), 0o644, Encoding::None);
+        let data = edit_toc(&writer.serialize().unwrap(), |toc| {
+            toc.replacen("<type>file</type>", "<type link=\"original\">hardlink</type>", 1)
+                .replacen("<type>file</type>", "<type link=\"1\">hardlink</type>", 1)
+        });
+        let xar = Xar::load(data).unwrap();
+        assert_eq!(xar.files()[0].id, "1");
+        assert_eq!(xar.files()[1].hard_link.as_deref(), Some("1"));
+
+        let destination = std::env::temp_dir().join(format!("xcbuild-xar-hardlink-{}", std::process::id()));
+        xar.extract(&destination).unwrap();
+        let original = fs::metadata(destination.join("original")).unwrap();
+        let link = fs::metadata(destination.join("link")).unwrap();
+        assert_eq!(fs::read(destination.join("link")).unwrap(), b"shared contents");
+        assert_eq!(original.ino(), link.ino());
+        fs::remove_dir_all(&destination).unwrap();
+
+        // A link to an id that was never extracted is an error.
+        let data = edit_toc(&writer.serialize().unwrap(), |toc| {
+            toc.replacen("<type>file</type>", "<type link=\"9\">hardlink</type>", 1)
+        });
+        let xar = Xar::load(data).unwrap();
+        let destination = std::env::temp_dir().join(format!("xcbuild-xar-badlink-{}", std::process::id()));
+        assert!(matches!(xar.extract(&destination), Err(XarError::MissingLinkTarget(_))));
+        let _ = fs::remove_dir_all(&destination);
+    }
+
+    #[test]
+    fn test_enforces_declared_size() {
+        let mut writer = XarWriter::new(ChecksumAlgorithm::None);
+        writer.add_file("file", b"hello hello hello".to_vec(), 0o644, Encoding::Zlib);
+        let data = edit_toc(&writer.serialize().unwrap(), |toc| toc.replace("<size>17</size>", "<size>5</size>"));
+        let xar = Xar::load(data).unwrap();
+        let file = xar.file("file").unwrap();
+        assert!(matches!(xar.read(file), Err(XarError::DataOutOfBounds)));
+    }
+}
@@ -219,10 +219,8 @@ fn parse_build_action(
                     entries.push(entry);
                 }
             }
-            Ok(Event::End(ref e)) => {
-                if e.name().as_ref() == b"BuildAction" {
-                    break;
-                }
+            Ok(Event::End(ref e)) if e.name().as_ref() == b"BuildAction" => {
+                break;
             }
             Ok(Event::Eof) => break,
             Err(_) => break,
@@ -258,10 +256,8 @@ fn parse_test_action(
                     });
                 }
             }
-            Ok(Event::End(ref e)) => {
-                if e.name().as_ref() == b"TestAction" {
-                    break;
-                }
+            Ok(Event::End(ref e)) if e.name().as_ref() == b"TestAction" => {
+                break;
             }
             Ok(Event::Eof) => break,
             Err(_) => break,
@@ -297,10 +293,8 @@ fn parse_launch_action(
                     runnable = parse_buildable_reference(reader);
                 }
             }
-            Ok(Event::End(ref e)) => {
-                if e.name().as_ref() == b"LaunchAction" {
-                    break;
-                }
+            Ok(Event::End(ref e)) if e.name().as_ref() == b"LaunchAction" => {
+                break;
             }
             Ok(Event::Eof) => break,
             Err(_) => break,
@@ -337,10 +331,8 @@ fn parse_profile_action(
                     runnable = parse_buildable_reference(reader);
                 }
             }
-            Ok(Event::End(ref e)) => {
-                if e.name().as_ref() == b"ProfileAction" {
-                    break;
-                }
+            Ok(Event::End(ref e)) if e.name().as_ref() == b"ProfileAction" => {
+                break;
             }
             Ok(Event::Eof) => break,
             Err(_) => break,
@@ -364,10 +356,8 @@ fn parse_analyze_action(
     // Skip to end of AnalyzeAction
     loop {
         match reader.read_event() {
-            Ok(Event::End(ref e)) => {
-                if e.name().as_ref() == b"AnalyzeAction" {
-                    break;
-                }
+            Ok(Event::End(ref e)) if e.name().as_ref() == b"AnalyzeAction" => {
+                break;
             }
             Ok(Event::Eof) => break,
             Err(_) => break,
@@ -391,10 +381,8 @@ fn parse_archive_action(
     // Skip to end of ArchiveAction
     loop {
         match reader.read_event() {
-            Ok(Event::End(ref e)) => {
-                if e.name().as_ref() == b"ArchiveAction" {
-                    break;
-                }
+            Ok(Event::End(ref e)) if e.name().as_ref() == b"ArchiveAction" => {
+                break;
             }
             Ok(Event::Eof) => break,
             Err(_) => break,
@@ -1,9 +1,17 @@
 use anyhow::{Context, Result};
 use plist::Value;
-use rustyline::DefaultEditor;
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Editor, Helper};
+use std::cell::RefCell;
 use std::env;
 use std::fs;
+use std::io::{IsTerminal, Read, Write};
 use std::path::Path;
+use std::rc::Rc;
 use xcbuild_plist::*;
 
 fn help(error: Option<&str>) -> ! {
@@ -14,7 +22,10 @@ fn help(error: Option<&str>) -> ! {
     eprintln!("Usage: PlistBuddy [options] <file.plist>\n");
     eprintln!("Options:");
     eprintln!("  -c \"<command>\" command to execute, otherwise run in interactive mode");
+    eprintln!("  -f <file> read commands from file, one per line (- for stdin)");
+    eprintln!("  -a abort on the first failing command without writing the file");
     eprintln!("  -x output will be in xml plist format");
+    eprintln!("  -j output will be in json format");
     eprintln!("  -l do not follow symlinks");
     eprintln!("  -h print help including commands");
 
@@ -28,7 +39,7 @@ fn command_help() {
     eprintln!("  Save - Save the changed plist file");
     eprintln!("  Revert - Revert to the saved plist file");
     eprintln!("  Clear <Type> - Clears all data, and sets root to an empty Type");
-    eprintln!("  Print [<KeyPath>] - Print value at KeyPath or root");
+    eprintln!("  Print [<KeyPath>] [-xml|-json|-ascii] - Print value at KeyPath or root");
     eprintln!("  Set <KeyPath> <Value> - Set value at KeyPath to Value");
     eprintln!("  Add <KeyPath> <Type> <Value> - Set value at KeyPath to Value");
     eprintln!("  Copy <SrcKeyPath> <DstKeyPath> - Copy SrcKeyPath to DstKeyPath ");
@@ -161,7 +172,7 @@ fn delete_at_key(root: &mut Value, keys: &[String]) -> bool {
     }
 }
 
-fn print_value(root: &Value, keys: &[String], use_xml: bool) -> bool {
+fn print_value(root: &Value, keys: &[String], format: PlistFormat) -> bool {
     let target = if keys.is_empty() {
         root
     } else {
@@ -174,15 +185,9 @@ fn print_value(root: &Value, keys: &[String], use_xml: bool) -> bool {
         }
     };
 
-    let format = if use_xml {
-        PlistFormat::Xml
-    } else {
-        PlistFormat::Ascii
-    };
-
     match xcbuild_plist::serialize(target, format) {
         Ok(bytes) => {
-            let _ = std::io::Write::write_all(&mut std::io::stdout(), &bytes);
+            let _ = std::io::stdout().write_all(&bytes);
             true
         }
         Err(e) => {
@@ -194,7 +199,7 @@ fn print_value(root: &Value, keys: &[String], use_xml: bool) -> bool {
 
 fn save_plist(root: &Value, format: PlistFormat, path: &str) -> bool {
     match xcbuild_plist::serialize(root, format) {
-        Ok(bytes) => match fs::write(path, bytes) {
+        Ok(bytes) => match write_atomically(Path::new(path), &bytes) {
             Ok(()) => true,
             Err(e) => {
                 eprintln!("Could not write to output: {e}");
@@ -208,13 +213,46 @@ fn save_plist(root: &Value, format: PlistFormat, path: &str) -> bool {
     }
 }
 
+/// Write to a temporary file next to the destination and rename it into
+/// place, so an interrupted save never leaves a truncated plist behind.
+/// Symlinks are resolved first so the link itself is preserved.
+fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => std::path::PathBuf::from("."),
+    };
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp = dir.join(format!(".{name}.{}.tmp", std::process::id()));
+
+    // create_new never follows or reuses whatever is already at `temp`.
+    let mut file = fs::OpenOptions::new().write(true).create_new(true).open(&temp)?;
+    let result = (|| {
+        file.write_all(contents)?;
+        file.sync_all()?;
+        if let Ok(metadata) = fs::metadata(&target) {
+            fs::set_permissions(&temp, metadata.permissions())?;
+        }
+        fs::rename(&temp, &target)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
 fn load_plist(path: &str) -> Result<(Value, PlistFormat)> {
     let data = fs::read(path).with_context(|| format!("unable to read {path}"))?;
     xcbuild_plist::deserialize(&data).with_context(|| format!("unable to parse {path}"))
 }
 
 /// Tokenize a command line, respecting quoted strings, backslash escaping,
-/// and both single and double quotes.
+/// and both single and double quotes. Outside single quotes a backslash
+/// makes the next character literal, except that `\:` is kept as is so it
+/// reaches the key path parser as an escaped separator.
 fn tokenize(input: &str) -> Result<Vec<String>, String> {
     let input = input.trim();
     let mut tokens = Vec::new();
@@ -227,6 +265,7 @@ fn tokenize(input: &str) -> Result<Vec<String>, String> {
         if ch == '\\' && !in_single_quote {
             // Backslash escaping: next character is literal
             match chars.next() {
+                Some(':') => current.push_str("\\:"),
                 Some(escaped) => current.push(escaped),
                 None => return Err("Trailing backslash".to_string()),
             }
@@ -257,15 +296,17 @@ fn tokenize(input: &str) -> Result<Vec<String>, String> {
     Ok(tokens)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn process_command(
     path: &str,
-    use_xml: bool,
+    print_format: PlistFormat,
     save_format: PlistFormat,
     root: &mut Value,
     input: &str,
     mutated: &mut bool,
     keep_reading: &mut bool,
     last_saved: &mut Value,
+    defer_save: bool,
 ) -> bool {
     let tokens = match tokenize(input) {
         Ok(t) => t,
@@ -281,14 +322,24 @@ fn process_command(
     let command = &tokens[0];
     match command.to_ascii_lowercase().as_str() {
         "print" => {
-            let keys = if tokens.len() > 1 {
-                parse_key_path(&tokens[1])
-            } else {
-                vec![]
-            };
-            print_value(root, &keys, use_xml)
+            let mut format = print_format;
+            let mut keys = vec![];
+            for token in &tokens[1..] {
+                match token.as_str() {
+                    "-xml" => format = PlistFormat::Xml,
+                    "-json" => format = PlistFormat::Json,
+                    "-ascii" => format = PlistFormat::Ascii,
+                    _ => keys = parse_key_path(token),
+                }
+            }
+            print_value(root, &keys, format)
         }
         "save" => {
+            if defer_save {
+                // Written once the whole script has succeeded.
+                *mutated = true;
+                return true;
+            }
             let result = save_plist(root, save_format, path);
             if result {
                 *last_saved = root.clone();
@@ -444,23 +495,27 @@ fn process_command(
             };
 
             match (target, merge_value) {
-                (Value::Dictionary(target_dict), Value::Dictionary(merge_dict)) => {
-                    for (k, v) in merge_dict.into_iter() {
-                        if target_dict.get(&k).is_some() {
-                            eprintln!("Skipping duplicate key: {k}");
-                        } else {
-                            target_dict.insert(k, v);
+                (target @ Value::Dictionary(_), merge_value @ Value::Dictionary(_))
+                | (target @ Value::Array(_), merge_value @ Value::Array(_)) => {
+                    // Existing keys are kept, as in Apple's PlistBuddy.
+                    let options = MergeOptions {
+                        conflict: Conflict::KeepExisting,
+                        deep: false,
+                        arrays: ArrayMerge::Append,
+                    };
+                    match xcbuild_plist::merge(target, merge_value, &options) {
+                        Ok(skipped) => {
+                            for key_path in skipped {
+                                eprintln!("Skipping duplicate key: {}", &key_path[1..]);
+                            }
+                            *mutated = true;
+                            true
+                        }
+                        Err(e) => {
+                            eprintln!("{e}");
+                            false
                         }
                     }
-                    *mutated = true;
-                    true
-                }
-                (Value::Array(target_arr), Value::Array(merge_arr)) => {
-                    for v in merge_arr {
-                        target_arr.push(v);
-                    }
-                    *mutated = true;
-                    true
                 }
                 (Value::Dictionary(_), Value::Array(_)) => {
                     eprintln!("Cannot merge array into dictionary");
@@ -518,6 +573,291 @@ fn process_command(
     }
 }
 
+const COMMANDS: &[&str] = &[
+    "Help", "Exit", "Save", "Revert", "Clear", "Print", "Set", "Add", "Copy", "Delete", "Merge",
+    "Import",
+];
+
+const TYPES: &[&str] = &[
+    "string", "dictionary", "array", "bool", "real", "integer", "date", "data",
+];
+
+/// Interactive history is kept in `~/.plistbuddy_history`.
+fn history_path() -> Option<std::path::PathBuf> {
+    env::var_os("HOME").map(|home| Path::new(&home).join(".plistbuddy_history"))
+}
+
+/// What an argument of a command is, for completion.
+enum Argument {
+    KeyPath,
+    Type,
+    File,
+    None,
+}
+
+fn argument_kind(command: &str, index: usize) -> Argument {
+    match (command.to_ascii_lowercase().as_str(), index) {
+        ("print" | "set" | "add" | "delete" | "copy" | "import", 1) => Argument::KeyPath,
+        ("copy" | "merge", 2) => Argument::KeyPath,
+        ("add", 2) | ("clear", 1) => Argument::Type,
+        ("merge", 1) | ("import", 2) => Argument::File,
+        _ => Argument::None,
+    }
+}
+
+/// Escape a completed word so that `tokenize` reads it back unchanged.
+fn escape_word(word: &str) -> String {
+    let mut escaped = String::with_capacity(word.len());
+    for c in word.chars() {
+        if c == '\\' || c == '"' || c == '\'' || c.is_whitespace() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Completes command names, types, file names and key paths into the
+/// document being edited.
+struct CommandHelper {
+    root: Rc<RefCell<Value>>,
+    files: FilenameCompleter,
+}
+
+impl CommandHelper {
+    fn new(root: Rc<RefCell<Value>>) -> Self {
+        CommandHelper {
+            root,
+            files: FilenameCompleter::new(),
+        }
+    }
+
+    /// Complete the last component of a key path against the keys or
+    /// indices of the container its prefix refers to.
+    fn complete_key_path(&self, word: &str) -> Vec<String> {
+        // Undo the command line quoting as `tokenize` does; the word may
+        // still be unterminated.
+        let mut path = String::new();
+        let mut chars = word.chars();
+        let mut in_double_quote = false;
+        let mut in_single_quote = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if !in_single_quote => match chars.next() {
+                    Some(':') => path.push_str("\\:"),
+                    next => path.extend(next),
+                },
+                '"' if !in_single_quote => in_double_quote = !in_double_quote,
+                '\'' if !in_double_quote => in_single_quote = !in_single_quote,
+                _ => path.push(c),
+            }
+        }
+
+        // Split at the last separator that isn't escaped.
+        let mut split = None;
+        let mut escaped = false;
+        for (i, c) in path.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                ':' => split = Some(i),
+                _ => {}
+            }
+        }
+        let (parent, partial) = match split {
+            Some(i) => (&path[..i], &path[i + 1..]),
+            None => ("", path.as_str()),
+        };
+        let partial = parse_key_path(partial).pop().unwrap_or_default();
+        let root = self.root.borrow();
+        let container = match get_at_key_path(&root, &parse_key_path(parent)) {
+            Some(v) => v,
+            None => return vec![],
+        };
+        let children: Vec<String> = match container {
+            Value::Dictionary(dict) => dict.keys().cloned().collect(),
+            Value::Array(arr) => (0..arr.len()).map(|i| i.to_string()).collect(),
+            _ => return vec![],
+        };
+
+        let prefix = if split.is_some() { format!("{parent}:") } else { String::new() };
+        children
+            .iter()
+            .filter(|key| key.starts_with(&partial))
+            .map(|key| escape_word(&format!("{prefix}{}", escape_key(key, ':'))))
+            .collect()
+    }
+}
+
+impl Completer for CommandHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        // Find the start of the word under the cursor, skipping escaped
+        // whitespace, and how many words precede it.
+        let before = &line[..pos];
+        let mut start = 0;
+        let mut words = Vec::new();
+        let mut escaped = false;
+        for (i, c) in before.char_indices() {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c.is_whitespace() {
+                if start < i {
+                    words.push(&before[start..i]);
+                }
+                start = i + c.len_utf8();
+            }
+        }
+        let word = &before[start..];
+
+        let candidates: Vec<String> = if words.is_empty() {
+            COMMANDS
+                .iter()
+                .filter(|c| c.to_ascii_lowercase().starts_with(&word.to_ascii_lowercase()))
+                .map(|c| c.to_string())
+                .collect()
+        } else {
+            match argument_kind(words[0], words.len()) {
+                Argument::KeyPath => self.complete_key_path(word),
+                Argument::Type => TYPES
+                    .iter()
+                    .filter(|t| t.starts_with(word))
+                    .map(|t| t.to_string())
+                    .collect(),
+                Argument::File => return self.files.complete(line, pos, ctx),
+                Argument::None => vec![],
+            }
+        };
+
+        let pairs = candidates
+            .into_iter()
+            .map(|c| Pair {
+                display: c.clone(),
+                replacement: c,
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for CommandHelper {
+    type Hint = String;
+}
+
+impl Highlighter for CommandHelper {}
+
+impl Validator for CommandHelper {}
+
+impl Helper for CommandHelper {}
+
+/// A batch command, with the file and line it came from when read from a
+/// script.
+struct ScriptLine {
+    location: Option<(String, usize)>,
+    text: String,
+}
+
+/// Read commands from a script file, or stdin for `-`.
+fn read_script(path: &str) -> Result<Vec<ScriptLine>> {
+    if path == "-" {
+        let mut text = String::new();
+        std::io::stdin()
+            .lock()
+            .read_to_string(&mut text)
+            .context("unable to read commands from stdin")?;
+        Ok(parse_script("<stdin>", &text))
+    } else {
+        let text = fs::read_to_string(path).with_context(|| format!("unable to read {path}"))?;
+        Ok(parse_script(path, &text))
+    }
+}
+
+/// Split a script into commands, numbering lines from 1. Blank lines and
+/// lines starting with `#` are skipped.
+fn parse_script(source: &str, text: &str) -> Vec<ScriptLine> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map(|(index, text)| ScriptLine {
+            location: Some((source.to_string(), index + 1)),
+            text: text.to_string(),
+        })
+        .collect()
+}
+
+/// The error reported for a failed script command, with its location.
+fn failure_message(command: &ScriptLine) -> Option<String> {
+    let (source, line) = command.location.as_ref()?;
+    Some(format!("{source}:{line}: command failed: {}", command.text.trim()))
+}
+
+/// Run batch commands, saving after each mutation, or only once every
+/// command has succeeded when aborting on errors. Returns whether every
+/// command succeeded; with `abort_on_error`, the first failure stops the
+/// batch and leaves `path` untouched.
+#[allow(clippy::too_many_arguments)]
+fn run_batch(
+    path: &str,
+    print_format: PlistFormat,
+    save_format: PlistFormat,
+    root: &mut Value,
+    last_saved: &mut Value,
+    commands: &[ScriptLine],
+    abort_on_error: bool,
+) -> bool {
+    let mut success = true;
+    let mut pending_save = false;
+    for command in commands {
+        let mut mutated = false;
+        let mut keep_reading = true;
+        let ok = process_command(
+            path,
+            print_format,
+            save_format,
+            root,
+            &command.text,
+            &mut mutated,
+            &mut keep_reading,
+            last_saved,
+            abort_on_error,
+        );
+        if !ok {
+            if let Some(message) = failure_message(command) {
+                eprintln!("{message}");
+            }
+            if abort_on_error {
+                eprintln!("Aborting, {path} was not modified");
+                return false;
+            }
+        }
+        success &= ok;
+        if abort_on_error {
+            pending_save |= mutated;
+        } else if mutated && !path.is_empty() {
+            save_plist(root, save_format, path);
+            *last_saved = root.clone();
+        }
+        if !keep_reading {
+            break;
+        }
+    }
+    if pending_save && !path.is_empty() {
+        success &= save_plist(root, save_format, path);
+    }
+    success
+}
+
 /// Check whether any component of the given path is a symlink.
 fn path_contains_symlink(path: &str) -> bool {
     let p = Path::new(path);
@@ -537,9 +877,10 @@ fn main() {
     let args: Vec<String> = env::args().skip(1).collect();
 
     let mut show_help = false;
-    let mut use_xml = false;
+    let mut print_format = PlistFormat::Ascii;
     let mut no_follow_symlinks = false;
-    let mut commands: Vec<String> = Vec::new();
+    let mut abort_on_error = false;
+    let mut commands: Vec<ScriptLine> = Vec::new();
     let mut input = String::new();
 
     let mut i = 0;
@@ -547,15 +888,33 @@ fn main() {
         let arg = &args[i];
         match arg.as_str() {
             "-h" => show_help = true,
-            "-x" => use_xml = true,
+            "-x" => print_format = PlistFormat::Xml,
+            "-j" => print_format = PlistFormat::Json,
             "-l" => no_follow_symlinks = true,
             "-c" => {
                 i += 1;
                 if i >= args.len() {
                     help(Some("missing value for -c"));
                 }
-                commands.push(args[i].clone());
+                commands.push(ScriptLine {
+                    location: None,
+                    text: args[i].clone(),
+                });
             }
+            "-f" => {
+                i += 1;
+                if i >= args.len() {
+                    help(Some("missing value for -f"));
+                }
+                match read_script(&args[i]) {
+                    Ok(lines) => commands.extend(lines),
+                    Err(e) => {
+                        eprintln!("Error: {e:#}");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "-a" => abort_on_error = true,
             _ => {
                 if arg.starts_with('-') {
                     help(Some(&format!("unknown argument {arg}")));
@@ -574,11 +933,9 @@ fn main() {
     let mut root: Value;
     let mut save_format = PlistFormat::Xml;
 
-    if no_follow_symlinks && !input.is_empty() {
-        if path_contains_symlink(&input) {
-            eprintln!("Error: path contains a symlink and -l was specified");
-            std::process::exit(1);
-        }
+    if no_follow_symlinks && !input.is_empty() && path_contains_symlink(&input) {
+        eprintln!("Error: path contains a symlink and -l was specified");
+        std::process::exit(1);
     }
 
     if !input.is_empty() && Path::new(&input).exists() {
@@ -602,29 +959,41 @@ fn main() {
     let mut last_saved = root.clone();
     let mut success = true;
 
-    if !commands.is_empty() {
-        // Batch mode: process each -c command, save after each mutation
-        for cmd in &commands {
-            let mut mutated = false;
-            let mut keep_reading = true;
-            success &= process_command(
-                &input,
-                use_xml,
-                save_format,
-                &mut root,
-                cmd,
-                &mut mutated,
-                &mut keep_reading,
-                &mut last_saved,
-            );
-            if mutated && !input.is_empty() {
-                save_plist(&root, save_format, &input);
-                last_saved = root.clone();
+    // Without -c or -f, commands piped on stdin are run as a script.
+    if commands.is_empty() && !std::io::stdin().is_terminal() {
+        match read_script("-") {
+            Ok(lines) => commands.extend(lines),
+            Err(e) => {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
             }
         }
+        if commands.is_empty() {
+            std::process::exit(0);
+        }
+    }
+
+    if !commands.is_empty() {
+        success = run_batch(
+            &input,
+            print_format,
+            save_format,
+            &mut root,
+            &mut last_saved,
+            &commands,
+            abort_on_error,
+        );
     } else {
         // Interactive mode
-        let mut editor = DefaultEditor::new().expect("failed to create editor");
+        // The completer reads the document the commands edit.
+        let root = Rc::new(RefCell::new(root));
+        let mut editor: Editor<CommandHelper, DefaultHistory> =
+            Editor::new().expect("failed to create editor");
+        editor.set_helper(Some(CommandHelper::new(Rc::clone(&root))));
+        let history = history_path();
+        if let Some(history) = &history {
+            let _ = editor.load_history(history);
+        }
         let mut keep_reading = true;
 
         while keep_reading {
@@ -634,13 +1003,14 @@ fn main() {
                     let mut mutated = false;
                     success &= process_command(
                         &input,
-                        use_xml,
+                        print_format,
                         save_format,
-                        &mut root,
+                        &mut root.borrow_mut(),
                         &line,
                         &mut mutated,
                         &mut keep_reading,
                         &mut last_saved,
+                        false,
                     );
                 }
                 Err(_) => {
@@ -648,7 +1018,139 @@ fn main() {
                 }
             }
         }
+        if let Some(history) = &history {
+            let _ = editor.save_history(history);
+        }
     }
 
     std::process::exit(if success { 0 } else { 1 });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Value {
+        deserialize_with_format(text.as_bytes(), PlistFormat::Ascii).unwrap()
+    }
+
+    fn run(root: &mut Value, command: &str) -> bool {
+        let mut mutated = false;
+        let mut keep_reading = true;
+        let mut last_saved = root.clone();
+        process_command(
+            "unused.plist",
+            PlistFormat::Xml,
+            PlistFormat::Xml,
+            root,
+            command,
+            &mut mutated,
+            &mut keep_reading,
+            &mut last_saved,
+            true,
+        )
+    }
+
+    #[test]
+    fn test_escaped_separator() {
+        assert_eq!(tokenize(r#"Add :a\:b string "v\"w""#).unwrap(), vec!["Add", ":a\\:b", "string", "v\"w"]);
+        assert_eq!(tokenize(r"Print ':a\:b'").unwrap(), vec!["Print", ":a\\:b"]);
+        assert!(tokenize(r"Print :a\").is_err());
+
+        let mut root = parse("{ }");
+        assert!(run(&mut root, r"Add :a\:b string v"));
+        assert!(run(&mut root, r#"Add ":c\:d" string w"#));
+        assert_eq!(root, parse(r#"{ "a:b" = v; "c:d" = w; }"#));
+    }
+
+    #[test]
+    fn test_complete_escaped_keys() {
+        let helper = CommandHelper::new(Rc::new(RefCell::new(parse(r#"{ "a:b" = { "c d" = 1; cx = 2; }; }"#))));
+        assert_eq!(helper.complete_key_path(":a"), vec![r":a\\:b"]);
+        assert_eq!(helper.complete_key_path(r":a\\:b:c"), vec![r":a\\:b:c\ d", r":a\\:b:cx"]);
+        assert_eq!(helper.complete_key_path(r":a\:b:c"), vec![r":a\\:b:c\ d", r":a\\:b:cx"]);
+        assert_eq!(helper.complete_key_path(r"':a\:b:cx'"), vec![r":a\\:b:cx"]);
+
+        let mut root = parse("{ }");
+        assert!(run(&mut root, r"Add :a\\:b string v"));
+        assert_eq!(root, parse(r#"{ "a:b" = v; }"#));
+    }
+
+    #[test]
+    fn test_merge_skips_duplicate_keys() {
+        let path = env::temp_dir().join(format!("PlistBuddy-merge-{}.plist", std::process::id()));
+        fs::write(&path, "{ list = (r); name = new; extra = 1; }").unwrap();
+        let mut root = parse("{ list = (p); name = old; }");
+        let merged = run(&mut root, &format!("Merge '{}'", path.display()));
+        fs::remove_file(&path).unwrap();
+        assert!(merged);
+        assert_eq!(root, parse("{ list = (p); name = old; extra = 1; }"));
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let commands = parse_script("cmds", "Add :a string x\n\n# comment\n  \nDelete :b\n");
+        let lines: Vec<_> = commands.iter().map(|c| (c.location.clone().unwrap().1, c.text.as_str())).collect();
+        assert_eq!(lines, vec![(1, "Add :a string x"), (5, "Delete :b")]);
+        assert_eq!(failure_message(&commands[1]).unwrap(), "cmds:5: command failed: Delete :b");
+        assert_eq!(failure_message(&ScriptLine { location: None, text: "Print".to_string() }), None);
+    }
+
+    #[test]
+    fn test_abort_leaves_file_unchanged() {
+        let path = env::temp_dir().join(format!("PlistBuddy-abort-{}.plist", std::process::id()));
+        let original = "{ a = 1; }";
+        fs::write(&path, original).unwrap();
+        let input = path.to_str().unwrap();
+        let commands = parse_script("cmds", "Set :a 2\nDelete :missing\nSet :a 3\n");
+
+        let mut root = parse(original);
+        let mut last_saved = root.clone();
+        let ok = run_batch(input, PlistFormat::Xml, PlistFormat::Ascii, &mut root, &mut last_saved, &commands, true);
+        assert!(!ok);
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+
+        // Without -a, earlier mutations are saved and later commands still run.
+        let mut root = parse(original);
+        let mut last_saved = root.clone();
+        let ok = run_batch(input, PlistFormat::Xml, PlistFormat::Ascii, &mut root, &mut last_saved, &commands, false);
+        let saved = load_plist(input).unwrap().0;
+        fs::remove_file(&path).unwrap();
+        assert!(!ok);
+        assert_eq!(saved, parse("{ a = 3; }"));
+    }
+
+    #[test]
+    fn test_write_atomically() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = env::temp_dir().join(format!("PlistBuddy-atomic-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("target.plist");
+        let link = dir.join("link.plist");
+        fs::write(&target, "old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        symlink(&target, &link).unwrap();
+
+        // Writing through a symlink replaces the target and keeps the link.
+        write_atomically(&link, b"new").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert_eq!(fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o600);
+
+        // A symlink planted at the temp path is neither followed nor removed.
+        let victim = dir.join("victim");
+        fs::write(&victim, "keep").unwrap();
+        let temp = dir.join(format!(".target.plist.{}.tmp", std::process::id()));
+        symlink(&victim, &temp).unwrap();
+        assert!(write_atomically(&target, b"newer").is_err());
+        let victim_contents = fs::read_to_string(&victim).unwrap();
+        let temp_is_link = fs::symlink_metadata(&temp).unwrap().file_type().is_symlink();
+        let target_contents = fs::read_to_string(&target).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(victim_contents, "keep");
+        assert!(temp_is_link);
+        assert_eq!(target_contents, "new");
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::fs;
+use std::path::Path;
+use xcbuild_xar::{Xar, XarFileType};
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    // dump_xar [--toc] [-x directory] file
+    let mut print_toc = false;
+    let mut extract: Option<String> = None;
+    let mut inputs: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--toc" => print_toc = true,
+            "-x" => {
+                i += 1;
+                extract = args.get(i).cloned();
+                if extract.is_none() {
+                    eprintln!("error: missing argument for -x");
+                    std::process::exit(1);
+                }
+            }
+            _ => inputs.push(args[i].clone()),
+        }
+        i += 1;
+    }
+
+    if inputs.len() != 1 {
+        eprintln!("error: missing argument");
+        std::process::exit(1);
+    }
+
+    let path = &inputs[0];
+    let data = fs::read(path).with_context(|| format!("failed to read {path}"))?;
+
+    let xar = Xar::load(data).with_context(|| "failed to load xar archive")?;
+
+    if print_toc {
+        print!("{}", xar.toc());
+        return Ok(());
+    }
+
+    if let Some(destination) = extract {
+        xar.extract(Path::new(&destination))
+            .with_context(|| format!("failed to extract to {destination}"))?;
+        return Ok(());
+    }
+
+    let header = xar.header();
+    println!("header:");
+    println!("\tsize: {}", header.header_size);
+    println!("\tversion: {}", header.version);
+    println!("\ttoc compressed length: {}", header.toc_length_compressed);
+    println!("\ttoc uncompressed length: {}", header.toc_length_uncompressed);
+    println!("\tchecksum: {}", header.checksum.name());
+    if let Some((offset, size)) = xar.toc_checksum() {
+        println!("\ttoc checksum: heap offset {offset:x} ({size:x} bytes)");
+    }
+    println!();
+
+    println!("files:");
+    for file in xar.files() {
+        let kind = match file.file_type {
+            XarFileType::File => "file",
+            XarFileType::Directory => "directory",
+            XarFileType::Symlink => "symlink",
+            XarFileType::Hardlink => "hardlink",
+            XarFileType::Other => "other",
+        };
+        print!("\t{}: {} ({kind}", file.id, file.path);
+        if let Some(mode) = file.mode {
+            print!(", mode {mode:04o}");
+        }
+        if let Some(link) = &file.link {
+            print!(", link to {link}");
+        }
+        println!(")");
+
+        if let Some(data) = &file.data {
+            println!(
+                "\t\tdata at heap offset {:x}: {:x} bytes archived, {:x} bytes extracted, {}",
+                data.offset,
+                data.length,
+                data.size,
+                data.encoding.style()
+            );
+            let status = match xar.read(file) {
+                Ok(_) => "ok".to_string(),
+                Err(e) => e.to_string(),
+            };
+            println!("\t\tchecksum: {status}");
+        }
+    }
+
+    Ok(())
+}
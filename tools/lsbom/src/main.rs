@@ -4,7 +4,8 @@ use std::env;
 use std::fmt::Write as _;
 use std::fs;
 use std::io::{self, Write};
-use xcbuild_bom::paths::{FileKey, PathInfo1, PathInfo2, PathType};
+use serde_json::{json, Map, Value};
+use xcbuild_bom::paths::{self, ArchInfo, FileKey, PathInfo1, PathInfo2, PathType};
 use xcbuild_bom::Bom;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -38,9 +39,22 @@ struct Options {
     no_modes: bool,
     print_format: Option<Vec<PrintItem>>,
     arch: Option<String>,
+    json: bool,
     inputs: Vec<String>,
 }
 
+/// One path to list, with the values that depend on the BOM's side trees
+/// and the selected architecture already resolved.
+struct Listing {
+    path: String,
+    info: PathInfo2,
+    /// Full size, from `Size64` when the 32-bit field overflowed.
+    size: u64,
+    checksum: u32,
+    /// The first path of this file's hard link group, if it is not this one.
+    hard_link: Option<String>,
+}
+
 /// Resolves UIDs to user names and GIDs to group names by reading /etc/passwd and /etc/group.
 struct NameLookup {
     users: HashMap<u32, String>,
@@ -148,7 +162,8 @@ fn help(error: Option<&str>) -> ! {
     eprintln!("  -m\t  print modification times");
     eprintln!("  -s\t  print only paths");
     eprintln!("  -x\t  print no modes");
-    eprintln!("  --arch [arch]");
+    eprintln!("  --arch [arch]  list only files containing arch, with its size and checksum");
+    eprintln!("  --json\t  print entries as a JSON array (an object of arrays keyed by input for several inputs)");
     eprintln!("  -p [flags]\n");
     eprintln!("Print flags:");
     eprintln!("  c\t  print checksum");
@@ -189,6 +204,7 @@ fn parse_options(args: &[String]) -> Options {
         no_modes: false,
         print_format: None,
         arch: None,
+        json: false,
         inputs: Vec::new(),
     };
 
@@ -198,6 +214,8 @@ fn parse_options(args: &[String]) -> Options {
 
         if arg == "--help" {
             opts.help = true;
+        } else if arg == "--json" {
+            opts.json = true;
         } else if arg == "--arch" {
             i += 1;
             if i < args.len() {
@@ -313,7 +331,7 @@ fn format_permissions_text(mode: u16, path_type: PathType) -> String {
     s
 }
 
-fn format_size_human(size: u32) -> String {
+fn format_size_human(size: u64) -> String {
     if size < 1024 {
         format!("{size}")
     } else if size < 1024 * 1024 {
@@ -366,12 +384,9 @@ fn write_str(output: &str) {
     }
 }
 
-fn print_entry(
-    path: &str,
-    path_info_2: &PathInfo2,
-    format: &[PrintItem],
-    name_lookup: &NameLookup,
-) {
+fn print_entry(listing: &Listing, format: &[PrintItem], name_lookup: &NameLookup) {
+    let path = listing.path.as_str();
+    let path_info_2 = &listing.info;
     let mut output = String::new();
     let pt = path_info_2.path_type();
     let is_file_or_link = pt == PathType::File || pt == PathType::Link;
@@ -395,7 +410,7 @@ fn print_entry(
             }
             PrintItem::Checksum => {
                 if is_file_or_link {
-                    let _ = write!(output, "{}", path_info_2.checksum);
+                    let _ = write!(output, "{}", listing.checksum);
                 }
             }
             PrintItem::GroupID => {
@@ -415,12 +430,12 @@ fn print_entry(
             }
             PrintItem::FileSize => {
                 if is_file_or_link {
-                    let _ = write!(output, "{}", path_info_2.size);
+                    let _ = write!(output, "{}", listing.size);
                 }
             }
             PrintItem::FileSizeFormatted => {
                 if is_file_or_link {
-                    output.push_str(&format_size_human(path_info_2.size));
+                    output.push_str(&format_size_human(listing.size));
                 }
             }
             PrintItem::ModificationTime => {
@@ -455,135 +470,366 @@ fn print_entry(
     write_line(&output);
 }
 
-fn arch_to_cpu_type(arch: &str) -> Option<u16> {
-    // BOM stores architecture as a u16, using compact Mach-O CPU type values.
-    // The full Mach-O constants are 32-bit but BOM truncates to u16.
+/// Build the JSON value for one entry: the fields named by `-p`, just the
+/// path for `-s`, or every field lsbom knows about.
+fn json_entry(listing: &Listing, options: &Options, name_lookup: &NameLookup) -> Value {
+    let info = &listing.info;
+    let pt = info.path_type();
+    let is_file_or_link = pt == PathType::File || pt == PathType::Link;
+    let mut object = Map::new();
+
+    let Some(format) = options.print_format.as_deref() else {
+        if options.only_path {
+            return json!(listing.path);
+        }
+        object.insert("path".into(), json!(listing.path));
+        let type_name = match pt {
+            PathType::File => "file",
+            PathType::Directory => "directory",
+            PathType::Link => "link",
+            PathType::Device => "device",
+        };
+        object.insert("type".into(), json!(type_name));
+        // As in the text listing, -x drops modes of directories and links.
+        if !(options.no_modes && matches!(pt, PathType::Directory | PathType::Link)) {
+            object.insert("mode".into(), json!(format!("{:o}", info.mode)));
+            object.insert("uid".into(), json!(info.user));
+            object.insert("gid".into(), json!(info.group));
+        }
+        object.insert("mtime".into(), json!(info.modtime));
+        if is_file_or_link {
+            object.insert("size".into(), json!(listing.size));
+            object.insert("checksum".into(), json!(listing.checksum));
+        }
+        match pt {
+            PathType::Link => {
+                object.insert("linkTarget".into(), json!(info.link_name));
+            }
+            PathType::Device => {
+                object.insert("device".into(), json!(info.size));
+            }
+            _ => {}
+        }
+        if !info.binary_info.is_empty() {
+            let architectures: Vec<Value> = info
+                .binary_info
+                .iter()
+                .map(|arch| {
+                    json!({
+                        "cpuType": arch.cpu_type,
+                        "cpuSubtype": arch.cpu_subtype,
+                        "size": arch.size,
+                        "checksum": arch.checksum,
+                    })
+                })
+                .collect();
+            object.insert("architectures".into(), Value::Array(architectures));
+        }
+        if let Some(hard_link) = &listing.hard_link {
+            object.insert("hardLink".into(), json!(hard_link));
+        }
+        return Value::Object(object);
+    };
+
+    for item in format {
+        match item {
+            PrintItem::FileName | PrintItem::FileNameQuoted => {
+                object.insert("path".into(), json!(listing.path));
+            }
+            PrintItem::Checksum => {
+                if is_file_or_link {
+                    object.insert("checksum".into(), json!(listing.checksum));
+                }
+            }
+            PrintItem::GroupID => {
+                object.insert("gid".into(), json!(info.group));
+            }
+            PrintItem::GroupName => {
+                object.insert("group".into(), json!(name_lookup.resolve_group(info.group)));
+            }
+            PrintItem::Permissions => {
+                object.insert("mode".into(), json!(format!("{:o}", info.mode)));
+            }
+            PrintItem::PermissionsText => {
+                object.insert("permissions".into(), json!(format_permissions_text(info.mode, pt)));
+            }
+            PrintItem::FileSize => {
+                if is_file_or_link {
+                    object.insert("size".into(), json!(listing.size));
+                }
+            }
+            PrintItem::FileSizeFormatted => {
+                if is_file_or_link {
+                    object.insert("sizeFormatted".into(), json!(format_size_human(listing.size)));
+                }
+            }
+            PrintItem::ModificationTime => {
+                if is_file_or_link {
+                    object.insert("mtime".into(), json!(info.modtime));
+                }
+            }
+            PrintItem::ModificationTimeFormatted => {
+                if is_file_or_link {
+                    object.insert("mtimeFormatted".into(), json!(format_timestamp(info.modtime)));
+                }
+            }
+            PrintItem::UserID => {
+                object.insert("uid".into(), json!(info.user));
+            }
+            PrintItem::UserName => {
+                object.insert("user".into(), json!(name_lookup.resolve_user(info.user)));
+            }
+            PrintItem::UserGroupID => {
+                object.insert("uid".into(), json!(info.user));
+                object.insert("gid".into(), json!(info.group));
+            }
+            PrintItem::UserGroupName => {
+                object.insert("user".into(), json!(name_lookup.resolve_user(info.user)));
+                object.insert("group".into(), json!(name_lookup.resolve_group(info.group)));
+            }
+        }
+    }
+    Value::Object(object)
+}
+
+fn arch_to_cpu_type(arch: &str) -> Option<u32> {
+    // Full Mach-O CPU types, as recorded for each slice of a fat binary.
+    // Thin binaries only store the low 16 bits in PathInfo2::architecture.
     match arch {
         "i386" => Some(0x07),
-        "x86_64" => Some(0x07), // 0x01000007 truncated
+        "x86_64" => Some(0x0100_0007),
         "arm" | "armv7" | "armv7s" | "armv7k" => Some(0x0C),
-        "arm64" | "arm64e" => Some(0x0C), // 0x0100000C truncated
+        "arm64" | "arm64e" => Some(0x0100_000C),
+        "arm64_32" => Some(0x0200_000C),
         "ppc" => Some(0x12),
-        "ppc64" => Some(0x12), // 0x01000012 truncated
+        "ppc64" => Some(0x0100_0012),
         _ => None,
     }
 }
 
-fn main() -> Result<()> {
-    let args: Vec<String> = env::args().skip(1).collect();
-    let options = parse_options(&args);
-
-    if options.help {
-        help(None);
+/// Pick the slice of a Mach-O file for `cpu_type`. Returns `None` if the
+/// file doesn't contain that architecture, the slice of a fat file, and the
+/// file itself otherwise.
+fn select_arch(info: &PathInfo2, cpu_type: u32) -> Option<Option<ArchInfo>> {
+    if !info.binary_info.is_empty() {
+        let arch = info.binary_info.iter().find(|arch| arch.cpu_type == cpu_type)?;
+        return Some((info.architecture == paths::ARCH_FAT).then_some(*arch));
     }
-
-    if options.inputs.is_empty() {
-        help(Some("input is required"));
+    // Without binary_info only the low 16 bits of a thin binary's CPU type
+    // are known, which can't tell 32- and 64-bit variants apart.
+    if info.architecture != 0
+        && info.architecture != paths::ARCH_FAT
+        && info.architecture != cpu_type as u16
+    {
+        return None;
     }
+    Some(None)
+}
 
+/// Collect the entries of a BOM that pass the type and architecture
+/// filters, sorted by path.
+fn list_bom(bom: &Bom, options: &Options, arch_cpu_type: Option<u32>) -> Result<Vec<Listing>> {
     let include_all = !options.include_block_devices
         && !options.include_character_devices
         && !options.include_directories
         && !options.include_files
         && !options.include_symbolic_links;
 
-    // Build name lookup for user/group resolution
-    let name_lookup = NameLookup::new();
-
-    for input in &options.inputs {
-        let data = fs::read(input).with_context(|| format!("failed to read {input}"))?;
-        let bom = Bom::load(data).with_context(|| "failed to load BOM")?;
+    let entries = bom
+        .tree_entries("Paths")
+        .with_context(|| "failed to load paths tree")?;
+
+    // Sizes of files over 4 GiB, by ID. Older BOMs have no such tree.
+    let mut size64: HashMap<u32, u64> = HashMap::new();
+    if let Ok(tree) = bom.tree("Size64") {
+        for entry in tree.iter() {
+            let (key, value) = entry.with_context(|| "failed to read Size64 tree")?;
+            if let Some((id, size)) = paths::parse_size64(key, value) {
+                size64.insert(id, size);
+            }
+        }
+    }
 
-        let entries = bom
-            .tree_entries("Paths")
-            .with_context(|| "failed to load paths tree")?;
-
-        // Map from id -> (parent, name)
-        let mut files: HashMap<u32, (u32, String)> = HashMap::new();
-
-        // Collect all entries into a vec for sorting
-        let mut collected: Vec<(String, PathInfo2)> = Vec::new();
-
-        for entry in &entries {
-            let file_key = match FileKey::from_bytes(&entry.key) {
-                Some(fk) => fk,
-                None => continue,
-            };
-            let path_info_1 = match PathInfo1::from_bytes(&entry.value) {
-                Some(pi) => pi,
-                None => continue,
-            };
-
-            files.insert(path_info_1.id, (file_key.parent, file_key.name.clone()));
-
-            // Get secondary path info
-            let path_info_2_data = match bom.index_get(path_info_1.index) {
-                Some(d) => d,
-                None => {
-                    eprintln!("error: failed to get secondary path info");
-                    continue;
+    // Map from the ID of each hard link to the first ID of its group
+    let mut hard_links: HashMap<u32, u32> = HashMap::new();
+    if let Ok(tree) = bom.tree("HLIndex") {
+        for entry in tree.iter() {
+            let (_, value) = entry.with_context(|| "failed to read HLIndex tree")?;
+            let ids = paths::parse_hard_link(value);
+            if let Some((&first, rest)) = ids.split_first() {
+                for &id in rest {
+                    hard_links.insert(id, first);
                 }
-            };
+            }
+        }
+    }
 
-            let path_info_2 = match PathInfo2::from_bytes(path_info_2_data) {
-                Some(pi) => pi,
-                None => continue,
-            };
+    // Map from id -> (parent, name)
+    let mut files: HashMap<u32, (u32, String)> = HashMap::new();
 
-            // Filter by type
-            if !include_all {
-                match path_info_2.path_type() {
-                    PathType::File => {
-                        if !options.include_files {
-                            continue;
-                        }
+    // Collect all entries into a vec for sorting, with their IDs
+    let mut collected: Vec<(u32, Listing)> = Vec::new();
+
+    for entry in &entries {
+        let file_key = match FileKey::from_bytes(&entry.key) {
+            Some(fk) => fk,
+            None => continue,
+        };
+        let path_info_1 = match PathInfo1::from_bytes(&entry.value) {
+            Some(pi) => pi,
+            None => continue,
+        };
+
+        files.insert(path_info_1.id, (file_key.parent, file_key.name.clone()));
+
+        // Get secondary path info
+        let path_info_2_data = match bom.index_get(path_info_1.index) {
+            Some(d) => d,
+            None => {
+                eprintln!("error: failed to get secondary path info");
+                continue;
+            }
+        };
+
+        let path_info_2 = match PathInfo2::from_bytes(path_info_2_data) {
+            Some(pi) => pi,
+            None => continue,
+        };
+
+        // Filter by type
+        if !include_all {
+            match path_info_2.path_type() {
+                PathType::File => {
+                    if !options.include_files {
+                        continue;
                     }
-                    PathType::Directory => {
-                        if !options.include_directories {
-                            continue;
-                        }
+                }
+                PathType::Directory => {
+                    if !options.include_directories {
+                        continue;
                     }
-                    PathType::Link => {
-                        if !options.include_symbolic_links {
-                            continue;
-                        }
+                }
+                PathType::Link => {
+                    if !options.include_symbolic_links {
+                        continue;
                     }
-                    PathType::Device => {
-                        if path_info_2.mode & 0x4000 != 0 {
-                            if !options.include_block_devices {
-                                continue;
-                            }
-                        } else if !options.include_character_devices {
+                }
+                PathType::Device => {
+                    if path_info_2.mode & 0x4000 != 0 {
+                        if !options.include_block_devices {
                             continue;
                         }
+                    } else if !options.include_character_devices {
+                        continue;
                     }
                 }
             }
+        }
 
-            // Filter by architecture
-            if let Some(ref arch_name) = options.arch {
-                if path_info_2.path_type() == PathType::File {
-                    if let Some(cpu_type) = arch_to_cpu_type(arch_name) {
-                        if path_info_2.architecture != 0 && path_info_2.architecture != cpu_type {
-                            continue;
-                        }
+        let mut size = size64
+            .get(&path_info_1.id)
+            .copied()
+            .unwrap_or(path_info_2.size as u64);
+        let mut checksum = path_info_2.checksum;
+
+        // Filter by architecture, listing only the selected slice of fat files
+        if let Some(cpu_type) = arch_cpu_type {
+            if path_info_2.path_type() == PathType::File {
+                match select_arch(&path_info_2, cpu_type) {
+                    None => continue,
+                    Some(Some(arch)) => {
+                        size = arch.size as u64;
+                        checksum = arch.checksum;
                     }
+                    Some(None) => {}
                 }
             }
+        }
 
-            // Build full path
-            let path = xcbuild_bom::paths::resolve_path(&file_key, &files);
+        // Build full path
+        let path = paths::resolve_path(&file_key, &files);
 
-            collected.push((path, path_info_2));
+        let listing = Listing {
+            path,
+            info: path_info_2,
+            size,
+            checksum,
+            hard_link: None,
+        };
+        collected.push((path_info_1.id, listing));
+    }
+
+    // Resolve hard links to the path of the first link in their group,
+    // which may itself have been filtered out.
+    let listed: HashMap<u32, String> = collected
+        .iter()
+        .map(|(id, listing)| (*id, listing.path.clone()))
+        .collect();
+    for (id, listing) in &mut collected {
+        if let Some(first) = hard_links.get(id) {
+            listing.hard_link = listed.get(first).cloned().or_else(|| {
+                let (parent, name) = files.get(first)?;
+                let key = FileKey {
+                    parent: *parent,
+                    name: name.clone(),
+                };
+                Some(paths::resolve_path(&key, &files))
+            });
         }
+    }
+
+    // Sort entries alphabetically by path
+    let mut collected: Vec<Listing> = collected.into_iter().map(|(_, listing)| listing).collect();
+    collected.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(collected)
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = parse_options(&args);
+
+    if options.help {
+        help(None);
+    }
 
-        // Sort entries alphabetically by path
-        collected.sort_by(|a, b| a.0.cmp(&b.0));
+    if options.inputs.is_empty() {
+        help(Some("input is required"));
+    }
+
+    let arch_cpu_type = match &options.arch {
+        Some(arch) => match arch_to_cpu_type(arch) {
+            Some(cpu_type) => Some(cpu_type),
+            None => help(Some(&format!("unknown architecture {arch}"))),
+        },
+        None => None,
+    };
+
+    // Build name lookup for user/group resolution
+    let name_lookup = NameLookup::new();
+    // JSON listings of each input, printed as one document at the end.
+    let mut json_listings = Map::new();
+
+    for input in &options.inputs {
+        let data = fs::read(input).with_context(|| format!("failed to read {input}"))?;
+        let bom = Bom::load(data).with_context(|| "failed to load BOM")?;
+
+        let collected = list_bom(&bom, &options, arch_cpu_type)?;
+
+        if options.json {
+            let entries: Vec<Value> = collected
+                .iter()
+                .map(|listing| json_entry(listing, &options, &name_lookup))
+                .collect();
+            json_listings.insert(input.clone(), Value::Array(entries));
+            continue;
+        }
 
         // Print sorted entries
-        for (path, path_info_2) in &collected {
+        for listing in &collected {
+            let path = &listing.path;
+            let path_info_2 = &listing.info;
             if let Some(ref format) = options.print_format {
-                print_entry(path, path_info_2, format, &name_lookup);
+                print_entry(listing, format, &name_lookup);
             } else if options.only_path {
                 write_line(path);
             } else {
@@ -604,13 +850,13 @@ fn main() -> Result<()> {
 
                 match pt {
                     PathType::File => {
-                        let size = path_info_2.size.to_string();
-                        let checksum = path_info_2.checksum.to_string();
+                        let size = listing.size.to_string();
+                        let checksum = listing.checksum.to_string();
                         write_str(&format!("\t{size}\t{checksum}"));
                     }
                     PathType::Link => {
-                        let size = path_info_2.size.to_string();
-                        let checksum = path_info_2.checksum.to_string();
+                        let size = listing.size.to_string();
+                        let checksum = listing.checksum.to_string();
                         let link_target = &path_info_2.link_name;
                         write_str(&format!("\t{size}\t{checksum}\t{link_target}"));
                     }
@@ -630,5 +876,135 @@ fn main() -> Result<()> {
         }
     }
 
+    // One input gives an array of entries; several give an object of
+    // arrays keyed by input path.
+    if options.json {
+        let document = match options.inputs.len() {
+            1 => json_listings.into_iter().next().map(|(_, entries)| entries).unwrap_or_default(),
+            _ => Value::Object(json_listings),
+        };
+        write_line(&serde_json::to_string_pretty(&document)?);
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xcbuild_bom::paths::PathEntry;
+    use xcbuild_bom::{BomWriter, DEFAULT_BLOCK_SIZE};
+
+    const X86_64: ArchInfo = ArchInfo { cpu_type: 0x0100_0007, cpu_subtype: 3, size: 100, checksum: 11 };
+    const ARM64: ArchInfo = ArchInfo { cpu_type: 0x0100_000C, cpu_subtype: 0, size: 200, checksum: 22 };
+
+    fn entry(path: &str, path_type: u8, size: u64, architecture: u16, binary_info: &[ArchInfo]) -> PathEntry {
+        PathEntry {
+            path: path.to_string(),
+            info: PathInfo2 {
+                path_type,
+                architecture,
+                mode: if path_type == 2 { 0o40755 } else { 0o100644 },
+                user: 0,
+                group: 0,
+                modtime: 0,
+                size: u32::try_from(size).unwrap_or(u32::MAX),
+                checksum: 33,
+                link_name: String::new(),
+                binary_info: binary_info.to_vec(),
+            },
+            size,
+        }
+    }
+
+    /// A BOM with a fat binary, thin binaries with and without slice info,
+    /// a file over 4 GiB and the hard-link pair `./a` and `./b`.
+    fn test_bom() -> Bom {
+        let mut writer = BomWriter::new();
+        writer.add_paths(&[
+            entry(".", 2, 0, 0, &[]),
+            entry("./bin", 2, 0, 0, &[]),
+            entry("./bin/fat", 1, 316, paths::ARCH_FAT, &[X86_64, ARM64]),
+            entry("./bin/thin", 1, 200, 0x000C, &[ARM64]),
+            entry("./bin/old", 1, 200, 0x000C, &[]),
+            entry("./big", 1, 5 << 30, 0, &[]),
+            entry("./a", 1, 1, 0, &[]),
+            entry("./b", 1, 1, 0, &[]),
+        ]);
+        writer
+            .replace_tree("HLIndex", &[paths::hard_link_entry(&[7, 8])], DEFAULT_BLOCK_SIZE, |a, b| a.cmp(b))
+            .unwrap();
+        Bom::load(writer.serialize()).unwrap()
+    }
+
+    fn list(args: &[&str]) -> Vec<Listing> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let options = parse_options(&args);
+        let cpu_type = options.arch.as_deref().map(|arch| arch_to_cpu_type(arch).unwrap());
+        list_bom(&test_bom(), &options, cpu_type).unwrap()
+    }
+
+    fn summary(listings: &[Listing]) -> Vec<(&str, u64, u32)> {
+        listings.iter().map(|l| (l.path.as_str(), l.size, l.checksum)).collect()
+    }
+
+    #[test]
+    fn test_sizes_and_hard_links() {
+        let listings = list(&["-f"]);
+        assert_eq!(
+            summary(&listings),
+            vec![
+                ("./a", 1, 33),
+                ("./b", 1, 33),
+                ("./big", 5 << 30, 33),
+                ("./bin/fat", 316, 33),
+                ("./bin/old", 200, 33),
+                ("./bin/thin", 200, 33),
+            ]
+        );
+        let hard_links: Vec<_> = listings.iter().map(|l| l.hard_link.as_deref()).collect();
+        assert_eq!(hard_links, vec![None, Some("./a"), None, None, None, None]);
+    }
+
+    #[test]
+    fn test_select_arch() {
+        // Fat files list the selected slice; thin files with slice info
+        // match on the full CPU type, and those without on its low bits.
+        assert_eq!(
+            summary(&list(&["-f", "--arch", "arm64"]))[3..],
+            [("./bin/fat", 200, 22), ("./bin/old", 200, 33), ("./bin/thin", 200, 33)]
+        );
+        assert_eq!(summary(&list(&["-f", "--arch=x86_64"]))[3..], [("./bin/fat", 100, 11)]);
+        assert_eq!(summary(&list(&["-f", "--arch", "armv7"]))[3..], [("./bin/old", 200, 33)]);
+        assert!(list(&["--arch", "i386"]).iter().any(|l| l.path == "./bin"));
+    }
+
+    #[test]
+    fn test_json_entry() {
+        let json = |args: &[&str], path: &str| {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let options = parse_options(&args);
+            let listings = list_bom(&test_bom(), &options, None).unwrap();
+            let listing = listings.iter().find(|l| l.path == path).unwrap();
+            json_entry(listing, &options, &NameLookup::new())
+        };
+
+        let big = json(&[], "./big");
+        assert_eq!(big["size"], json!(5u64 << 30));
+        assert_eq!(json(&[], "./b")["hardLink"], json!("./a"));
+        assert_eq!(
+            json(&[], "./bin/fat")["architectures"],
+            json!([
+                { "cpuType": 0x0100_0007, "cpuSubtype": 3, "size": 100, "checksum": 11 },
+                { "cpuType": 0x0100_000C, "cpuSubtype": 0, "size": 200, "checksum": 22 },
+            ])
+        );
+
+        assert_eq!(json(&["-s"], "./big"), json!("./big"));
+        assert_eq!(json(&["-x"], "./bin").get("mode"), None);
+        assert_eq!(json(&["-x"], "./big")["mode"], json!("100644"));
+        // -p names the fields, whether or not -s is given.
+        assert_eq!(json(&["-s", "-p", "fs"], "./big"), json!({ "path": "./big", "size": 5u64 << 30 }));
+        assert_eq!(json(&["-pfs"], "./bin"), json!({ "path": "./bin" }));
+    }
+}
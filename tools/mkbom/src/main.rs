@@ -1,10 +1,10 @@
 use anyhow::{bail, Context, Result};
-use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::io::{self, BufRead};
 use std::os::unix::fs::MetadataExt;
-use xcbuild_bom::paths::{FileKey, PathInfo1, PathInfo2};
+use xcbuild_bom::files::crc32_of_file;
+use xcbuild_bom::paths::{PathEntry, PathInfo2};
 use xcbuild_bom::BomWriter;
 
 fn help(error: Option<&str>) -> ! {
@@ -12,11 +12,14 @@ fn help(error: Option<&str>) -> ! {
         eprintln!("error: {e}\n");
     }
 
-    eprintln!("Usage: mkbom [-s] [-i filelist] directory bom");
+    eprintln!("Usage: mkbom [-s] [-u uid] [-g gid] [-i filelist] directory bom");
     eprintln!();
     eprintln!("Options:");
     eprintln!("  -s          create a simplified BOM with only paths");
     eprintln!("  -i filelist read entries from lsbom output instead of scanning a directory");
+    eprintln!("  -u uid      record every path as owned by uid");
+    eprintln!("  -g gid      record every path as owned by gid");
+    eprintln!("  --reproducible  record zero modification times");
     eprintln!("  -h, --help  show this help");
 
     std::process::exit(if error.is_some() { 1 } else { 0 });
@@ -28,31 +31,11 @@ struct Entry {
     mode: u16,
     user: u32,
     group: u32,
-    size: u32,
+    size: u64,
     checksum: u32,
     modtime: u32,
 }
 
-fn crc32_of_file(path: &std::path::Path) -> u32 {
-    let data = match fs::read(path) {
-        Ok(d) => d,
-        Err(_) => return 0,
-    };
-    // Simple CRC32 (IEEE/ISO 3309)
-    let mut crc: u32 = 0xFFFFFFFF;
-    for &byte in &data {
-        crc ^= byte as u32;
-        for _ in 0..8 {
-            if crc & 1 != 0 {
-                crc = (crc >> 1) ^ 0xEDB88320;
-            } else {
-                crc >>= 1;
-            }
-        }
-    }
-    crc ^ 0xFFFFFFFF
-}
-
 fn scan_directory(dir: &str, simplified: bool) -> Result<Vec<Entry>> {
     let mut entries = Vec::new();
 
@@ -83,17 +66,16 @@ fn scan_directory(dir: &str, simplified: bool) -> Result<Vec<Entry>> {
         })?;
 
         let (path_type, size, checksum) = if meta.is_dir() {
-            (2u8, 0u32, 0u32)
+            (2u8, 0u64, 0u32)
         } else if meta.is_symlink() {
-            (3u8, 0u32, 0u32)
+            (3u8, 0u64, 0u32)
         } else {
-            let size = meta.len() as u32;
             let cksum = if simplified {
                 0
             } else {
-                crc32_of_file(entry.path())
+                crc32_of_file(entry.path()).unwrap_or(0)
             };
-            (1u8, size, cksum)
+            (1u8, meta.len(), cksum)
         };
 
         entries.push(Entry {
@@ -155,7 +137,7 @@ fn parse_filelist(filelist: &str) -> Result<Vec<Entry>> {
             (0, 0)
         };
 
-        let size: u32 = if parts.len() > 3 {
+        let size: u64 = if parts.len() > 3 {
             parts[3].parse().unwrap_or(0)
         } else {
             0
@@ -192,86 +174,44 @@ fn parse_filelist(filelist: &str) -> Result<Vec<Entry>> {
 }
 
 fn build_bom(entries: &[Entry]) -> Result<Vec<u8>> {
-    let mut writer = BomWriter::new();
-
-    // Assign IDs to each path component.
-    // BOM uses a parent-child tree: each entry has an ID and a parent ID.
-    let mut path_id_map: HashMap<String, u32> = HashMap::new();
-    let mut next_id: u32 = 0;
-
-    // Collect tree entries (FileKey -> PathInfo1)
-    let mut tree_entries: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
-
-    for entry in entries {
-        // Determine parent path and file name
-        let path = &entry.path;
-        let (parent_path, name) = if path == "." {
-            (String::new(), ".".to_string())
-        } else if let Some(pos) = path.rfind('/') {
-            let parent = &path[..pos];
-            let name = &path[pos + 1..];
-            if parent.is_empty() {
-                (".".to_string(), name.to_string())
-            } else {
-                (parent.to_string(), name.to_string())
-            }
-        } else {
-            (".".to_string(), path.clone())
-        };
-
-        let my_id = next_id;
-        next_id += 1;
-        path_id_map.insert(path.clone(), my_id);
-
-        let parent_id = if path == "." {
-            0 // root has no real parent
-        } else {
-            *path_id_map.get(&parent_path).unwrap_or(&0)
-        };
-
-        // Create PathInfo2 and store as a block
-        let info2 = PathInfo2 {
-            path_type: entry.path_type,
-            architecture: 0,
-            mode: entry.mode,
-            user: entry.user,
-            group: entry.group,
-            modtime: entry.modtime,
+    let paths: Vec<PathEntry> = entries
+        .iter()
+        .map(|entry| PathEntry {
+            path: entry.path.clone(),
+            info: PathInfo2 {
+                path_type: entry.path_type,
+                architecture: 0,
+                mode: entry.mode,
+                user: entry.user,
+                group: entry.group,
+                modtime: entry.modtime,
+                size: u32::try_from(entry.size).unwrap_or(u32::MAX),
+                checksum: entry.checksum,
+                link_name: String::new(),
+                binary_info: Vec::new(),
+            },
             size: entry.size,
-            checksum: entry.checksum,
-            link_name: String::new(),
-        };
-        let info2_idx = writer.add_block(info2.to_bytes());
+        })
+        .collect();
 
-        let info1 = PathInfo1 {
-            id: my_id,
-            index: info2_idx,
-        };
-
-        let file_key = FileKey {
-            parent: parent_id,
-            name,
-        };
+    let mut writer = BomWriter::new();
+    writer.add_paths(&paths);
+    Ok(writer.serialize())
+}
 
-        tree_entries.push((file_key.to_bytes(), info1.to_bytes()));
+/// Apply -u/-g ownership and --reproducible to every entry.
+fn apply_overrides(entries: &mut [Entry], user: Option<u32>, group: Option<u32>, reproducible: bool) {
+    for entry in entries {
+        if let Some(user) = user {
+            entry.user = user;
+        }
+        if let Some(group) = group {
+            entry.group = group;
+        }
+        if reproducible {
+            entry.modtime = 0;
+        }
     }
-
-    let tree_idx = writer.build_tree(&tree_entries);
-    writer.add_variable("Paths", tree_idx);
-
-    // Add empty trees for standard BOM variables
-    let empty_tree_idx = writer.build_tree(&[]);
-    writer.add_variable("HLIndex", empty_tree_idx);
-
-    let empty_tree_idx2 = writer.build_tree(&[]);
-    writer.add_variable("Size64", empty_tree_idx2);
-
-    // VIndex - store a simple block with entry count
-    let vindex_data = (entries.len() as u32).to_be_bytes().to_vec();
-    let vindex_idx = writer.add_block(vindex_data);
-    writer.add_variable("VIndex", vindex_idx);
-
-    Ok(writer.serialize())
 }
 
 fn main() -> Result<()> {
@@ -279,6 +219,9 @@ fn main() -> Result<()> {
 
     let mut simplified = false;
     let mut filelist: Option<String> = None;
+    let mut user: Option<u32> = None;
+    let mut group: Option<u32> = None;
+    let mut reproducible = false;
     let mut positional: Vec<String> = Vec::new();
 
     let mut i = 0;
@@ -293,6 +236,21 @@ fn main() -> Result<()> {
                 }
                 filelist = Some(args[i].clone());
             }
+            "-u" | "-g" => {
+                let flag = args[i].clone();
+                i += 1;
+                let id = match args.get(i).map(|a| a.parse::<u32>()) {
+                    Some(Ok(id)) => id,
+                    Some(Err(_)) => help(Some(&format!("invalid id for {flag}: {}", args[i]))),
+                    None => help(Some(&format!("missing argument for {flag}"))),
+                };
+                if flag == "-u" {
+                    user = Some(id);
+                } else {
+                    group = Some(id);
+                }
+            }
+            "--reproducible" => reproducible = true,
             _ => {
                 if args[i].starts_with('-') {
                     help(Some(&format!("unknown argument {}", args[i])));
@@ -303,16 +261,17 @@ fn main() -> Result<()> {
         i += 1;
     }
 
-    if filelist.is_some() {
+    if let Some(filelist) = &filelist {
         // mkbom [-s] -i filelist bom
         if positional.len() != 1 {
             help(Some("expected: mkbom [-s] -i filelist bom"));
         }
         let bom_path = &positional[0];
-        let entries = parse_filelist(filelist.as_ref().unwrap())?;
+        let mut entries = parse_filelist(filelist)?;
         if entries.is_empty() {
             bail!("no entries found in filelist");
         }
+        apply_overrides(&mut entries, user, group, reproducible);
         let data = build_bom(&entries)?;
         fs::write(bom_path, data).with_context(|| format!("failed to write {bom_path}"))?;
     } else {
@@ -322,13 +281,96 @@ fn main() -> Result<()> {
         }
         let dir = &positional[0];
         let bom_path = &positional[1];
-        let entries = scan_directory(dir, simplified)?;
+        let mut entries = scan_directory(dir, simplified)?;
         if entries.is_empty() {
             bail!("no entries found in directory");
         }
+        apply_overrides(&mut entries, user, group, reproducible);
         let data = build_bom(&entries)?;
         fs::write(bom_path, data).with_context(|| format!("failed to write {bom_path}"))?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use xcbuild_bom::files::crc32_update;
+    use xcbuild_bom::paths::{self, FileKey, PathInfo1};
+    use xcbuild_bom::Bom;
+
+    /// Every path of a BOM with its ID and info.
+    fn read_paths(bom: &Bom) -> HashMap<String, (u32, PathInfo2)> {
+        let mut names = HashMap::new();
+        let mut infos = Vec::new();
+        for entry in bom.tree_entries("Paths").unwrap() {
+            let key = FileKey::from_bytes(&entry.key).unwrap();
+            let value = PathInfo1::from_bytes(&entry.value).unwrap();
+            let info = PathInfo2::from_bytes(bom.index_get(value.index).unwrap()).unwrap();
+            names.insert(value.id, (key.parent, key.name.clone()));
+            infos.push((key, value.id, info));
+        }
+        infos
+            .into_iter()
+            .map(|(key, id, info)| (paths::resolve_path(&key, &names), (id, info)))
+            .collect()
+    }
+
+    fn entry(path: &str, path_type: u8, size: u64) -> Entry {
+        Entry {
+            path: path.to_string(),
+            path_type,
+            mode: 0o644,
+            user: 0,
+            group: 0,
+            size,
+            checksum: 0,
+            modtime: 0,
+        }
+    }
+
+    #[test]
+    fn test_large_files_use_size64() {
+        let size = 5 << 30;
+        let entries = [entry(".", 2, 0), entry("./big", 1, size), entry("./small", 1, 10)];
+        let bom = Bom::load(build_bom(&entries).unwrap()).unwrap();
+
+        let paths = read_paths(&bom);
+        let (big_id, big) = &paths["./big"];
+        assert_eq!(big.size, u32::MAX);
+        assert_eq!(paths["./small"].1.size, 10);
+
+        let size64 = bom.tree_entries("Size64").unwrap();
+        assert_eq!(size64.len(), 1);
+        assert_eq!(paths::parse_size64(&size64[0].key, &size64[0].value), Some((*big_id, size)));
+    }
+
+    #[test]
+    fn test_scan_with_overrides() {
+        let dir = env::temp_dir().join(format!("mkbom-scan-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/file"), b"hello").unwrap();
+        fs::hard_link(dir.join("sub/file"), dir.join("link")).unwrap();
+
+        let mut entries = scan_directory(dir.to_str().unwrap(), false).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        apply_overrides(&mut entries, Some(501), Some(20), true);
+        let bom = Bom::load(build_bom(&entries).unwrap()).unwrap();
+
+        let paths = read_paths(&bom);
+        assert_eq!(paths.len(), 4);
+        for (_, info) in paths.values() {
+            assert_eq!((info.user, info.group, info.modtime), (501, 20, 0));
+            assert_eq!(info.architecture, 0);
+        }
+        // Hard links are listed as separate files, and HLIndex stays empty
+        // until its layout is checked against Apple's tools.
+        let file = &paths["./sub/file"].1;
+        assert_eq!((file.size, file.checksum), (5, crc32_update(0, b"hello")));
+        assert_eq!(paths["./link"].1.checksum, file.checksum);
+        assert!(bom.tree_entries("HLIndex").unwrap().is_empty());
+    }
+}
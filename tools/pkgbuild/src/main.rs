@@ -0,0 +1,513 @@
+use anyhow::{bail, Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use xcbuild_bom::files::{crc32_of_file, crc32_update};
+use xcbuild_bom::paths::{PathEntry, PathInfo2};
+use xcbuild_bom::BomWriter;
+use xcbuild_xar::{ChecksumAlgorithm, Encoding, XarWriter};
+
+fn help(error: Option<&str>) -> ! {
+    if let Some(e) = error {
+        eprintln!("error: {e}\n");
+    }
+
+    eprintln!("Usage: pkgbuild --root root-path --identifier id [options] package-output-path");
+    eprintln!();
+    eprintln!("Build a flat component package from the contents of a directory.");
+    eprintln!();
+    eprintln!("Options:");
+    eprintln!("  --root path              directory whose contents are installed");
+    eprintln!("  --identifier id          unique identifier of the package");
+    eprintln!("  --version version        package version (default 0)");
+    eprintln!("  --install-location path  where the root is installed (default /)");
+    eprintln!("  --scripts path           directory with preinstall and postinstall scripts");
+    eprintln!("  --ownership mode         recommended (root:wheel) or preserve (default recommended)");
+    eprintln!("  -h, --help               show this help");
+
+    std::process::exit(if error.is_some() { 1 } else { 0 });
+}
+
+/// How to record the owner of installed paths, as in Apple's pkgbuild.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ownership {
+    /// Everything is owned by root:wheel.
+    Recommended,
+    /// Keep the owner and group from the root directory.
+    Preserve,
+}
+
+struct Entry {
+    /// Path relative to the archive root, as `.` or `./name`.
+    path: String,
+    source: PathBuf,
+    /// Full `st_mode`, including the file type bits.
+    mode: u32,
+    user: u32,
+    group: u32,
+    size: u64,
+    modtime: u32,
+    checksum: u32,
+    link_name: String,
+    rdev: u64,
+    inode: (u64, u64),
+    nlink: u64,
+}
+
+impl Entry {
+    fn is_file(&self) -> bool {
+        self.mode & 0o170000 == 0o100000
+    }
+
+    fn is_dir(&self) -> bool {
+        self.mode & 0o170000 == 0o040000
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.mode & 0o170000 == 0o120000
+    }
+}
+
+fn scan_directory(dir: &Path, ownership: Ownership) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+
+    for entry in walkdir::WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry.with_context(|| "error walking directory")?;
+        let rel_path = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        let path = if rel_path.as_os_str().is_empty() {
+            ".".to_string()
+        } else {
+            format!("./{}", rel_path.to_string_lossy())
+        };
+
+        let meta = entry
+            .metadata()
+            .with_context(|| format!("cannot stat {}", entry.path().display()))?;
+
+        let mut entry = Entry {
+            path,
+            source: entry.path().to_path_buf(),
+            mode: meta.mode(),
+            user: meta.uid(),
+            group: meta.gid(),
+            size: 0,
+            modtime: meta.mtime() as u32,
+            checksum: 0,
+            link_name: String::new(),
+            rdev: meta.rdev(),
+            inode: (meta.dev(), meta.ino()),
+            nlink: 1,
+        };
+        if ownership == Ownership::Recommended {
+            entry.user = 0;
+            entry.group = 0;
+        }
+        if entry.is_file() {
+            entry.size = meta.len();
+            entry.checksum = crc32_of_file(&entry.source)
+                .with_context(|| format!("cannot read {}", entry.source.display()))?;
+        } else if entry.is_symlink() {
+            let target = fs::read_link(&entry.source)
+                .with_context(|| format!("cannot read link {}", entry.source.display()))?;
+            entry.link_name = target.to_string_lossy().to_string();
+            entry.size = entry.link_name.len() as u64;
+            entry.checksum = crc32_update(0, entry.link_name.as_bytes());
+        }
+        entries.push(entry);
+    }
+
+    // Count hard links within the root only, so links to files outside it
+    // are archived as plain files.
+    let mut links: HashMap<(u64, u64), u64> = HashMap::new();
+    for entry in entries.iter().filter(|e| e.is_file()) {
+        *links.entry(entry.inode).or_default() += 1;
+    }
+    for entry in entries.iter_mut().filter(|e| e.is_file()) {
+        entry.nlink = links[&entry.inode];
+    }
+
+    Ok(entries)
+}
+
+fn build_bom(entries: &[Entry]) -> Vec<u8> {
+    let paths: Vec<PathEntry> = entries
+        .iter()
+        .map(|entry| {
+            let path_type = if entry.is_dir() {
+                2
+            } else if entry.is_symlink() {
+                3
+            } else if entry.is_file() {
+                1
+            } else {
+                4
+            };
+            PathEntry {
+                path: entry.path.clone(),
+                info: PathInfo2 {
+                    path_type,
+                    architecture: 0,
+                    mode: entry.mode as u16,
+                    user: entry.user,
+                    group: entry.group,
+                    modtime: entry.modtime,
+                    // Devices store their device number in the size field.
+                    size: if path_type == 4 {
+                        entry.rdev as u32
+                    } else {
+                        u32::try_from(entry.size).unwrap_or(u32::MAX)
+                    },
+                    checksum: entry.checksum,
+                    link_name: entry.link_name.clone(),
+                    binary_info: Vec::new(),
+                },
+                size: entry.size,
+            }
+        })
+        .collect();
+
+    let mut writer = BomWriter::new();
+    writer.add_paths(&paths);
+    writer.serialize()
+}
+
+/// Write one entry of a portable (odc) cpio archive, the format installer
+/// payloads use. Every field is fixed-width octal.
+fn write_cpio_header(out: &mut impl Write, entry: &Entry, ino: u64, name: &str, data_size: u64) -> Result<()> {
+    if data_size > 0o77777777777 {
+        bail!("{} is too large for a cpio archive", entry.path);
+    }
+    write!(
+        out,
+        "070707{:06o}{:06o}{:06o}{:06o}{:06o}{:06o}{:06o}{:011o}{:06o}{:011o}",
+        entry.inode.0 & 0o777777,
+        ino & 0o777777,
+        entry.mode & 0o777777,
+        entry.user & 0o777777,
+        entry.group & 0o777777,
+        entry.nlink & 0o777777,
+        entry.rdev & 0o777777,
+        entry.modtime,
+        name.len() + 1,
+        data_size,
+    )?;
+    out.write_all(name.as_bytes())?;
+    out.write_all(&[0])?;
+    Ok(())
+}
+
+/// Archive `entries` as a gzip-compressed cpio stream.
+fn build_cpio(entries: &[Entry]) -> Result<Vec<u8>> {
+    let mut out = GzEncoder::new(Vec::new(), Compression::default());
+
+    // Hard links share an inode number; everything else gets its own.
+    let mut inodes: HashMap<(u64, u64), u64> = HashMap::new();
+    for entry in entries {
+        let next = inodes.len() as u64 + 1;
+        let ino = *inodes.entry(entry.inode).or_insert(next);
+
+        if entry.is_symlink() {
+            write_cpio_header(&mut out, entry, ino, &entry.path, entry.link_name.len() as u64)?;
+            out.write_all(entry.link_name.as_bytes())?;
+        } else if entry.is_file() {
+            write_cpio_header(&mut out, entry, ino, &entry.path, entry.size)?;
+            let mut file = fs::File::open(&entry.source)
+                .with_context(|| format!("cannot open {}", entry.source.display()))?;
+            let copied = io::copy(&mut file, &mut out)
+                .with_context(|| format!("cannot read {}", entry.source.display()))?;
+            if copied != entry.size {
+                bail!("{} changed while it was archived", entry.source.display());
+            }
+        } else {
+            write_cpio_header(&mut out, entry, ino, &entry.path, 0)?;
+        }
+    }
+
+    let trailer = Entry {
+        path: String::new(),
+        source: PathBuf::new(),
+        mode: 0,
+        user: 0,
+        group: 0,
+        size: 0,
+        modtime: 0,
+        checksum: 0,
+        link_name: String::new(),
+        rdev: 0,
+        inode: (0, 0),
+        nlink: 1,
+    };
+    write_cpio_header(&mut out, &trailer, 0, "TRAILER!!!", 0)?;
+
+    Ok(out.finish()?)
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+struct PackageInfo<'a> {
+    identifier: &'a str,
+    version: &'a str,
+    install_location: &'a str,
+    number_of_files: usize,
+    install_kbytes: u64,
+    preinstall: bool,
+    postinstall: bool,
+}
+
+fn package_info_xml(info: &PackageInfo) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<pkg-info format-version=\"2\" identifier=\"{}\" version=\"{}\" install-location=\"{}\" auth=\"root\" overwrite-permissions=\"true\" relocatable=\"false\" postinstall-action=\"none\">",
+        xml_escape(info.identifier),
+        xml_escape(info.version),
+        xml_escape(info.install_location),
+    );
+    let _ = writeln!(
+        xml,
+        "    <payload numberOfFiles=\"{}\" installKBytes=\"{}\"/>",
+        info.number_of_files, info.install_kbytes
+    );
+    for element in [
+        "bundle-version",
+        "upgrade-bundle",
+        "update-bundle",
+        "atomic-update-bundle",
+        "strict-identifier",
+        "relocate",
+    ] {
+        let _ = writeln!(xml, "    <{element}/>");
+    }
+    if info.preinstall || info.postinstall {
+        xml.push_str("    <scripts>\n");
+        if info.preinstall {
+            xml.push_str("        <preinstall file=\"./preinstall\"/>\n");
+        }
+        if info.postinstall {
+            xml.push_str("        <postinstall file=\"./postinstall\"/>\n");
+        }
+        xml.push_str("    </scripts>\n");
+    }
+    xml.push_str("</pkg-info>\n");
+    xml
+}
+
+struct PackageOptions<'a> {
+    root: &'a Path,
+    identifier: &'a str,
+    version: &'a str,
+    install_location: &'a str,
+    scripts: Option<&'a Path>,
+    ownership: Ownership,
+}
+
+/// Build a flat package: a xar archive with the Bom, Payload, optional
+/// Scripts and PackageInfo of the root directory.
+fn build_package(options: &PackageOptions) -> Result<Vec<u8>> {
+    let root = options.root;
+    if !root.is_dir() {
+        bail!("root {} is not a directory", root.display());
+    }
+    let entries = scan_directory(root, options.ownership)?;
+
+    let bom = build_bom(&entries);
+    let payload = build_cpio(&entries).with_context(|| "failed to build payload")?;
+
+    let mut preinstall = false;
+    let mut postinstall = false;
+    let mut scripts_archive = None;
+    if let Some(scripts) = options.scripts {
+        if !scripts.is_dir() {
+            bail!("scripts {} is not a directory", scripts.display());
+        }
+        preinstall = scripts.join("preinstall").is_file();
+        postinstall = scripts.join("postinstall").is_file();
+        // Scripts run as root, whoever owns them here.
+        let script_entries = scan_directory(scripts, Ownership::Recommended)?;
+        scripts_archive = Some(build_cpio(&script_entries).with_context(|| "failed to build scripts")?);
+    }
+
+    let files: Vec<&Entry> = entries.iter().filter(|e| e.path != ".").collect();
+    let package_info = package_info_xml(&PackageInfo {
+        identifier: options.identifier,
+        version: options.version,
+        install_location: options.install_location,
+        number_of_files: files.len(),
+        install_kbytes: files.iter().map(|e| e.size.div_ceil(1024)).sum(),
+        preinstall,
+        postinstall,
+    });
+
+    // Payloads are already gzipped and are stored unchanged.
+    let mut xar = XarWriter::new(ChecksumAlgorithm::Sha1);
+    xar.add_file("Bom", bom, 0o644, Encoding::Zlib);
+    xar.add_file("Payload", payload, 0o644, Encoding::None);
+    if let Some(data) = scripts_archive {
+        xar.add_file("Scripts", data, 0o644, Encoding::None);
+    }
+    xar.add_file("PackageInfo", package_info.into_bytes(), 0o644, Encoding::Zlib);
+
+    Ok(xar.serialize()?)
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let mut root: Option<String> = None;
+    let mut identifier: Option<String> = None;
+    let mut version = "0".to_string();
+    let mut install_location = "/".to_string();
+    let mut scripts: Option<String> = None;
+    let mut ownership = Ownership::Recommended;
+    let mut positional: Vec<String> = Vec::new();
+
+    let mut i = 0;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        match arg {
+            "-h" | "--help" => help(None),
+            "--root" | "--identifier" | "--version" | "--install-location" | "--scripts" | "--ownership" => {
+                i += 1;
+                let Some(value) = args.get(i).cloned() else {
+                    help(Some(&format!("missing argument for {arg}")));
+                };
+                match arg {
+                    "--root" => root = Some(value),
+                    "--identifier" => identifier = Some(value),
+                    "--version" => version = value,
+                    "--install-location" => install_location = value,
+                    "--scripts" => scripts = Some(value),
+                    _ => {
+                        ownership = match value.as_str() {
+                            "recommended" => Ownership::Recommended,
+                            "preserve" => Ownership::Preserve,
+                            _ => help(Some(&format!("invalid ownership mode {value}"))),
+                        }
+                    }
+                }
+            }
+            _ => {
+                if arg.starts_with('-') {
+                    help(Some(&format!("unknown argument {arg}")));
+                }
+                positional.push(arg.to_string());
+            }
+        }
+        i += 1;
+    }
+
+    let Some(root) = root else {
+        help(Some("--root is required"));
+    };
+    let Some(identifier) = identifier else {
+        help(Some("--identifier is required"));
+    };
+    if positional.len() != 1 {
+        help(Some("expected a single package output path"));
+    }
+    let output = &positional[0];
+
+    let package = build_package(&PackageOptions {
+        root: Path::new(&root),
+        identifier: &identifier,
+        version: &version,
+        install_location: &install_location,
+        scripts: scripts.as_deref().map(Path::new),
+        ownership,
+    })?;
+    fs::write(output, package).with_context(|| format!("failed to write {output}"))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use xcbuild_bom::paths::{self, FileKey, PathInfo1};
+    use xcbuild_bom::Bom;
+    use xcbuild_xar::Xar;
+
+    #[test]
+    fn test_build_package() {
+        let root = env::temp_dir().join(format!("pkgbuild-root-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::write(root.join("README"), b"hello").unwrap();
+        fs::hard_link(root.join("README"), root.join("README.txt")).unwrap();
+        std::os::unix::fs::symlink("README", root.join("link")).unwrap();
+        fs::write(root.join("bin/tool"), b"#!/bin/sh\n").unwrap();
+
+        let package = build_package(&PackageOptions {
+            root: &root,
+            identifier: "com.example.test",
+            version: "1.0",
+            install_location: "/",
+            scripts: None,
+            ownership: Ownership::Recommended,
+        });
+        fs::remove_dir_all(&root).unwrap();
+
+        let xar = Xar::load(package.unwrap()).unwrap();
+        let read = |name: &str| {
+            let file = xar.files().iter().find(|f| f.path == name).unwrap();
+            xar.read(file).unwrap()
+        };
+
+        let package_info = String::from_utf8(read("PackageInfo")).unwrap();
+        assert!(package_info.contains("identifier=\"com.example.test\""));
+        assert!(package_info.contains("numberOfFiles=\"5\""));
+
+        let mut payload = Vec::new();
+        GzDecoder::new(&read("Payload")[..]).read_to_end(&mut payload).unwrap();
+        assert!(payload.starts_with(b"070707"));
+
+        let bom = Bom::load(read("Bom")).unwrap();
+        let mut names = HashMap::new();
+        let mut infos = HashMap::new();
+        for entry in bom.tree_entries("Paths").unwrap() {
+            let key = FileKey::from_bytes(&entry.key).unwrap();
+            let value = PathInfo1::from_bytes(&entry.value).unwrap();
+            let info = PathInfo2::from_bytes(bom.index_get(value.index).unwrap()).unwrap();
+            names.insert(value.id, (key.parent, key.name.clone()));
+            infos.insert(value.id, (key, info));
+        }
+        let by_path: HashMap<String, (u32, PathInfo2)> = infos
+            .into_iter()
+            .map(|(id, (key, info))| (paths::resolve_path(&key, &names), (id, info)))
+            .collect();
+        assert_eq!(by_path.len(), 6);
+
+        let (_, readme) = &by_path["./README"];
+        assert_eq!((readme.user, readme.group, readme.size), (0, 0, 5));
+        assert_eq!(readme.checksum, crc32_update(0, b"hello"));
+        assert_eq!(by_path["./README.txt"].1.checksum, readme.checksum);
+        assert_eq!(by_path["./link"].1.link_name, "README");
+        assert_eq!(by_path["./bin/tool"].1.architecture, 0);
+
+        // Hard links and architectures are not written until their layout
+        // is checked against Apple's tools.
+        assert!(bom.tree_entries("HLIndex").unwrap().is_empty());
+    }
+}
@@ -16,15 +16,21 @@ fn help(error: Option<&str>) -> ! {
     eprintln!("commands:");
     eprintln!("  -lint (default)");
     eprintln!("  -help (this message)");
-    eprintln!("  -p");
+    eprintln!("  -p [-resolve]");
+    eprintln!("  -diff <file1> <file2> [-json]");
     eprintln!("  -convert <format>");
     eprintln!("  -create <format>");
     eprintln!("  -insert <key> <value> [-append]");
     eprintln!("  -replace <key> <value>");
     eprintln!("  -remove <key>");
-    eprintln!("  -extract <key> <format> [-expect <type>]");
+    eprintln!("  -extract <key> <format> [-expect <type>] (* in <key> matches any key or index)");
     eprintln!("  -type <key> [-expect <type>]");
 
+    eprintln!("\noptions:");
+    eprintln!("  -o <path>  write the result to <path> (- for stdout), single input only");
+    eprintln!("  -e <ext>   write the result next to the input with extension <ext>");
+    eprintln!("  -s         do not print anything on success");
+
     eprintln!("\nvalues:");
     eprintln!("  -bool <YES|NO>");
     eprintln!("  -integer <number>");
@@ -42,13 +48,18 @@ fn help(error: Option<&str>) -> ! {
     eprintln!("  binary1");
     eprintln!("  openstep1");
     eprintln!("  json");
+    eprintln!("  json-tagged (lossless JSON with $data, $date and $uid objects)");
     eprintln!("  raw");
     eprintln!("  swift");
     eprintln!("  objc");
+    eprintln!("  swift-codable (Codable structs inferred from the plist)");
+    eprintln!("  objc-interface (ObjC interfaces inferred from the plist)");
 
     eprintln!("\nflags:");
     eprintln!("  -r  human readable (sorted JSON)");
-    eprintln!("  -n  no trailing newline (raw format)");
+    eprintln!("  -n  no trailing newline (raw format, written to stdout unless -o is given)");
+    eprintln!("  -tagged  read and write JSON in the lossless tagged dialect");
+    eprintln!("  -resolve  with -p, print NSKeyedArchiver archives as their object graph");
 
     std::process::exit(if error.is_some() { 1 } else { 0 });
 }
@@ -59,6 +70,7 @@ enum Command {
     Print,
     Help,
     Create,
+    Diff,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -79,6 +91,11 @@ struct Adjustment {
     append: bool,
 }
 
+/// Type names accepted by -expect, as reported by -type.
+const EXPECT_TYPES: &[&str] = &[
+    "bool", "integer", "float", "string", "date", "data", "array", "dictionary",
+];
+
 fn value_type_name(value: &Value) -> &'static str {
     match value {
         Value::Boolean(_) => "bool",
@@ -160,51 +177,88 @@ enum PathPart {
     Index(usize),
 }
 
-/// Parse plutil's dot-separated key path with backslash escape support.
-/// Escaped dots (\.) are treated as literal dots within a key.
-fn parse_dot_key_path(path: &str) -> Result<Vec<PathPart>> {
-    let mut parts = Vec::new();
-    let mut current = String::new();
-    let mut escaped = false;
-
-    for ch in path.chars() {
-        if escaped {
-            current.push(ch);
-            escaped = false;
-            continue;
-        }
-        if ch == '\\' {
-            escaped = true;
-            continue;
-        }
-        if ch == '.' {
-            if current.is_empty() {
-                bail!("invalid keypath '{path}'");
-            }
-            parts.push(classify_path_part(&current));
-            current.clear();
-            continue;
-        }
-        current.push(ch);
-    }
+/// Parse plutil's dot-separated key path. Escaped dots (\.) are treated
+/// as literal dots within a key.
+fn parse_dot_key_path(path: &str) -> Result<Vec<String>> {
+    xcbuild_plist::parse_dot_key_path(path).map_err(|e| match e {
+        PlistError::InvalidEscape(_) => anyhow::anyhow!("invalid escape in keypath '{path}'"),
+        _ => anyhow::anyhow!("invalid keypath '{path}'"),
+    })
+}
 
-    if escaped {
-        bail!("invalid escape in keypath '{path}'");
+/// Interpret a key path segment against its container: numeric segments
+/// index arrays, while dictionaries are always looked up by key (so a key
+/// named "0" is reachable).
+fn classify_path_part(s: &str, container: &Value) -> PathPart {
+    match (container, s.parse::<usize>()) {
+        (Value::Array(_), Ok(idx)) => PathPart::Index(idx),
+        _ => PathPart::Key(s.to_string()),
     }
+}
 
-    if !current.is_empty() {
-        parts.push(classify_path_part(&current));
+/// Root type name for generated declarations: the input file's stem
+/// (`Info.plist` gives `Info`).
+fn root_type_name(file: &str) -> String {
+    let stem = Path::new(file).file_stem().unwrap_or_default().to_string_lossy();
+    let name: String = stem
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w[..1].to_ascii_uppercase() + &w[1..])
+        .collect();
+    match name.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => name,
+        _ => "Root".to_string(),
     }
+}
 
-    Ok(parts)
+fn read_plist(file: &str, tagged: bool) -> Result<Value, String> {
+    let data = read_input(file).map_err(|e| format!("error: {e}"))?;
+    let format = match xcbuild_plist::identify_format(&data) {
+        Some(PlistFormat::Json) if tagged => PlistFormat::TaggedJson,
+        Some(format) => format,
+        None => return Err(diagnostic(file, &PlistError::UnknownFormat)),
+    };
+    xcbuild_plist::deserialize_with_format(&data, format).map_err(|e| diagnostic(file, &e))
 }
 
-fn classify_path_part(s: &str) -> PathPart {
-    if let Ok(idx) = s.parse::<usize>() {
-        PathPart::Index(idx)
+/// Compare two plists structurally. Exits 0 when they are equal, 1 when
+/// they differ and 2 on errors, like diff(1).
+fn run_diff(old: &str, new: &str, tagged: bool, json: bool) -> i32 {
+    let (old_value, new_value) = match (read_plist(old, tagged), read_plist(new, tagged)) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{e}");
+            return 2;
+        }
+    };
+
+    let changes = xcbuild_plist::diff::diff(&old_value, &new_value);
+    if json {
+        let array = serde_json::Value::Array(changes.iter().map(|c| c.to_json()).collect());
+        println!("{}", serde_json::to_string_pretty(&array).unwrap_or_default());
     } else {
-        PathPart::Key(s.to_string())
+        for change in &changes {
+            println!("{change}");
+        }
     }
+    if changes.is_empty() { 0 } else { 1 }
+}
+
+/// Format a parse error as a `file:line:col: error: message` diagnostic.
+fn diagnostic(file: &str, error: &PlistError) -> String {
+    let Some(span) = error.span() else {
+        return format!("{file}: error: {error}");
+    };
+    let location = if span.line > 0 {
+        format!("{file}:{}:{}", span.line, span.column)
+    } else {
+        format!("{file}: offset {}", span.offset)
+    };
+    let mut message = format!("{location}: error: {}", error.message());
+    if !span.key_path.is_empty() {
+        message.push_str(&format!(" (key path {})", span.key_path_string()));
+    }
+    message
 }
 
 fn read_input(path: &str) -> Result<Vec<u8>> {
@@ -241,41 +295,36 @@ fn output_path(output: &Option<String>, extension: &Option<String>, file: &str)
     file.to_string()
 }
 
-fn navigate_to_value<'a>(root: &'a Value, parts: &[PathPart]) -> Result<&'a Value> {
-    let mut current = root;
-    for part in parts {
-        current = match (part, current) {
-            (PathPart::Key(key), Value::Dictionary(dict)) => dict
-                .get(key.as_str())
-                .ok_or_else(|| anyhow::anyhow!("invalid key path"))?,
-            (PathPart::Index(idx), Value::Array(arr)) => arr
-                .get(*idx)
-                .ok_or_else(|| anyhow::anyhow!("invalid key path"))?,
-            _ => bail!("invalid key path"),
-        };
-    }
-    Ok(current)
+fn navigate_to_value<'a>(root: &'a Value, parts: &[String]) -> Result<&'a Value> {
+    xcbuild_plist::get_at_key_path(root, parts).ok_or_else(|| anyhow::anyhow!("invalid key path"))
 }
 
-fn navigate_to_value_mut<'a>(root: &'a mut Value, parts: &[PathPart]) -> Result<&'a mut Value> {
-    let mut current = root;
-    for part in parts {
-        current = match part {
-            PathPart::Key(key) => match current {
-                Value::Dictionary(dict) => dict
-                    .get_mut(key.as_str())
-                    .ok_or_else(|| anyhow::anyhow!("invalid key path"))?,
-                _ => bail!("invalid key path"),
-            },
-            PathPart::Index(idx) => match current {
-                Value::Array(arr) => arr
-                    .get_mut(*idx)
-                    .ok_or_else(|| anyhow::anyhow!("invalid key path"))?,
-                _ => bail!("invalid key path"),
-            },
-        };
+fn navigate_to_value_mut<'a>(root: &'a mut Value, parts: &[String]) -> Result<&'a mut Value> {
+    xcbuild_plist::get_at_key_path_mut(root, parts)
+        .ok_or_else(|| anyhow::anyhow!("invalid key path"))
+}
+
+/// Extract every value matching a key path containing `*` wildcards, as
+/// an array.
+fn extract_matches(root: &Value, adjustment: &Adjustment) -> Result<Value> {
+    let segments = xcbuild_plist::parse_key_path_segments(&adjustment.path, '.');
+    let mut values = Vec::new();
+    for (path, value) in xcbuild_plist::query(root, &segments) {
+        if let Some(ref expect) = adjustment.expect_type {
+            let type_name = value_type_name(value);
+            if type_name != expect.as_str() {
+                bail!(
+                    "expected {expect} but found {type_name} at key path {}",
+                    path.iter()
+                        .map(|k| xcbuild_plist::escape_key(k, '.'))
+                        .collect::<Vec<_>>()
+                        .join(".")
+                );
+            }
+        }
+        values.push(value.clone());
     }
-    Ok(current)
+    Ok(Value::Array(values))
 }
 
 fn perform_adjustment(
@@ -283,6 +332,13 @@ fn perform_adjustment(
     adjustment: &Adjustment,
 ) -> Result<Option<Value>> {
     let parts = parse_dot_key_path(&adjustment.path)?;
+    if matches!(adjustment.adj_type, AdjustmentType::Extract)
+        && xcbuild_plist::parse_key_path_segments(&adjustment.path, '.')
+            .contains(&KeyPathSegment::Wildcard)
+    {
+        return extract_matches(root, adjustment).map(Some);
+    }
+
     if parts.is_empty() {
         // Empty path = root object
         match adjustment.adj_type {
@@ -323,25 +379,22 @@ fn perform_adjustment(
     }
 
     // For Type and Extract, handle expect_type validation
-    match adjustment.adj_type {
-        AdjustmentType::Type => {
-            let target = navigate_to_value(root, &parts)?;
-            let type_name = value_type_name(target);
-            if let Some(ref expect) = adjustment.expect_type {
-                if type_name != expect.as_str() {
-                    bail!(
-                        "expected {expect} but found {type_name} at key path {}",
-                        adjustment.path
-                    );
-                }
+    if let AdjustmentType::Type = adjustment.adj_type {
+        let target = navigate_to_value(root, &parts)?;
+        let type_name = value_type_name(target);
+        if let Some(ref expect) = adjustment.expect_type {
+            if type_name != expect.as_str() {
+                bail!(
+                    "expected {expect} but found {type_name} at key path {}",
+                    adjustment.path
+                );
             }
-            return Ok(Some(Value::String(type_name.to_string())));
         }
-        _ => {}
+        return Ok(Some(Value::String(type_name.to_string())));
     }
 
     let parent_parts = &parts[..parts.len() - 1];
-    let last_part = &parts[parts.len() - 1];
+    let last_name = &parts[parts.len() - 1];
 
     // Navigate to parent
     let parent = if parent_parts.is_empty() {
@@ -349,6 +402,7 @@ fn perform_adjustment(
     } else {
         navigate_to_value_mut(root, parent_parts)?
     };
+    let last_part = &classify_path_part(last_name, parent);
 
     match adjustment.adj_type {
         AdjustmentType::Insert => {
@@ -465,6 +519,9 @@ fn main() {
     let mut separator = false;
     let mut human_readable = false;
     let mut no_newline = false;
+    let mut tagged = false;
+    let mut json_output = false;
+    let mut resolve = false;
 
     let mut i = 0;
     while i < args.len() {
@@ -486,6 +543,12 @@ fn main() {
             "-p" => {
                 command = Some(Command::Print);
             }
+            "-diff" => {
+                command = Some(Command::Diff);
+            }
+            "-json" if matches!(command, Some(Command::Diff)) => {
+                json_output = true;
+            }
             "-create" => {
                 command = Some(Command::Create);
                 i += 1;
@@ -544,19 +607,12 @@ fn main() {
                     }
                 };
 
-                // Check for -append flag
-                let mut append = false;
-                if i + 1 < args.len() && args[i + 1] == "-append" {
-                    append = true;
-                    i += 1;
-                }
-
                 adjustments.push(Adjustment {
                     adj_type: AdjustmentType::Insert,
                     path,
                     value: Some(value),
                     expect_type: None,
-                    append,
+                    append: false,
                 });
             }
             "-replace" => {
@@ -628,19 +684,11 @@ fn main() {
                 }
                 convert_format = fmt;
 
-                // Check for -expect
-                let mut expect_type = None;
-                if i + 2 < args.len() && args[i + 1] == "-expect" {
-                    i += 1; // skip -expect
-                    i += 1; // consume type
-                    expect_type = Some(args[i].clone());
-                }
-
                 adjustments.push(Adjustment {
                     adj_type: AdjustmentType::Extract,
                     path,
                     value: None,
-                    expect_type,
+                    expect_type: None,
                     append: false,
                 });
             }
@@ -651,22 +699,34 @@ fn main() {
                 }
                 let path = args[i].clone();
 
-                // Check for -expect
-                let mut expect_type = None;
-                if i + 2 < args.len() && args[i + 1] == "-expect" {
-                    i += 1; // skip -expect
-                    i += 1; // consume type
-                    expect_type = Some(args[i].clone());
-                }
-
                 adjustments.push(Adjustment {
                     adj_type: AdjustmentType::Type,
                     path,
                     value: None,
-                    expect_type,
+                    expect_type: None,
                     append: false,
                 });
             }
+            "-expect" => {
+                // Applies to the preceding -extract or -type.
+                i += 1;
+                if i >= args.len() {
+                    help(Some("missing value for -expect"));
+                }
+                if !EXPECT_TYPES.contains(&args[i].as_str()) {
+                    help(Some(&format!("unknown type {}", args[i])));
+                }
+                match adjustments.last_mut() {
+                    Some(adj) if matches!(adj.adj_type, AdjustmentType::Extract | AdjustmentType::Type) => {
+                        adj.expect_type = Some(args[i].clone());
+                    }
+                    _ => help(Some("-expect must follow -extract or -type")),
+                }
+            }
+            "-append" => match adjustments.last_mut() {
+                Some(adj) if matches!(adj.adj_type, AdjustmentType::Insert) => adj.append = true,
+                _ => help(Some("-append must follow -insert")),
+            },
             "-o" => {
                 i += 1;
                 if i >= args.len() {
@@ -690,6 +750,12 @@ fn main() {
             "-n" => {
                 no_newline = true;
             }
+            "-tagged" => {
+                tagged = true;
+            }
+            "-resolve" => {
+                resolve = true;
+            }
             "--" => {
                 separator = true;
             }
@@ -720,6 +786,17 @@ fn main() {
         help(Some("-n is only supported with -extract <keypath> raw"));
     }
 
+    if resolve && !matches!(command, Some(Command::Print)) {
+        help(Some("-resolve is only supported with -p"));
+    }
+
+    if output.is_some() && extension.is_some() {
+        help(Some("-o and -e cannot be used together"));
+    }
+    if output.is_some() && inputs.len() > 1 {
+        help(Some("-o cannot be used with multiple input files"));
+    }
+
     // Handle -create: create empty plist
     if matches!(command, Some(Command::Create)) {
         let fmt = create_format.unwrap_or(PlistFormat::Xml);
@@ -747,6 +824,13 @@ fn main() {
         std::process::exit(0);
     }
 
+    if matches!(command, Some(Command::Diff)) {
+        if inputs.len() != 2 {
+            help(Some("-diff requires exactly two files"));
+        }
+        std::process::exit(run_diff(&inputs[0], &inputs[1], tagged, json_output));
+    }
+
     // Check conflicts
     if modify && matches!(command, Some(Command::Lint | Command::Print)) {
         help(Some("conflicting options specified"));
@@ -768,10 +852,18 @@ fn main() {
             }
         };
 
-        let (root, format) = match xcbuild_plist::deserialize(&data) {
+        let detected = match xcbuild_plist::identify_format(&data) {
+            Some(PlistFormat::Json) if tagged => Some(PlistFormat::TaggedJson),
+            other => other,
+        };
+        let parsed = match detected {
+            Some(f) => xcbuild_plist::deserialize_with_format(&data, f).map(|v| (v, f)),
+            None => Err(PlistError::UnknownFormat),
+        };
+        let (root, format) = match parsed {
             Ok((v, f)) => (v, f),
             Err(e) => {
-                eprintln!("error: {file}: {e}");
+                eprintln!("{}", diagnostic(file, &e));
                 success = false;
                 continue;
             }
@@ -780,71 +872,105 @@ fn main() {
         if modify {
             let mut root = root;
             let mut write_value: Option<Value> = None;
-            let mut type_output: Option<String> = None;
+            let mut types = Vec::new();
+            let mut failed = false;
 
+            // Apply every adjustment before writing anything, so a failing
+            // one leaves the file untouched.
             for adj in &adjustments {
                 match perform_adjustment(&mut root, adj) {
                     Ok(Some(extracted)) => {
                         if matches!(adj.adj_type, AdjustmentType::Type) {
-                            // For -type, the extracted value is a string with the type name
-                            if let Value::String(ref s) = extracted {
-                                type_output = Some(s.clone());
+                            // For -type, the extracted value is a string with the
+                            // type name, printed once every adjustment succeeded
+                            if let Value::String(s) = extracted {
+                                types.push(s);
                             }
                         } else {
                             write_value = Some(extracted);
                         }
                     }
+                    Ok(None) if matches!(adj.adj_type, AdjustmentType::Extract) => {
+                        eprintln!("error: no value at that key path or invalid key path: {}", adj.path);
+                        failed = true;
+                        break;
+                    }
                     Ok(None) => {}
                     Err(e) => {
                         eprintln!("error: {e}");
-                        success = false;
+                        failed = true;
+                        break;
                     }
                 }
             }
-
-            // Output type result
-            if let Some(ref type_name) = type_output {
+            if failed {
+                success = false;
+                continue;
+            }
+            for type_name in &types {
                 println!("{type_name}");
             }
 
-            // Only write file output if there's something to write
             // -type alone doesn't produce file output
-            if !has_type_adj || write_value.is_some() || convert_format.is_some() {
-                if has_type_adj && write_value.is_none() && convert_format.is_none() {
-                    // -type only, no file output needed
-                } else {
-                    let out_value = write_value.as_ref().unwrap_or(&root);
-                    let out_format = convert_format.unwrap_or(format);
+            if has_type_adj && write_value.is_none() && convert_format.is_none() {
+                continue;
+            }
+            let out_value = write_value.as_ref().unwrap_or(&root);
+            let out_format = match convert_format.unwrap_or(format) {
+                PlistFormat::Json if tagged => PlistFormat::TaggedJson,
+                other => other,
+            };
 
-                    let serialized = if human_readable && out_format == PlistFormat::Json {
-                        xcbuild_plist::serialize_json_sorted(out_value)
-                    } else {
-                        xcbuild_plist::serialize(out_value, out_format)
-                    };
+            let serialized = if human_readable && out_format == PlistFormat::Json {
+                xcbuild_plist::serialize_json_sorted(out_value)
+            } else if matches!(
+                out_format,
+                PlistFormat::SwiftCodable | PlistFormat::ObjcInterface
+            ) {
+                xcbuild_plist::serialize_schema(out_value, out_format, &root_type_name(file))
+            } else {
+                xcbuild_plist::serialize(out_value, out_format)
+            };
 
-                    match serialized {
-                        Ok(mut bytes) => {
-                            if no_newline && out_format == PlistFormat::Raw {
-                                // Remove trailing newline if -n flag is set
-                                if bytes.last() == Some(&b'\n') {
-                                    bytes.pop();
-                                }
-                            }
-                            let out_path = output_path(&output, &extension, file);
-                            if let Err(e) = write_output(&out_path, &bytes) {
-                                eprintln!("error: {e}");
-                                success = false;
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("error: {e}");
-                            success = false;
+            match serialized {
+                Ok(mut bytes) => {
+                    if no_newline && out_format == PlistFormat::Raw {
+                        // Remove trailing newline if -n flag is set
+                        if bytes.last() == Some(&b'\n') {
+                            bytes.pop();
                         }
                     }
+                    // Raw values go to stdout unless redirected, and
+                    // generated source never replaces the input plist.
+                    let default_extension = match out_format {
+                        PlistFormat::SwiftCodable => Some("swift".to_string()),
+                        PlistFormat::ObjcInterface => Some("h".to_string()),
+                        _ => None,
+                    };
+                    let out_path = if has_extract_raw && output.is_none() && extension.is_none() {
+                        "-".to_string()
+                    } else {
+                        output_path(&output, &extension.clone().or(default_extension), file)
+                    };
+                    if let Err(e) = write_output(&out_path, &bytes) {
+                        eprintln!("error: {e}");
+                        success = false;
+                    }
+                }
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    success = false;
                 }
             }
         } else if matches!(command, Some(Command::Print)) {
-            match xcbuild_plist::serialize(&root, PlistFormat::Ascii) {
+            // With -resolve, keyed archives are printed as their object
+            // graph instead of the raw $objects table.
+            let printed = if resolve && keyed_archive::is_keyed_archive(&root) {
+                keyed_archive::Archive::decode(&root).map(|a| a.pretty().into_bytes())
+            } else {
+                xcbuild_plist::serialize(&root, PlistFormat::Ascii)
+            };
+            match printed {
                 Ok(bytes) => {
                     if let Err(e) = write_output("-", &bytes) {
                         eprintln!("error: {e}");
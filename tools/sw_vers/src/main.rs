@@ -1,6 +1,9 @@
 use anyhow::{bail, Result};
 use clap::Parser;
-use xcbuild_sdk::{find_developer_root, Configuration, Manager, Product};
+use plist::Value;
+use std::cmp::Ordering;
+use xcbuild_plist::PlistFormat;
+use xcbuild_sdk::{find_developer_root, Configuration, Manager, Product, Target};
 
 #[derive(Parser)]
 #[command(about = "Print macOS version information")]
@@ -17,25 +20,83 @@ struct Cli {
 
     #[arg(long = "buildVersion", alias = "BuildVersion")]
     build_version: bool,
+
+    /// Print all fields as an XML property list
+    #[arg(long)]
+    plist: bool,
+
+    /// Print all fields as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Compare dotted version strings numerically ("10.15" < "14.0").
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parse = |v: &str| -> Vec<u64> { v.split('.').map(|c| c.parse().unwrap_or(0)).collect() };
+    let (a, b) = (parse(a), parse(b));
+    for i in 0..a.len().max(b.len()) {
+        let ordering = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Pick the macOS SDK to describe the host: the one named by `sdkroot` if it
+/// is a macOS SDK, otherwise the newest macOS SDK that has a
+/// SystemVersion.plist.
+fn select_macos_target<'a>(manager: &'a Manager, sdkroot: Option<&str>) -> Option<&'a Target> {
+    if let Some(sdkroot) = sdkroot {
+        if let Some((platform, target)) = manager.find_target(sdkroot) {
+            if platform.name == "macosx" && target.product.is_some() {
+                return Some(target);
+            }
+        }
+    }
+
+    manager
+        .platforms
+        .iter()
+        .filter(|platform| platform.name == "macosx")
+        .flat_map(|platform| platform.targets.iter())
+        .filter(|target| target.product.is_some())
+        .max_by(|a, b| {
+            compare_versions(
+                a.version.as_deref().unwrap_or(""),
+                b.version.as_deref().unwrap_or(""),
+            )
+        })
 }
 
-fn find_product() -> Result<Product> {
+/// Find the product to report, reading environment variables through `var`.
+fn find_product(var: impl Fn(&str) -> Option<String>) -> Result<Product> {
+    // An explicit SystemVersion.plist always wins
+    if let Some(path) = var("SYSTEM_VERSION_PLIST") {
+        if !path.is_empty() {
+            return match Product::open_file(&path) {
+                Some(product) => Ok(product),
+                None => bail!("unable to read SYSTEM_VERSION_PLIST '{path}'"),
+            };
+        }
+    }
+
     let dev_root = find_developer_root().unwrap_or_default();
     let config = Configuration::load(&Configuration::default_paths());
     let manager = Manager::open(&dev_root, config.as_ref());
 
-    if let Some(mgr) = &manager {
-        for platform in &mgr.platforms {
-            for target in &platform.targets {
-                if let Some(product) = &target.product {
-                    return Ok(product.clone());
-                }
-            }
+    let sdkroot = var("SDKROOT");
+    if let Some(target) = manager
+        .as_ref()
+        .and_then(|manager| select_macos_target(manager, sdkroot.as_deref()))
+    {
+        if let Some(product) = &target.product {
+            return Ok(product.clone());
         }
     }
 
     // Fall back to MACOSX_DEPLOYMENT_TARGET
-    if let Ok(version) = std::env::var("MACOSX_DEPLOYMENT_TARGET") {
+    if let Some(version) = var("MACOSX_DEPLOYMENT_TARGET") {
         return Ok(Product {
             name: Some("macOS".to_string()),
             version: Some(version),
@@ -59,6 +120,8 @@ fn normalize_args() -> Vec<String> {
         "ProductVersionExtra",
         "buildVersion",
         "BuildVersion",
+        "plist",
+        "json",
     ];
     std::env::args()
         .map(|arg| {
@@ -74,7 +137,32 @@ fn normalize_args() -> Vec<String> {
 
 fn main() -> Result<()> {
     let cli = Cli::parse_from(normalize_args());
-    let product = find_product()?;
+    let product = find_product(|name| std::env::var(name).ok())?;
+
+    if cli.plist || cli.json {
+        let mut dict = plist::Dictionary::new();
+        let name = product.name.clone().unwrap_or_else(|| "macOS".to_string());
+        dict.insert("ProductName".to_string(), Value::String(name));
+        let fields = [
+            ("ProductVersion", &product.version),
+            ("ProductVersionExtra", &product.version_extra),
+            ("ProductUserVisibleVersion", &product.user_visible_version),
+            ("ProductBuildVersion", &product.build_version),
+            ("ProductCopyright", &product.copyright),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                dict.insert(key.to_string(), Value::String(value.clone()));
+            }
+        }
+        let format = if cli.json { PlistFormat::Json } else { PlistFormat::Xml };
+        let data = xcbuild_plist::serialize(&Value::Dictionary(dict), format)?;
+        print!("{}", String::from_utf8_lossy(&data));
+        if cli.json {
+            println!();
+        }
+        return Ok(());
+    }
 
     if cli.product_name {
         println!("{}", product.name.as_deref().unwrap_or("macOS"));
@@ -82,13 +170,13 @@ fn main() -> Result<()> {
         println!("{}", product.version.as_deref().unwrap_or(""));
     } else if cli.product_version_extra {
         // Rapid Security Response version; empty if none installed
-        println!("{}", product.user_visible_version.as_deref().unwrap_or(""));
+        println!("{}", product.version_extra.as_deref().unwrap_or(""));
     } else if cli.build_version {
         println!("{}", product.build_version.as_deref().unwrap_or(""));
     } else {
         println!("ProductName:\t\t{}", product.name.as_deref().unwrap_or("macOS"));
         println!("ProductVersion:\t\t{}", product.version.as_deref().unwrap_or(""));
-        if let Some(extra) = &product.user_visible_version {
+        if let Some(extra) = &product.version_extra {
             println!("ProductVersionExtra:\t{}", extra);
         }
         println!("BuildVersion:\t\t{}", product.build_version.as_deref().unwrap_or(""));
@@ -96,3 +184,77 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn system_version(version: &str) -> String {
+        format!(
+            "{{ ProductName = macOS; ProductVersion = \"{version}\"; ProductBuildVersion = 0A0; }}"
+        )
+    }
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    /// A developer directory with macOS SDKs for `versions`; the ones marked
+    /// `false` have no SystemVersion.plist.
+    fn developer_dir(name: &str, versions: &[(&str, bool)]) -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("sw_vers-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let platform = root.join("Platforms/MacOSX.platform");
+        write(&platform.join("Info.plist"), "{ Name = macosx; }");
+        for (version, has_product) in versions {
+            let sdk = platform.join(format!("Developer/SDKs/MacOSX{version}.sdk"));
+            write(
+                &sdk.join("SDKSettings.plist"),
+                &format!("{{ CanonicalName = \"macosx{version}\"; Version = \"{version}\"; }}"),
+            );
+            if *has_product {
+                write(&sdk.join("System/Library/CoreServices/SystemVersion.plist"), &system_version(version));
+            }
+        }
+        root
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("10.15", "14.0"), Ordering::Less);
+        assert_eq!(compare_versions("14", "14.0"), Ordering::Equal);
+        assert_eq!(compare_versions("14.2", "14.10"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_select_newest_sdk() {
+        let root = developer_dir("newest", &[("10.15", true), ("9.0", true), ("14.2", true), ("15.0", false)]);
+        let manager = Manager::open(root.to_str().unwrap(), None).unwrap();
+        let newest = select_macos_target(&manager, None).and_then(|t| t.version.clone());
+        let named = select_macos_target(&manager, Some("macosx10.15")).and_then(|t| t.version.clone());
+        let unknown = select_macos_target(&manager, Some("iphoneos")).and_then(|t| t.version.clone());
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(newest.as_deref(), Some("14.2"));
+        assert_eq!(named.as_deref(), Some("10.15"));
+        assert_eq!(unknown.as_deref(), Some("14.2"));
+    }
+
+    #[test]
+    fn test_system_version_plist_override() {
+        let path = std::env::temp_dir().join(format!("sw_vers-override-{}.plist", std::process::id()));
+        write(&path, &system_version("13.1"));
+        let var = |name: &str| match name {
+            "SYSTEM_VERSION_PLIST" => Some(path.to_string_lossy().into_owned()),
+            "MACOSX_DEPLOYMENT_TARGET" => Some("11.0".to_string()),
+            _ => None,
+        };
+        let product = find_product(var);
+        let missing = find_product(|name| (name == "SYSTEM_VERSION_PLIST").then(|| "/nonexistent".to_string()));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(product.unwrap().version.as_deref(), Some("13.1"));
+        assert!(missing.is_err());
+    }
+}
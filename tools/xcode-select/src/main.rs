@@ -1,5 +1,12 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
 use std::process;
+
+use anyhow::{Context, Result};
+use plist::Value;
 use xcbuild_sdk::*;
 
 fn help(error: Option<&str>) -> ! {
@@ -7,14 +14,20 @@ fn help(error: Option<&str>) -> ! {
         eprintln!("error: {e}\n");
     }
 
-    eprintln!("Usage: xcode-select [action]\n");
+    eprintln!("Usage: xcode-select [options] [action]\n");
     eprintln!("Manipulate default developer directory.\n");
 
     eprintln!("Actions:");
     eprintln!("  -p, --print-path");
     eprintln!("  -r, --reset");
     eprintln!("  -s <path>, --switch <path>");
-    eprintln!("  --install");
+    eprintln!("  --install [archive or directory]");
+    eprintln!();
+
+    eprintln!("Options:");
+    eprintln!("  -u, --user (use the per-user link instead of the system link)");
+    eprintln!("  --install-dir <path>");
+    eprintln!("  --verbose (report which lookup step found the developer directory)");
     eprintln!();
 
     eprintln!("More information:");
@@ -33,6 +46,10 @@ fn main() {
     let mut reset_path = false;
     let mut switch_path: Option<String> = None;
     let mut install = false;
+    let mut install_source: Option<String> = None;
+    let mut install_dir: Option<String> = None;
+    let mut user = false;
+    let mut verbose = false;
 
     let mut i = 0;
     while i < args.len() {
@@ -49,7 +66,22 @@ fn main() {
                 }
                 switch_path = Some(args[i].clone());
             }
-            "--install" => install = true,
+            "--install" => {
+                install = true;
+                if args.get(i + 1).is_some_and(|a| !a.starts_with('-')) {
+                    i += 1;
+                    install_source = Some(args[i].clone());
+                }
+            }
+            "--install-dir" => {
+                i += 1;
+                if i >= args.len() {
+                    help(Some("missing value for --install-dir"));
+                }
+                install_dir = Some(args[i].clone());
+            }
+            "-u" | "--user" => user = true,
+            "--verbose" => verbose = true,
             _ => {
                 help(Some(&format!("unknown argument {arg}")));
             }
@@ -66,9 +98,19 @@ fn main() {
         process::exit(0);
     }
 
+    let scope = if user {
+        LinkScope::User
+    } else {
+        LinkScope::default_for_environment()
+    };
+    let link_path = scope.link_path().unwrap_or_default();
+
     if print_path {
-        match find_developer_root() {
-            Some(path) => {
+        match find_developer_root_with_source() {
+            Some((path, source)) => {
+                if verbose {
+                    eprintln!("xcode-select: using developer directory from {source}");
+                }
                 println!("{path}");
                 process::exit(0);
             }
@@ -80,41 +122,578 @@ fn main() {
     }
 
     if reset_path {
-        if !write_developer_root(None) {
-            eprintln!("error: failed to reset developer root. are you root?");
+        if !write_developer_root(None, scope) {
+            eprintln!("error: failed to reset developer root at '{link_path}'. are you root? (try --user)");
             process::exit(1);
         }
         process::exit(0);
     }
 
     if let Some(path) = &switch_path {
-        if !write_developer_root(Some(path)) {
-            eprintln!("error: failed to set developer root. are you root?");
+        if !write_developer_root(Some(path), scope) {
+            eprintln!("error: failed to set developer root at '{link_path}'. are you root? (try --user)");
             process::exit(1);
         }
+        if verbose {
+            eprintln!("xcode-select: wrote '{link_path}'");
+        }
         process::exit(0);
     }
 
     if install {
-        #[cfg(target_os = "macos")]
-        {
-            let status = process::Command::new("/usr/bin/xcode-select")
-                .arg("--install")
-                .status();
-            match status {
-                Ok(s) => process::exit(s.code().unwrap_or(1)),
-                Err(e) => {
-                    eprintln!("error: failed to launch xcode-select --install: {e}");
-                    process::exit(1);
+        let Some(source) = &install_source else {
+            #[cfg(target_os = "macos")]
+            {
+                let status = process::Command::new("/usr/bin/xcode-select")
+                    .arg("--install")
+                    .status();
+                match status {
+                    Ok(s) => process::exit(s.code().unwrap_or(1)),
+                    Err(e) => {
+                        eprintln!("error: failed to launch xcode-select --install: {e}");
+                        process::exit(1);
+                    }
                 }
             }
+            #[cfg(not(target_os = "macos"))]
+            {
+                eprintln!("error: xcode-select --install without an archive or directory is only supported on macOS");
+                process::exit(1);
+            }
+        };
+        let destination = match install_dir.map(PathBuf::from).or_else(|| default_install_dir(scope)) {
+            Some(d) => d,
+            None => help(Some("unable to determine install directory; pass --install-dir")),
+        };
+        if let Err(e) = install_developer_dir(Path::new(source), &destination, verbose) {
+            eprintln!("error: {e:#}");
+            process::exit(1);
         }
-        #[cfg(not(target_os = "macos"))]
-        {
-            eprintln!("error: xcode-select --install is only supported on macOS");
+        let destination = destination.to_string_lossy().to_string();
+        if !write_developer_root(Some(&destination), scope) {
+            eprintln!("error: installed to '{destination}' but failed to write '{link_path}'. are you root? (try --user)");
             process::exit(1);
         }
+        println!("{destination}");
+        process::exit(0);
     }
 
     help(Some("no actions provided"));
 }
+
+/// Where `--install` puts the developer directory when `--install-dir` is
+/// not given.
+fn default_install_dir(scope: LinkScope) -> Option<PathBuf> {
+    match scope {
+        LinkScope::User => home_dir().map(|home| PathBuf::from(home).join(".xcsdk/Developer")),
+        LinkScope::System => Some(PathBuf::from("/opt/xcsdk/Developer")),
+    }
+}
+
+/// Install a developer directory from a directory or a tar (optionally
+/// gzip-compressed) archive. The source may contain an Xcode.app, a
+/// developer directory (with `Platforms` or `Toolchains`), or bare `.sdk`
+/// bundles, which are placed under the matching `.platform`.
+fn install_developer_dir(source: &Path, destination: &Path, verbose: bool) -> Result<()> {
+    fs::create_dir_all(destination)
+        .with_context(|| format!("failed to create {}", destination.display()))?;
+
+    if source.is_dir() {
+        install_from_directory(source, destination, verbose)
+    } else {
+        install_from_archive(source, destination, verbose)
+    }
+}
+
+fn install_from_directory(source: &Path, destination: &Path, verbose: bool) -> Result<()> {
+    if let Some(developer) = find_developer_layout(source, 3) {
+        if verbose {
+            eprintln!("xcode-select: installing developer directory {}", developer.display());
+        }
+        return copy_tree(&developer, destination);
+    }
+
+    let sdks = find_sdks(source, 3);
+    if sdks.is_empty() {
+        anyhow::bail!("no developer directory or SDK found in '{}'", source.display());
+    }
+    for sdk in &sdks {
+        let settings_path = sdk.join("SDKSettings.plist");
+        let data = fs::read(&settings_path)
+            .with_context(|| format!("failed to read {}", settings_path.display()))?;
+        let platform = sdk_platform(&data)
+            .with_context(|| format!("unable to determine platform for {}", sdk.display()))?;
+        let sdk_destination = sdk_destination(destination, &platform, sdk)?;
+        if verbose {
+            eprintln!("xcode-select: installing SDK {} to {}", sdk.display(), sdk_destination.display());
+        }
+        copy_tree(sdk, &sdk_destination)?;
+        write_platform_info(destination, &platform)?;
+    }
+    Ok(())
+}
+
+/// Unpack an archive straight into the destination. A first pass over the
+/// entries finds the developer directory or SDKs; the second pass writes
+/// each entry under its final location.
+fn install_from_archive(archive: &Path, destination: &Path, verbose: bool) -> Result<()> {
+    let mut directories = BTreeSet::new();
+    let mut sdk_settings = BTreeMap::new();
+    for entry in open_archive(archive)?.entries()? {
+        let mut entry = entry?;
+        let path = archive_entry_path(&entry)?;
+        directories.extend(path.ancestors().skip(1).map(Path::to_path_buf));
+        if entry.header().entry_type().is_dir() {
+            directories.insert(path);
+        } else if path.file_name().is_some_and(|n| n == "SDKSettings.plist") {
+            if let Some(sdk) = path.parent().filter(|p| p.extension().is_some_and(|e| e == "sdk")) {
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data)?;
+                sdk_settings.insert(sdk.to_path_buf(), data);
+            }
+        }
+    }
+
+    let mut mappings = Vec::new();
+    let mut platforms = Vec::new();
+    if let Some(developer) = find_archive_developer_layout(&directories, Path::new(""), 3) {
+        if verbose {
+            eprintln!(
+                "xcode-select: installing developer directory {}",
+                archive.join(&developer).display()
+            );
+        }
+        mappings.push((developer, destination.to_path_buf()));
+    } else {
+        for (sdk, data) in &sdk_settings {
+            // Match `find_sdks`: no deeper than three levels, and not nested
+            // inside another SDK.
+            if sdk.components().count() > 4
+                || sdk_settings.keys().any(|other| other != sdk && sdk.starts_with(other))
+            {
+                continue;
+            }
+            let platform = sdk_platform(data).with_context(|| {
+                format!("unable to determine platform for {}", archive.join(sdk).display())
+            })?;
+            let sdk_destination = sdk_destination(destination, &platform, sdk)?;
+            if verbose {
+                eprintln!(
+                    "xcode-select: installing SDK {} to {}",
+                    archive.join(sdk).display(),
+                    sdk_destination.display()
+                );
+            }
+            mappings.push((sdk.clone(), sdk_destination));
+            platforms.push(platform);
+        }
+        if mappings.is_empty() {
+            anyhow::bail!("no developer directory or SDK found in '{}'", archive.display());
+        }
+    }
+
+    for entry in open_archive(archive)?.entries()? {
+        let mut entry = entry?;
+        let path = archive_entry_path(&entry)?;
+        if let Some(target) = map_archive_path(&mappings, &path) {
+            unpack_entry(&mut entry, &target, destination, &mappings)?;
+        }
+    }
+
+    for platform in &platforms {
+        write_platform_info(destination, platform)?;
+    }
+    Ok(())
+}
+
+fn open_archive(archive: &Path) -> Result<tar::Archive<Box<dyn Read>>> {
+    let mut file =
+        fs::File::open(archive).with_context(|| format!("failed to open {}", archive.display()))?;
+    let mut magic = [0u8; 2];
+    let n = file.read(&mut magic)?;
+    let file =
+        fs::File::open(archive).with_context(|| format!("failed to open {}", archive.display()))?;
+
+    let reader: Box<dyn Read> = if n == 2 && magic == [0x1f, 0x8b] {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+/// The entry's path relative to the archive root. Absolute paths and `..`
+/// components are rejected.
+fn archive_entry_path(entry: &tar::Entry<'_, Box<dyn Read>>) -> Result<PathBuf> {
+    normalize_archive_path(&entry.path()?)
+}
+
+fn normalize_archive_path(path: &Path) -> Result<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::CurDir => {}
+            _ => anyhow::bail!("unsafe path '{}' in archive", path.display()),
+        }
+    }
+    Ok(normalized)
+}
+
+fn map_archive_path(mappings: &[(PathBuf, PathBuf)], path: &Path) -> Option<PathBuf> {
+    mappings
+        .iter()
+        .find_map(|(from, to)| path.strip_prefix(from).ok().map(|rest| to.join(rest)))
+}
+
+fn unpack_entry(
+    entry: &mut tar::Entry<'_, Box<dyn Read>>,
+    target: &Path,
+    destination: &Path,
+    mappings: &[(PathBuf, PathBuf)],
+) -> Result<()> {
+    // Never write through a symlink, whether the archive created it or an
+    // earlier install left it behind.
+    let relative = target.strip_prefix(destination)?;
+    let mut current = destination.to_path_buf();
+    for component in relative.parent().into_iter().flat_map(Path::components) {
+        current.push(component);
+        if fs::symlink_metadata(&current).is_ok_and(|m| m.file_type().is_symlink()) {
+            anyhow::bail!("refusing to write {} through symlink {}", target.display(), current.display());
+        }
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let kind = entry.header().entry_type();
+    if !kind.is_dir() && fs::symlink_metadata(target).is_ok_and(|m| !m.is_dir()) {
+        fs::remove_file(target).with_context(|| format!("failed to replace {}", target.display()))?;
+    }
+
+    if kind.is_hard_link() {
+        let link = entry
+            .link_name()?
+            .with_context(|| format!("hard link {} has no target", target.display()))?;
+        let source = map_archive_path(mappings, &normalize_archive_path(&link)?)
+            .with_context(|| format!("hard link {} points outside the installed files", target.display()))?;
+        fs::hard_link(&source, target)
+            .with_context(|| format!("failed to link {}", target.display()))?;
+        return Ok(());
+    }
+    entry
+        .unpack(target)
+        .with_context(|| format!("failed to unpack {}", target.display()))?;
+    Ok(())
+}
+
+/// Find a developer directory: `Contents/Developer` of an app bundle, or a
+/// directory that has `Platforms` or `Toolchains`.
+fn find_developer_layout(dir: &Path, depth: usize) -> Option<PathBuf> {
+    let app_developer = dir.join("Contents/Developer");
+    if app_developer.is_dir() {
+        return Some(app_developer);
+    }
+    if dir.join("Platforms").is_dir() || dir.join("Toolchains").is_dir() {
+        return Some(dir.to_path_buf());
+    }
+    if depth == 0 {
+        return None;
+    }
+    subdirectories(dir)
+        .into_iter()
+        .find_map(|child| find_developer_layout(&child, depth - 1))
+}
+
+/// `find_developer_layout` over the directories listed in an archive.
+fn find_archive_developer_layout(
+    directories: &BTreeSet<PathBuf>,
+    dir: &Path,
+    depth: usize,
+) -> Option<PathBuf> {
+    let app_developer = dir.join("Contents/Developer");
+    if directories.contains(&app_developer) {
+        return Some(app_developer);
+    }
+    if directories.contains(&dir.join("Platforms")) || directories.contains(&dir.join("Toolchains")) {
+        return Some(dir.to_path_buf());
+    }
+    if depth == 0 {
+        return None;
+    }
+    directories
+        .iter()
+        .filter(|child| child.parent() == Some(dir))
+        .find_map(|child| find_archive_developer_layout(directories, child, depth - 1))
+}
+
+fn find_sdks(dir: &Path, depth: usize) -> Vec<PathBuf> {
+    if dir.extension().is_some_and(|e| e == "sdk") && dir.join("SDKSettings.plist").is_file() {
+        return vec![dir.to_path_buf()];
+    }
+    if depth == 0 {
+        return Vec::new();
+    }
+    subdirectories(dir)
+        .into_iter()
+        .flat_map(|child| find_sdks(&child, depth - 1))
+        .collect()
+}
+
+fn subdirectories(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+                .map(|e| e.path())
+                .collect()
+        })
+        .unwrap_or_default();
+    dirs.sort();
+    dirs
+}
+
+/// Platform bundle directory names for SDK platform names.
+fn platform_directory_name(platform: &str) -> String {
+    match platform {
+        "macosx" => "MacOSX".to_string(),
+        "iphoneos" => "iPhoneOS".to_string(),
+        "iphonesimulator" => "iPhoneSimulator".to_string(),
+        "appletvos" => "AppleTVOS".to_string(),
+        "appletvsimulator" => "AppleTVSimulator".to_string(),
+        "watchos" => "WatchOS".to_string(),
+        "watchsimulator" => "WatchSimulator".to_string(),
+        "xros" => "XROS".to_string(),
+        "xrsimulator" => "XRSimulator".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// The platform an SDK belongs to, from its `SDKSettings.plist`.
+fn sdk_platform(settings_data: &[u8]) -> Option<String> {
+    let (settings, _) = xcbuild_plist::deserialize(settings_data).ok()?;
+
+    // Prefer DefaultProperties.PLATFORM_NAME; otherwise strip the version
+    // from the canonical name (e.g. "iphoneos17.0" -> "iphoneos").
+    settings
+        .as_dictionary()
+        .and_then(|d| d.get("DefaultProperties"))
+        .and_then(|v| v.as_dictionary())
+        .and_then(|d| d.get("PLATFORM_NAME"))
+        .and_then(|v| v.as_string())
+        .map(|s| s.to_string())
+        .or_else(|| {
+            settings
+                .as_dictionary()
+                .and_then(|d| d.get("CanonicalName"))
+                .and_then(|v| v.as_string())
+                .map(|s| s.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.').to_string())
+        })
+        .filter(|s| !s.is_empty())
+}
+
+fn platform_dir(destination: &Path, platform: &str) -> PathBuf {
+    destination
+        .join("Platforms")
+        .join(format!("{}.platform", platform_directory_name(platform)))
+}
+
+fn sdk_destination(destination: &Path, platform: &str, sdk: &Path) -> Result<PathBuf> {
+    let sdk_name = sdk.file_name().context("invalid SDK path")?;
+    Ok(platform_dir(destination, platform).join("Developer/SDKs").join(sdk_name))
+}
+
+fn write_platform_info(destination: &Path, platform: &str) -> Result<()> {
+    let info_path = platform_dir(destination, platform).join("Info.plist");
+    if !info_path.exists() {
+        let mut info = plist::Dictionary::new();
+        info.insert("Name".to_string(), Value::String(platform.to_string()));
+        info.insert(
+            "Identifier".to_string(),
+            Value::String(format!("com.apple.platform.{platform}")),
+        );
+        info.insert("Description".to_string(), Value::String(platform.to_string()));
+        let data = xcbuild_plist::serialize(&Value::Dictionary(info), xcbuild_plist::PlistFormat::Xml)?;
+        fs::write(&info_path, data)
+            .with_context(|| format!("failed to write {}", info_path.display()))?;
+    }
+    Ok(())
+}
+
+/// Recursively copy a directory, preserving symlinks and permissions.
+fn copy_tree(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to).with_context(|| format!("failed to create {}", to.display()))?;
+    for entry in fs::read_dir(from).with_context(|| format!("failed to read {}", from.display()))? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_tree(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            let link = fs::read_link(entry.path())?;
+            if fs::symlink_metadata(&target).is_ok() {
+                fs::remove_file(&target)?;
+            }
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(&link, &target)
+                .with_context(|| format!("failed to create link {}", target.display()))?;
+            #[cfg(not(unix))]
+            anyhow::bail!("cannot copy symlink {} on this platform", link.display());
+        } else {
+            fs::copy(entry.path(), &target)
+                .with_context(|| format!("failed to copy {}", entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    enum Item<'a> {
+        Dir(&'a str),
+        File(&'a str, &'a str),
+        Symlink(&'a str, &'a str),
+        HardLink(&'a str, &'a str),
+    }
+
+    /// Write a tar archive. Names are copied into the header as is, since
+    /// `tar::Header::set_path` refuses the unsafe ones under test.
+    fn write_archive(path: &Path, items: &[Item]) {
+        fn set_name(header: &mut tar::Header, name: &str) {
+            let field = &mut header.as_old_mut().name;
+            field.fill(0);
+            field[..name.len()].copy_from_slice(name.as_bytes());
+        }
+        fn set_link(header: &mut tar::Header, link: &str) {
+            let field = &mut header.as_old_mut().linkname;
+            field.fill(0);
+            field[..link.len()].copy_from_slice(link.as_bytes());
+        }
+
+        let mut builder = tar::Builder::new(fs::File::create(path).unwrap());
+        for item in items {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o755);
+            header.set_size(0);
+            let data = match item {
+                Item::Dir(name) => {
+                    header.set_entry_type(tar::EntryType::Directory);
+                    set_name(&mut header, name);
+                    "".as_bytes()
+                }
+                Item::File(name, contents) => {
+                    header.set_entry_type(tar::EntryType::Regular);
+                    header.set_size(contents.len() as u64);
+                    set_name(&mut header, name);
+                    contents.as_bytes()
+                }
+                Item::Symlink(name, link) | Item::HardLink(name, link) => {
+                    let kind = match item {
+                        Item::Symlink(..) => tar::EntryType::Symlink,
+                        _ => tar::EntryType::Link,
+                    };
+                    header.set_entry_type(kind);
+                    set_name(&mut header, name);
+                    set_link(&mut header, link);
+                    "".as_bytes()
+                }
+            };
+            header.set_cksum();
+            builder.append(&header, data).unwrap();
+        }
+        builder.finish().unwrap();
+    }
+
+    /// Install `items` from an archive into a fresh destination and return
+    /// the result along with the test's base directory.
+    fn install(name: &str, items: &[Item]) -> (Result<()>, PathBuf) {
+        let base = env::temp_dir().join(format!("xcode-select-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(&base).unwrap();
+        let archive = base.join("archive.tar");
+        write_archive(&archive, items);
+        let result = install_from_archive(&archive, &base.join("install"), false);
+        (result, base)
+    }
+
+    #[test]
+    fn test_normalize_archive_path() {
+        assert_eq!(normalize_archive_path(Path::new("./a/./b")).unwrap(), PathBuf::from("a/b"));
+        assert!(normalize_archive_path(Path::new("a/../../b")).is_err());
+        assert!(normalize_archive_path(Path::new("/etc/passwd")).is_err());
+    }
+
+    #[test]
+    fn test_install_archive() {
+        let (result, base) = install(
+            "archive",
+            &[
+                Item::Dir("Xcode/Platforms/"),
+                Item::File("Xcode/Platforms/a", "data"),
+                Item::HardLink("Xcode/Platforms/b", "Xcode/Platforms/a"),
+                Item::Symlink("Xcode/Platforms/c", "a"),
+            ],
+        );
+        let platforms = base.join("install/Platforms");
+        let b = fs::read_to_string(platforms.join("b"));
+        let c = fs::read_link(platforms.join("c"));
+        fs::remove_dir_all(&base).unwrap();
+        result.unwrap();
+        assert_eq!(b.unwrap(), "data");
+        assert_eq!(c.unwrap(), PathBuf::from("a"));
+    }
+
+    #[test]
+    fn test_reject_parent_and_absolute_paths() {
+        for (name, path) in [("parent", "Xcode/../../escape"), ("absolute", "/tmp/escape")] {
+            let (result, base) =
+                install(name, &[Item::Dir("Xcode/Platforms/"), Item::File(path, "data")]);
+            fs::remove_dir_all(&base).unwrap();
+            let error = result.unwrap_err().to_string();
+            assert!(error.contains("unsafe path"), "{error}");
+        }
+    }
+
+    #[test]
+    fn test_reject_write_through_symlink() {
+        let base = env::temp_dir().join(format!("xcode-select-symlink-{}", process::id()));
+        let outside = base.with_extension("outside");
+        let _ = fs::remove_dir_all(&outside);
+        fs::create_dir_all(&outside).unwrap();
+        let (result, base) = install(
+            "symlink",
+            &[
+                Item::Dir("Xcode/Platforms/"),
+                Item::Symlink("Xcode/Platforms/link", outside.to_str().unwrap()),
+                Item::File("Xcode/Platforms/link/file", "data"),
+            ],
+        );
+        let escaped = outside.join("file").exists();
+        fs::remove_dir_all(&base).unwrap();
+        fs::remove_dir_all(&outside).unwrap();
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("through symlink"), "{error}");
+        assert!(!escaped);
+    }
+
+    #[test]
+    fn test_reject_hard_link_outside_install() {
+        for (name, link) in [("outside", "Other/file"), ("parent", "../file"), ("absolute", "/etc/passwd")] {
+            let (result, base) = install(
+                &format!("hardlink-{name}"),
+                &[
+                    Item::Dir("Xcode/Platforms/"),
+                    Item::File("Other/file", "data"),
+                    Item::HardLink("Xcode/Platforms/link", link),
+                ],
+            );
+            let created = base.join("install/Platforms/link").exists();
+            fs::remove_dir_all(&base).unwrap();
+            assert!(result.is_err(), "{link}");
+            assert!(!created, "{link}");
+        }
+    }
+}
@@ -3,6 +3,8 @@ use std::path::Path;
 use std::process;
 use xcbuild_sdk::*;
 
+use serde_json::json;
+
 fn help(error: Option<&str>) -> ! {
     if let Some(e) = error {
         eprintln!("error: {e}\n");
@@ -13,7 +15,7 @@ fn help(error: Option<&str>) -> ! {
 
     eprintln!("Modes:");
     eprintln!("  -r, --run (default)");
-    eprintln!("  -f, --find");
+    eprintln!("  -f, --find [tool...]");
     eprintln!("  -h, --help (this message)");
     eprintln!("  --version");
     eprintln!("  --show-sdk-path");
@@ -22,11 +24,14 @@ fn help(error: Option<&str>) -> ! {
     eprintln!("  --show-sdk-platform-path");
     eprintln!("  --show-sdk-platform-version");
     eprintln!("  --show-toolchain-path");
+    eprintln!("  --show-toolchain-version");
+    eprintln!("  --sdks, --show-sdks");
     eprintln!();
 
     eprintln!("Options:");
     eprintln!("  -v, --verbose");
     eprintln!("  -l, --log");
+    eprintln!("  --json");
     eprintln!("  -n, --no-cache (not implemented)");
     eprintln!("  -k, --kill-cache (not implemented)");
 
@@ -61,6 +66,9 @@ fn main() {
     let mut show_sdk_platform_path = false;
     let mut show_sdk_platform_version = false;
     let mut show_toolchain_path = false;
+    let mut show_toolchain_version = false;
+    let mut show_sdks = false;
+    let mut json_output = false;
     let mut verbose = false;
     let mut log_mode = false;
     let mut sdk_arg: Option<String> = None;
@@ -93,6 +101,11 @@ fn main() {
                 "--show-toolchain-path" | "-show-toolchain-path" => {
                     show_toolchain_path = true
                 }
+                "--show-toolchain-version" | "-show-toolchain-version" => {
+                    show_toolchain_version = true
+                }
+                "--sdks" | "-sdks" | "--show-sdks" | "-show-sdks" => show_sdks = true,
+                "--json" | "-json" => json_output = true,
                 "-l" | "--log" | "-log" => log_mode = true,
                 "-v" | "--verbose" | "-verbose" => verbose = true,
                 "-n" | "--no-cache" | "-no-cache" => {
@@ -181,6 +194,12 @@ fn main() {
         eprintln!("verbose: using developer root '{}'", manager.path);
     }
 
+    // Handle --sdks (does not depend on a selected SDK)
+    if show_sdks {
+        print_sdks(&manager, json_output);
+        process::exit(0);
+    }
+
     let show_sdk_value = show_sdk_path
         || show_sdk_version
         || show_sdk_build_version
@@ -242,13 +261,18 @@ fn main() {
         }
     }
 
-    // Handle --show-toolchain-path
-    if show_toolchain_path {
+    // Handle --show-toolchain-path / --show-toolchain-version
+    if show_toolchain_path || show_toolchain_version {
         if toolchains.is_empty() {
             eprintln!("error: unable to find any toolchains");
             process::exit(1);
         }
-        println!("{}", toolchains[0].path);
+        let toolchain = toolchains[0];
+        if show_toolchain_path {
+            print_value(json_output, "toolchainPath", Some(&toolchain.path));
+        } else {
+            print_value(json_output, "toolchainVersion", toolchain.version.as_deref());
+        }
         process::exit(0);
     }
 
@@ -257,18 +281,27 @@ fn main() {
         let (platform, target) = target_result.expect("target required for SDK queries");
 
         if show_sdk_path {
-            println!("{}", target.path);
+            print_value(json_output, "sdkPath", Some(&target.path));
         } else if show_sdk_version {
-            println!("{}", target.version.as_deref().unwrap_or(""));
+            print_value(json_output, "sdkVersion", target.version.as_deref());
         } else if show_sdk_build_version {
             let build_version = target.product.as_ref()
                 .and_then(|p| p.build_version.as_deref())
                 .or(target.canonical_name.as_deref());
-            println!("{}", build_version.unwrap_or(""));
+            print_value(json_output, "sdkBuildVersion", build_version);
         } else if show_sdk_platform_path {
-            println!("{}", platform.path);
+            print_value(json_output, "platformPath", Some(&platform.path));
         } else if show_sdk_platform_version {
-            println!("{}", platform.version.as_deref().unwrap_or(""));
+            // Like xcrun, fail rather than report the SDK's version when the
+            // platform has none.
+            let Some(version) = platform.version.as_deref() else {
+                eprintln!(
+                    "error: unable to lookup item 'PlatformVersion' in SDK '{}'",
+                    target.path
+                );
+                process::exit(1);
+            };
+            print_value(json_output, "platformVersion", Some(version));
         }
 
         process::exit(0);
@@ -307,20 +340,46 @@ fn main() {
         }
     }
 
-    // Check if tool is an absolute/relative path
-    let executable = if tool_name.contains('/') {
-        let path = std::path::PathBuf::from(&tool_name);
-        if path.is_file() {
-            Some(path)
+    // Find mode: every remaining argument is another tool to look up
+    if find_mode {
+        let names: Vec<&String> = std::iter::once(&tool_name).chain(tool_args.iter()).collect();
+        let mut found = Vec::new();
+        let mut missing = false;
+        for name in names {
+            match resolve_executable(name, &exec_paths) {
+                Some(path) => {
+                    if verbose {
+                        eprintln!("verbose: resolved tool '{name}' to: {}", path.display());
+                    }
+                    found.push((name.as_str(), path));
+                }
+                None => {
+                    eprintln!("error: tool '{name}' not found");
+                    missing = true;
+                }
+            }
+        }
+
+        if json_output {
+            let entries: Vec<serde_json::Value> = found
+                .iter()
+                .map(|(name, path)| json!({ "tool": name, "path": path.display().to_string() }))
+                .collect();
+            if entries.len() == 1 && tool_args.is_empty() {
+                print_json(&entries[0]);
+            } else {
+                print_json(&serde_json::Value::Array(entries));
+            }
         } else {
-            None
+            for (_, path) in &found {
+                println!("{}", path.display());
+            }
         }
-    } else {
-        find_executable(&tool_name, &exec_paths)
-    };
+        process::exit(if missing { 1 } else { 0 });
+    }
 
     // Find the tool
-    let executable = match executable {
+    let executable = match resolve_executable(&tool_name, &exec_paths) {
         Some(e) => e,
         None => {
             eprintln!("error: tool '{tool_name}' not found");
@@ -335,11 +394,6 @@ fn main() {
         );
     }
 
-    if find_mode {
-        println!("{}", executable.display());
-        process::exit(0);
-    }
-
     // Run mode (default)
     if let Some((_, target)) = &target_result {
         env::set_var("SDKROOT", &target.path);
@@ -360,6 +414,8 @@ fn main() {
         }
     }
 
+    apply_toolchain_environment(&toolchains, &executable, log_mode);
+
     // CPATH and LIBRARY_PATH env manipulation
     // When no explicit SDK is requested and SDKROOT was not in env
     if !sdk_explicit && !sdk_from_env {
@@ -402,6 +458,101 @@ fn main() {
     }
 }
 
+/// Apply each toolchain's `OverrideEnvironment` to the child environment.
+/// Toolchains are applied last-to-first so that the first toolchain in the
+/// search order wins when several set the same variable.
+fn apply_toolchain_environment(
+    toolchains: &[&Toolchain],
+    executable: &std::path::Path,
+    log_mode: bool,
+) {
+    for tc in toolchains.iter().rev() {
+        for (name, value) in &tc.override_environment {
+            env::set_var(name, value);
+            if log_mode {
+                println!("env {name}={value} {}", executable.display());
+            }
+        }
+    }
+}
+
+/// Resolve a tool name (or an absolute/relative path) to an executable.
+fn resolve_executable(name: &str, exec_paths: &[String]) -> Option<std::path::PathBuf> {
+    if name.contains('/') {
+        let path = std::path::PathBuf::from(name);
+        if path.is_file() {
+            Some(path)
+        } else {
+            None
+        }
+    } else {
+        find_executable(name, exec_paths)
+    }
+}
+
+fn print_json(value: &serde_json::Value) {
+    match serde_json::to_string_pretty(value) {
+        Ok(s) => println!("{s}"),
+        Err(e) => {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+    }
+}
+
+/// Print a single query result, either bare or as a one-key JSON object.
+fn print_value(json: bool, key: &str, value: Option<&str>) {
+    if json {
+        print_json(&json!({ key: value }));
+    } else {
+        println!("{}", value.unwrap_or(""));
+    }
+}
+
+fn sdk_json(platform: &Platform, target: &Target) -> serde_json::Value {
+    let product = target.product.as_ref();
+    json!({
+        "canonicalName": target.canonical_name,
+        "displayName": target.display_name,
+        "sdkVersion": target.version,
+        "sdkPath": target.path,
+        "buildID": product.and_then(|p| p.build_version.clone()),
+        "productVersion": product.and_then(|p| p.version.clone()),
+        "platform": platform.name,
+        "platformPath": platform.path,
+        "platformVersion": platform.version,
+        "toolchains": target.toolchain_identifiers,
+    })
+}
+
+/// List every SDK of every platform, in the style of `xcodebuild -showsdks`.
+fn print_sdks(manager: &Manager, json: bool) {
+    if json {
+        let entries: Vec<serde_json::Value> = manager
+            .platforms
+            .iter()
+            .flat_map(|platform| platform.targets.iter().map(move |t| sdk_json(platform, t)))
+            .collect();
+        print_json(&serde_json::Value::Array(entries));
+        return;
+    }
+
+    for platform in &manager.platforms {
+        if platform.targets.is_empty() {
+            continue;
+        }
+        let description = platform.description.as_deref().unwrap_or(&platform.name);
+        println!("{description} SDKs:");
+        for target in &platform.targets {
+            let display = target.display_name.as_deref().unwrap_or(&target.bundle_name);
+            let canonical = target.canonical_name.as_deref().unwrap_or("");
+            let version = target.version.as_deref().unwrap_or("");
+            println!("\t{display:<30}\t-sdk {canonical:<24}\t{version:<8}\t{}", target.path);
+        }
+        println!();
+    }
+}
+
 #[allow(dead_code)]
 fn run_tool(
     tool_name: String,
@@ -502,6 +653,8 @@ fn run_tool(
         }
     }
 
+    apply_toolchain_environment(&toolchains, &executable, log_mode);
+
     #[cfg(unix)]
     exec_unix(&executable, &tool_args);
 